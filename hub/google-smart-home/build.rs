@@ -2,9 +2,9 @@ use std::path::Path;
 
 fn main() -> anyhow::Result<()> {
     if cfg!(feature = "generate") {
-        generator::generate(
-            Path::new(&std::env::var("OUT_DIR")?).join("smart-home.rs"),
+        generator::generate_in_build_script(
             Path::new(&std::env::var("CARGO_MANIFEST_DIR")?).join("smart-home-schema"),
+            Path::new(&std::env::var("OUT_DIR")?).join("smart-home.rs"),
         )
     } else {
         Ok(())
@@ -0,0 +1,341 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::{read_dir, File};
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::{generate_to, name_map, Trait};
+
+/// Schemas of a trait, by file
+struct TraitSchemas {
+    attributes: serde_json::Value,
+    states: serde_json::Value,
+    commands: BTreeMap<String, serde_json::Value>,
+}
+
+/// Traits and error codes of a schema tree
+struct Schema {
+    traits: BTreeMap<String, TraitSchemas>,
+    errors: BTreeSet<String>,
+}
+
+fn read_json(path: &Path) -> anyhow::Result<serde_json::Value> {
+    serde_json::from_reader(File::open(path)?).with_context(|| format!("while parsing {:?}", path))
+}
+
+impl Schema {
+    fn read(schema_root: &Path) -> anyhow::Result<Self> {
+        let mut traits = BTreeMap::new();
+        for dir in read_dir(schema_root.join("traits"))? {
+            let scheme_dir = dir?.path();
+            let device_trait: Trait =
+                serde_yaml::from_reader(File::open(scheme_dir.join("index.yaml"))?)
+                    .with_context(|| format!("while parsing {:?}", scheme_dir))?;
+            let read_ref = |name: Option<&Path>| {
+                name.map(|name| read_json(&scheme_dir.join(name)))
+                    .transpose()
+                    .map(Option::unwrap_or_default)
+            };
+
+            let commands = device_trait
+                .commands
+                .iter()
+                .map(|(name, command)| {
+                    Ok((name.clone(), read_ref(Some(command.params.name.as_path()))?))
+                })
+                .collect::<anyhow::Result<_>>()?;
+            traits.insert(
+                device_trait.name.clone(),
+                TraitSchemas {
+                    attributes: read_ref(
+                        device_trait.attributes.as_ref().map(|r| r.name.as_path()),
+                    )?,
+                    states: read_ref(device_trait.states.as_ref().map(|r| r.name.as_path()))?,
+                    commands,
+                },
+            );
+        }
+
+        let errors = read_json(&schema_root.join("platform/errors.schema.json"))?;
+        let errors = errors["enum"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|error| error.as_str().map(str::to_string))
+            .collect();
+
+        Ok(Self { traits, errors })
+    }
+}
+
+/// Changes of the properties of a schema. A schema which is added or removed as a whole
+/// shows all its properties as added or removed.
+#[derive(Debug, Default)]
+pub struct SchemaDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    /// Whether anything other than the properties changed, like `required` or `oneOf`
+    pub constraints_changed: bool,
+}
+
+impl SchemaDiff {
+    fn new(old: &serde_json::Value, new: &serde_json::Value) -> Option<Self> {
+        if old == new {
+            return None;
+        }
+
+        let properties = |schema: &serde_json::Value| {
+            schema["properties"]
+                .as_object()
+                .cloned()
+                .unwrap_or_default()
+        };
+        let (old_properties, new_properties) = (properties(old), properties(new));
+        let mut diff = SchemaDiff::default();
+        for (name, schema) in new_properties.iter() {
+            match old_properties.get(name) {
+                None => diff.added.push(name.clone()),
+                Some(old_schema) if old_schema != schema => diff.changed.push(name.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = old_properties
+            .keys()
+            .filter(|name| !new_properties.contains_key(*name))
+            .cloned()
+            .collect();
+
+        // a missing schema has no constraints either
+        let without_properties = |schema: &serde_json::Value| {
+            let mut schema = schema.as_object().cloned().unwrap_or_default();
+            schema.remove("properties");
+            schema
+        };
+        diff.constraints_changed = without_properties(old) != without_properties(new);
+
+        Some(diff)
+    }
+}
+
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let changes = self
+            .added
+            .iter()
+            .map(|name| format!("+{}", name))
+            .chain(self.removed.iter().map(|name| format!("-{}", name)))
+            .chain(self.changed.iter().map(|name| format!("~{}", name)))
+            .chain(self.constraints_changed.then(|| "~constraints".to_string()))
+            .collect::<Vec<_>>();
+        write!(f, "{}", changes.join(" "))
+    }
+}
+
+#[derive(Debug)]
+pub struct TraitDiff {
+    pub name: String,
+    pub attributes: Option<SchemaDiff>,
+    pub states: Option<SchemaDiff>,
+    pub added_commands: Vec<String>,
+    pub removed_commands: Vec<String>,
+    pub changed_commands: Vec<(String, SchemaDiff)>,
+}
+
+/// Compatibility report of a newer schema tree against an older one
+#[derive(Debug, Default)]
+pub struct SchemaTreeDiff {
+    pub added_traits: Vec<String>,
+    pub removed_traits: Vec<String>,
+    pub changed_traits: Vec<TraitDiff>,
+    pub added_errors: Vec<String>,
+    pub removed_errors: Vec<String>,
+    /// `name_map` entries the newer tree doesn't use
+    pub stale_remaps: Vec<String>,
+    /// Generated names without a `name_map` entry in the newer tree only
+    pub needed_remaps: Vec<String>,
+}
+
+impl SchemaTreeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_traits.is_empty()
+            && self.removed_traits.is_empty()
+            && self.changed_traits.is_empty()
+            && self.added_errors.is_empty()
+            && self.removed_errors.is_empty()
+            && self.stale_remaps.is_empty()
+            && self.needed_remaps.is_empty()
+    }
+}
+
+fn added<'a, T: Ord + Clone + 'a, I: IntoIterator<Item = &'a T>>(
+    old: &BTreeSet<&'a T>,
+    new: I,
+) -> Vec<T> {
+    new.into_iter()
+        .filter(|item| !old.contains(item))
+        .cloned()
+        .collect()
+}
+
+/// Compare the schema trees at `old_root` and `new_root`, generating code of both to find
+/// the changes needed in `name_map`
+pub fn diff<PO: AsRef<Path>, PN: AsRef<Path>>(
+    old_root: PO,
+    new_root: PN,
+) -> anyhow::Result<SchemaTreeDiff> {
+    let (old_root, new_root) = (old_root.as_ref(), new_root.as_ref());
+    let old = Schema::read(old_root).context("while reading the old schema")?;
+    let new = Schema::read(new_root).context("while reading the new schema")?;
+
+    let old_traits = old.traits.keys().collect();
+    let new_traits = new.traits.keys().collect();
+    let mut diff = SchemaTreeDiff {
+        added_traits: added(&old_traits, new.traits.keys()),
+        removed_traits: added(&new_traits, old.traits.keys()),
+        added_errors: added(&old.errors.iter().collect(), new.errors.iter()),
+        removed_errors: added(&new.errors.iter().collect(), old.errors.iter()),
+        ..Default::default()
+    };
+
+    for (name, new_schemas) in new.traits.iter() {
+        let old_schemas = match old.traits.get(name) {
+            Some(old_schemas) => old_schemas,
+            None => continue,
+        };
+        let old_commands = old_schemas.commands.keys().collect();
+        let new_commands = new_schemas.commands.keys().collect();
+        let trait_diff = TraitDiff {
+            name: name.clone(),
+            attributes: SchemaDiff::new(&old_schemas.attributes, &new_schemas.attributes),
+            states: SchemaDiff::new(&old_schemas.states, &new_schemas.states),
+            added_commands: added(&old_commands, new_schemas.commands.keys()),
+            removed_commands: added(&new_commands, old_schemas.commands.keys()),
+            changed_commands: new_schemas
+                .commands
+                .iter()
+                .filter_map(|(command, schema)| {
+                    let old_schema = old_schemas.commands.get(command)?;
+                    Some((command.clone(), SchemaDiff::new(old_schema, schema)?))
+                })
+                .collect(),
+        };
+        if trait_diff.attributes.is_some()
+            || trait_diff.states.is_some()
+            || !trait_diff.added_commands.is_empty()
+            || !trait_diff.removed_commands.is_empty()
+            || !trait_diff.changed_commands.is_empty()
+        {
+            diff.changed_traits.push(trait_diff);
+        }
+    }
+
+    let old_remaps = generate_to(old_root, &mut std::io::sink(), false)
+        .context("while generating of the old schema")?;
+    let new_remaps = generate_to(new_root, &mut std::io::sink(), false)
+        .context("while generating of the new schema")?;
+    let remaps: BTreeSet<_> = name_map().into_keys().map(str::to_string).collect();
+    diff.stale_remaps = added(&new_remaps.used.iter().collect(), remaps.iter());
+    diff.needed_remaps = added(
+        &old_remaps.missing.iter().collect(),
+        new_remaps.missing.iter(),
+    );
+
+    Ok(diff)
+}
+
+impl fmt::Display for SchemaTreeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no changes");
+        }
+
+        for name in self.added_traits.iter() {
+            writeln!(f, "+ trait {}", name)?;
+        }
+        for name in self.removed_traits.iter() {
+            writeln!(f, "- trait {}", name)?;
+        }
+        for trait_diff in self.changed_traits.iter() {
+            writeln!(f, "~ trait {}", &trait_diff.name)?;
+            if let Some(attributes) = &trait_diff.attributes {
+                writeln!(f, "    attributes: {}", attributes)?;
+            }
+            if let Some(states) = &trait_diff.states {
+                writeln!(f, "    states: {}", states)?;
+            }
+            for command in trait_diff.added_commands.iter() {
+                writeln!(f, "    + command {}", command)?;
+            }
+            for command in trait_diff.removed_commands.iter() {
+                writeln!(f, "    - command {}", command)?;
+            }
+            for (command, params) in trait_diff.changed_commands.iter() {
+                writeln!(f, "    ~ command {}: {}", command, params)?;
+            }
+        }
+        for error in self.added_errors.iter() {
+            writeln!(f, "+ error {}", error)?;
+        }
+        for error in self.removed_errors.iter() {
+            writeln!(f, "- error {}", error)?;
+        }
+
+        let name_map = name_map();
+        for name in self.stale_remaps.iter() {
+            writeln!(
+                f,
+                "stale name_map entry {} => {}",
+                name,
+                name_map.get(name.as_str()).unwrap_or(&"?")
+            )?;
+        }
+        for name in self.needed_remaps.iter() {
+            writeln!(f, "needed name_map entry for {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn schema_diff() {
+    let old = serde_json::json!({
+        "properties": { "on": { "type": "boolean" }, "speed": { "type": "string" } },
+    });
+    let new = serde_json::json!({
+        "properties": { "on": { "type": "boolean" }, "speed": { "type": "number" } },
+        "required": ["on"],
+    });
+    assert!(SchemaDiff::new(&old, &old).is_none());
+    let diff = SchemaDiff::new(&old, &new).unwrap();
+    assert_eq!(diff.to_string(), "~speed ~constraints");
+    let diff = SchemaDiff::new(&serde_json::Value::Null, &new).unwrap();
+    assert_eq!(diff.to_string(), "+on +speed ~constraints");
+}
+
+#[test]
+fn diff_fixture_trees() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+    let diff = diff(fixtures.join("old"), fixtures.join("new")).unwrap();
+
+    assert_eq!(diff.added_traits, vec!["action.devices.traits.OnOff"]);
+    assert_eq!(diff.removed_traits, vec!["action.devices.traits.Toggles"]);
+    assert_eq!(diff.changed_traits.len(), 1);
+    let network_control = &diff.changed_traits[0];
+    assert_eq!(network_control.name, "action.devices.traits.NetworkControl");
+    assert_eq!(
+        network_control.attributes.as_ref().unwrap().to_string(),
+        "+speedTest"
+    );
+    assert!(network_control.states.is_none());
+    assert_eq!(diff.added_errors, vec!["deviceJammed"]);
+    assert_eq!(diff.removed_errors, vec!["authExpired"]);
+
+    // entries the old tree used and entries neither tree uses are both stale
+    let stale = |name: &str| diff.stale_remaps.iter().any(|remap| remap == name);
+    assert!(stale("Toggles_currentToggleSettings"));
+    assert!(stale("ColorSetting_color"));
+    assert!(!stale("NetworkControl_networkSettings"));
+    assert_eq!(diff.needed_remaps, vec!["NetworkControl_speedTest"]);
+}
@@ -1,7 +1,8 @@
 use anyhow::Context;
 use fallible_iterator::FallibleIterator;
 use inflector::Inflector;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::{read_dir, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -10,12 +11,14 @@ use std::path::{Path, PathBuf};
 struct Type {
     name: String,
     shortname: String,
+    description: Option<String>,
 }
 
 #[derive(serde::Deserialize)]
 struct Trait {
     name: String,
     shortname: String,
+    description: Option<String>,
     attributes: Option<Ref>,
     states: Option<Ref>,
     #[serde(default)]
@@ -38,20 +41,185 @@ use json_schema::{
     Definitions, Items, JSONSchema, JSONSchemaObject, SimpleTypes, Type as JsonType,
 };
 
+mod diff;
+pub use diff::*;
+
+/// Markdown of a schema description as rustdoc should read it. Lines are unindented, so
+/// indented blocks don't become code blocks, and fenced blocks are marked `text`, so none
+/// is compiled as a doctest.
+fn doc_markdown(description: &str) -> String {
+    let mut fence = None;
+    description
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim();
+            match fence {
+                Some(marker) => {
+                    if trimmed.starts_with(marker) {
+                        fence = None;
+                        trimmed.to_string()
+                    } else {
+                        line.to_string()
+                    }
+                }
+                None => match ["```", "~~~"]
+                    .into_iter()
+                    .find(|marker| trimmed.starts_with(marker))
+                {
+                    Some(marker) => {
+                        fence = Some(marker);
+                        format!("{}text", marker)
+                    }
+                    None => trimmed.to_string(),
+                },
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// `#[doc]` attribute made of the given paragraphs, skipping the missing ones
+fn doc_attribute<'a, I: IntoIterator<Item = Option<&'a str>>>(
+    paragraphs: I,
+) -> proc_macro2::TokenStream {
+    let doc = paragraphs
+        .into_iter()
+        .flatten()
+        .map(doc_markdown)
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if doc.is_empty() {
+        quote::quote!()
+    } else {
+        quote::quote!(#[doc = #doc])
+    }
+}
+
+/// `#[doc]` attribute of a schema, from its description and examples
+fn schema_doc_attribute(
+    summary: Option<&str>,
+    schema: &JSONSchemaObject,
+) -> proc_macro2::TokenStream {
+    let examples = schema
+        .examples
+        .as_ref()
+        .and_then(|examples| serde_json::to_value(examples).ok())
+        .and_then(|examples| match examples {
+            serde_json::Value::Array(examples) if !examples.is_empty() => Some(format!(
+                "Examples: {}",
+                examples
+                    .iter()
+                    .map(|example| format!("`{}`", example))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            _ => None,
+        });
+    doc_attribute([summary, schema.description.as_deref(), examples.as_deref()])
+}
+
+/// Field of a generated struct or struct variant
+struct Field {
+    ident: proc_macro2::Ident,
+    /// Type of the field, without the `Option` of optional fields
+    ty: proc_macro2::TokenStream,
+    optional: bool,
+    tokens: proc_macro2::TokenStream,
+}
+
+/// Entries of `name_map` used while generating, and generated names which have none
+#[derive(Debug, Default)]
+pub struct RemapUsage {
+    pub used: BTreeSet<String>,
+    pub missing: BTreeSet<String>,
+}
+
+/// `new` taking the required fields, with a setter of each optional field. `set_*` setters
+/// take the `Option` itself, for values which may be missing.
+fn constructor(ident: &proc_macro2::Ident, fields: &[Field]) -> proc_macro2::TokenStream {
+    let (required, optional): (Vec<_>, Vec<_>) = fields.iter().partition(|field| !field.optional);
+    let required_idents = required
+        .iter()
+        .map(|field| &field.ident)
+        .collect::<Vec<_>>();
+    let required_types = required.iter().map(|field| &field.ty);
+    let optional_idents = optional
+        .iter()
+        .map(|field| &field.ident)
+        .collect::<Vec<_>>();
+    let optional_types = optional.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let optional_setters = optional_idents
+        .iter()
+        .map(|ident| quote::format_ident!("set_{}", ident));
+    let default = if required.is_empty() {
+        quote::quote! {
+            impl Default for #ident {
+                fn default() -> Self {
+                    Self::new()
+                }
+            }
+        }
+    } else {
+        quote::quote!()
+    };
+
+    quote::quote! {
+        impl #ident {
+            #[allow(clippy::too_many_arguments)]
+            pub fn new(#(#required_idents: #required_types),*) -> Self {
+                Self {
+                    #(#required_idents,)*
+                    #(#optional_idents: None,)*
+                }
+            }
+
+            #(
+                pub fn #optional_idents(mut self, #optional_idents: #optional_types) -> Self {
+                    self.#optional_idents = Some(#optional_idents);
+                    self
+                }
+            )*
+
+            #(
+                pub fn #optional_setters(mut self, #optional_idents: Option<#optional_types>) -> Self {
+                    self.#optional_idents = #optional_idents;
+                    self
+                }
+            )*
+        }
+
+        #default
+    }
+}
+
 pub struct TypeGenerator<'a> {
     name_map: &'a HashMap<&'a str, &'a str>,
     ignore: &'a HashSet<&'a str>,
     nested_types: HashMap<String, proc_macro2::TokenStream>,
+    remaps: RefCell<RemapUsage>,
+    /// Whether to warn of names without a remap through cargo, when run by a build script
+    cargo_warnings: bool,
 }
 impl<'a> TypeGenerator<'a> {
     fn remap_name(&self, gen_name: &str) -> (String, bool) {
         if let Some(name) = self.name_map.get(gen_name) {
+            self.remaps.borrow_mut().used.insert(gen_name.to_string());
             (name.to_string(), true)
         } else {
             (gen_name.replace(".", "_"), false)
         }
     }
 
+    fn missing_remap(&self, name: &str) {
+        let first = self.remaps.borrow_mut().missing.insert(name.to_string());
+        if first && self.cargo_warnings {
+            println!("cargo:warning={} has no remap", name);
+        }
+    }
+
     pub fn generate_untagged_enum<'b, I: Iterator<Item = &'b JSONSchema>>(
         &mut self,
         name: &str,
@@ -61,12 +229,14 @@ impl<'a> TypeGenerator<'a> {
         let ident = quote::format_ident!("{}", name);
         let variants = variants.enumerate().map(|(idx, variant)| {
             if let JSONSchema::JSONSchemaObject(schema) = variant {
+                let doc = doc_attribute([schema.description.as_deref()]);
                 let (variant_name, _) = self.remap_name(&format!("{}_{}", name, idx));
                 let ident = quote::format_ident!("{}", variant_name);
                 if let Ok(struct_body) =
                     self.generate_type_struct(&variant_name, schema, false, definitions)
                 {
                     quote::quote! {
+                        #doc
                         #ident {
                             #(#struct_body,)*
                         }
@@ -77,6 +247,7 @@ impl<'a> TypeGenerator<'a> {
                         .unwrap()
                         .unwrap();
                     quote::quote! {
+                        #doc
                         #ident(#type_name)
                     }
                 }
@@ -112,7 +283,13 @@ impl<'a> TypeGenerator<'a> {
                 name,
                 variants.clone().flat_map(|o| {
                     if let JSONSchema::JSONSchemaObject(schema) = &o {
-                        schema._enum.as_ref().unwrap().iter()
+                        let description = schema.description.as_deref();
+                        schema
+                            ._enum
+                            .as_ref()
+                            .unwrap()
+                            .iter()
+                            .map(move |value| (value, description))
                     } else {
                         panic!("Invalid type");
                     }
@@ -167,10 +344,16 @@ impl<'a> TypeGenerator<'a> {
                 name,
                 variants.map(|o| {
                     if let JSONSchema::JSONSchemaObject(schema) = &o {
-                        if let JSONSchema::JSONSchemaObject(schema) =
+                        if let JSONSchema::JSONSchemaObject(tag_schema) =
                             schema.properties.as_ref().unwrap().get(tag_key).unwrap()
                         {
-                            return schema._enum.as_ref().unwrap().get(0).unwrap();
+                            return (
+                                tag_schema._enum.as_ref().unwrap().get(0).unwrap(),
+                                schema
+                                    .description
+                                    .as_deref()
+                                    .or(tag_schema.description.as_deref()),
+                            );
                         }
                     }
 
@@ -180,6 +363,7 @@ impl<'a> TypeGenerator<'a> {
         } else {
             let variants = variants.flat_map(|o| {
                 if let JSONSchema::JSONSchemaObject(schema) = &o {
+                    let doc = doc_attribute([schema.description.as_deref()]);
                     let properties = schema.properties.as_ref().unwrap();
                     let tag_names = if let JSONSchema::JSONSchemaObject(schema) =
                         properties.get(tag_key).unwrap()
@@ -206,6 +390,7 @@ impl<'a> TypeGenerator<'a> {
                             .into_iter()
                             .map(|(tag_name, tag_ident)| {
                                 quote::quote! {
+                                    #doc
                                     #[serde(rename = #tag_name)]
                                     #tag_ident
                                 }
@@ -226,7 +411,7 @@ impl<'a> TypeGenerator<'a> {
                                     name, representetive_tag_name, field_name
                                 ));
                                 if !has_remap {
-                                    eprintln!("warning={} has no remap", field_type_name);
+                                    self.missing_remap(&field_type_name);
                                 }
                                 let field_ident = quote::format_ident!("{}", k.to_snake_case());
                                 let field_type = if let JSONSchema::JSONSchemaObject(schema) = &v {
@@ -251,6 +436,7 @@ impl<'a> TypeGenerator<'a> {
                             .into_iter()
                             .map(move |(tag_name, tag_ident)| {
                                 quote::quote! {
+                                    #doc
                                     #[serde(rename = #tag_name)]
                                     #tag_ident{#(#fields,)*}
                                 }
@@ -286,6 +472,7 @@ impl<'a> TypeGenerator<'a> {
             fallible_iterator::convert(variants.iter().enumerate().filter_map(|(idx, variant)| {
                 let gen_name = format!("{}_{}", name, idx);
                 if let JSONSchema::JSONSchemaObject(prop) = variant {
+                    let doc = doc_attribute([prop.description.as_deref()]);
                     let (inner_name, has_remap) = self.remap_name(&gen_name);
                     let variant_name = if has_remap {
                         quote::format_ident!("{}", &inner_name)
@@ -297,9 +484,10 @@ impl<'a> TypeGenerator<'a> {
                         self.generate_type_struct(&inner_name, prop, false, definitions)
                     {
                         if !has_remap {
-                            eprintln!("cargo:warning={} has no mapped name", &inner_name)
+                            self.missing_remap(&inner_name);
                         }
                         Some(Ok(quote::quote! {
+                            #doc
                             #variant_name {
                                 #(#variants,)*
                             }
@@ -313,6 +501,7 @@ impl<'a> TypeGenerator<'a> {
                             };
 
                         Some(Ok(quote::quote! {
+                            #doc
                             #variant_name(#type_name)
                         }))
                     }
@@ -338,6 +527,20 @@ impl<'a> TypeGenerator<'a> {
         nested: bool,
         definitions: Option<&Definitions>,
     ) -> anyhow::Result<Vec<proc_macro2::TokenStream>> {
+        Ok(self
+            .generate_type_fields(name, schema, nested, definitions)?
+            .into_iter()
+            .map(|field| field.tokens)
+            .collect())
+    }
+
+    fn generate_type_fields(
+        &mut self,
+        name: &str,
+        schema: &JSONSchemaObject,
+        nested: bool,
+        definitions: Option<&Definitions>,
+    ) -> anyhow::Result<Vec<Field>> {
         let required: HashSet<String> = schema
             .required
             .as_ref()
@@ -359,18 +562,14 @@ impl<'a> TypeGenerator<'a> {
                                 Err(e) => return Some(Err(e)),
                             };
                             if let Some(type_name) = type_name {
-                                let (attr, type_name) = if required.contains(prop_name)
-                                    || type_name.to_string().starts_with("Vec<")
-                                {
-                                    (quote::quote!(), type_name)
-                                } else {
+                                let optional = !(required.contains(prop_name)
+                                    || type_name.to_string().starts_with("Vec<"));
+                                let (attr, field_type) = if optional {
                                     (quote::quote!(#[serde(skip_serializing_if = "Option::is_none")]), quote::quote! { Option<#type_name> })
-                                };
-                                let comment = if let Some(description) = &prop.description {
-                                    quote::quote! { #[doc = #description ] }
                                 } else {
-                                    quote::quote! {}
+                                    (quote::quote!(), type_name.clone())
                                 };
+                                let comment = schema_doc_attribute(None, prop);
                                 let name = quote::format_ident!("{}", prop_name.to_snake_case());
 
                                 let vis = if nested {
@@ -381,11 +580,16 @@ impl<'a> TypeGenerator<'a> {
 
                                 Some(Ok((
                                     prop_name.clone(),
-                                    quote::quote! {
-                                        #comment
-                                        #attr
-                                        #[serde(rename = #prop_name)]
-                                        #vis #name: #type_name
+                                    Field {
+                                        tokens: quote::quote! {
+                                            #comment
+                                            #attr
+                                            #[serde(rename = #prop_name)]
+                                            #vis #name: #field_type
+                                        },
+                                        ident: name,
+                                        ty: type_name,
+                                        optional,
                                     },
                                 )))
                             } else {
@@ -412,19 +616,25 @@ impl<'a> TypeGenerator<'a> {
                         .parse_type_from_json_schema(&name, nested_type, definitions)
                         .unwrap();
                     Some(quote::quote! {
-                        #[serde(flatten)]
-                        additional_values: std::collections::HashMap<String, #nested_type_name>
+                        std::collections::HashMap<String, #nested_type_name>
                     })
                 }
                 JSONSchema::JSONSchemaBoolean(true) => Some(quote::quote! {
-                    #[serde(flatten)]
-                    additional_values: std::collections::HashMap<String, serde_json::Value>
+                    std::collections::HashMap<String, serde_json::Value>
                 }),
                 JSONSchema::JSONSchemaBoolean(false) => None,
             });
 
         if let Some(additional_properties) = additional_properties {
-            props.push(additional_properties);
+            props.push(Field {
+                ident: quote::format_ident!("additional_values"),
+                tokens: quote::quote! {
+                    #[serde(flatten)]
+                    additional_values: #additional_properties
+                },
+                ty: additional_properties,
+                optional: false,
+            });
         }
 
         if let Some(one_of) = schema.one_of.as_ref() {
@@ -433,24 +643,35 @@ impl<'a> TypeGenerator<'a> {
             self.insert_nested_type(&name, has_remap, untagged_enum);
             let ident = quote::format_ident!("_details");
             let type_ident = quote::format_ident!("{}", name);
-            props.push(quote::quote! {
-                #[serde(flatten)]
-                #ident: #type_ident
+            props.push(Field {
+                tokens: quote::quote! {
+                    #[serde(flatten)]
+                    #ident: #type_ident
+                },
+                ident,
+                ty: quote::quote!(#type_ident),
+                optional: false,
             });
         }
 
         Ok(props)
     }
 
-    fn generate_simple_enum<'b, I: Iterator<Item = &'b serde_json::Value> + 'b>(
+    /// Enum of string values, each with its description if any
+    fn generate_simple_enum<
+        'b,
+        I: Iterator<Item = (&'b serde_json::Value, Option<&'b str>)> + 'b,
+    >(
         &mut self,
         name: &str,
         enum_items: I,
     ) -> proc_macro2::TokenStream {
-        let variants = enum_items.map(|variant| {
+        let variants = enum_items.map(|(variant, description)| {
             if let serde_json::Value::String(val) = variant {
                 let ident = quote::format_ident!("{}", &val.to_pascal_case().replace(".", "_"));
+                let doc = doc_attribute([description]);
                 quote::quote! {
+                    #doc
                     #[serde(rename = #val)]
                     #ident
                 }
@@ -489,7 +710,10 @@ impl<'a> TypeGenerator<'a> {
             }
         }
         if let Some(_enum) = &prop._enum {
-            return Ok(Some(self.generate_simple_enum(name, _enum.iter())));
+            return Ok(Some(self.generate_simple_enum(
+                name,
+                _enum.iter().map(|value| (value, None)),
+            )));
         }
         if let Some(one_of) = &prop.one_of {
             let filtered_one_of: Vec<_> = one_of
@@ -506,7 +730,7 @@ impl<'a> TypeGenerator<'a> {
                 .collect();
             let (name, has_remap) = self.remap_name(name);
             if !has_remap {
-                eprintln!("{} has no remap", name);
+                self.missing_remap(&name);
             }
             if filtered_one_of.len() == 1 {
                 if let JSONSchema::JSONSchemaObject(prop) = filtered_one_of.get(0).unwrap() {
@@ -525,7 +749,7 @@ impl<'a> TypeGenerator<'a> {
             let (name, has_remap) = self.remap_name(&name);
 
             if !has_remap {
-                eprintln!("cargo:warning={} has no remap", &name);
+                self.missing_remap(&name);
             }
 
             if let JSONSchema::JSONSchemaObject(items) = items.as_ref() {
@@ -606,13 +830,17 @@ impl<'a> TypeGenerator<'a> {
                     let inner_type = self.generate_type_enum(&name, &one_of, definitions)?;
                     inner_type
                 } else {
-                    let inner_type = self.generate_type_struct(&name, &prop, true, definitions)?;
+                    let fields = self.generate_type_fields(&name, &prop, true, definitions)?;
+                    let inner_type = fields.iter().map(|field| &field.tokens);
                     let ident = quote::format_ident!("{}", name);
+                    let constructor = constructor(&ident, &fields);
                     quote::quote! {
                         #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
                         pub struct #ident {
                             #(#inner_type,)*
                         }
+
+                        #constructor
                     }
                 };
 
@@ -651,14 +879,15 @@ impl<'a> TypeGenerator<'a> {
             self.nested_types.insert(name.to_string(), inner_type);
 
             if !has_remap {
-                eprintln!("cargo:warning={} has no remap", &name);
+                self.missing_remap(&name);
             }
         }
     }
 }
 
-pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> anyhow::Result<()> {
-    let name_map: HashMap<&'static str, &'static str> = [
+/// Names of generated types, from the names built of the path in the schema
+fn name_map() -> HashMap<&'static str, &'static str> {
+    [
         ("ColorSetting_color", "ColorSetting"),
         ("ColorSetting_0", "ColorSettingKelvin"),
         ("ColorSetting_1", "ColorRgb"),
@@ -728,6 +957,10 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
         ),
         ("SensorState_WaterLeak_CurrentSensorState", "WaterLeakSensorState"),
         ("SensorStateSupported_AirQuality_DescriptiveCapabilities", "AirQualitySensorStateSupported"),
+        (
+            "SensorState_AirQuality_CurrentSensorState",
+            "AirQualitySensorStateSupported_availableStates",
+        ),
         (
             "EnergyStorage_descriptiveCapacityRemaining",
             "CapacityRemaning",
@@ -738,10 +971,31 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
         ("OpenClose_openDirection", "OpenDirection"),
     ]
     .into_iter()
-    .collect();
+    .collect()
+}
+
+pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> anyhow::Result<()> {
+    generate_to(schema_root.as_ref(), &mut File::create(out)?, false)?;
+    Ok(())
+}
+
+/// [`generate`] run by a build script, which also warns of the generated names without a
+/// `name_map` entry. Cargo reads the warnings from the stdout of the build script.
+pub fn generate_in_build_script<PS: AsRef<Path>, PO: AsRef<Path>>(
+    schema_root: PS,
+    out: PO,
+) -> anyhow::Result<()> {
+    generate_to(schema_root.as_ref(), &mut File::create(out)?, true)?;
+    Ok(())
+}
 
-    let mut out = File::create(out)?;
-    let schema_root = schema_root.as_ref();
+/// Write the code generated of the schema at `schema_root` to `out`
+fn generate_to<W: Write>(
+    schema_root: &Path,
+    out: &mut W,
+    cargo_warnings: bool,
+) -> anyhow::Result<RemapUsage> {
+    let name_map = name_map();
 
     // generate Type
     {
@@ -756,7 +1010,10 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
                     serde_yaml::from_reader(scheme_file).with_context(|| {
                         format!("while parsing {}", dir.file_name().to_str().unwrap())
                     })?;
-                let comment = &device_type.shortname;
+                let doc = doc_attribute([
+                    Some(device_type.shortname.as_str()),
+                    device_type.description.as_deref(),
+                ]);
                 let type_name = &device_type.name;
                 let ident = quote::format_ident!(
                     "{}",
@@ -769,7 +1026,7 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
                 );
 
                 Ok(quote::quote! {
-                    #[doc = #comment]
+                    #doc
                     #[serde(rename = #type_name)]
                     #ident
                 })
@@ -781,6 +1038,7 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
             out,
             "{}",
             quote::quote! {
+                /// Type of a device, which sets its icon and the words Assistant understands
                 #[derive(Debug, Clone, Copy, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
                 pub enum Type {
                     #(#types,)*
@@ -789,9 +1047,11 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
         )?;
     };
 
+    let mut trait_names = BTreeMap::new();
     let mut attributes = BTreeMap::new();
     let mut states = BTreeMap::new();
     let mut commands = BTreeMap::new();
+    let mut command_shortnames = BTreeMap::new();
 
     // generate Trait
     {
@@ -813,8 +1073,11 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
                     .1
                     .to_pascal_case();
                 let comment = &device_trait.shortname;
+                let doc =
+                    doc_attribute([Some(comment.as_str()), device_trait.description.as_deref()]);
                 let trait_name = &device_trait.name;
                 let ident = quote::format_ident!("{}", &name);
+                trait_names.insert(name.clone(), comment.clone());
 
                 if let Some(attribute) = device_trait.attributes {
                     attributes.insert(name.clone(), scheme_dir.join(attribute.name));
@@ -823,11 +1086,15 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
                     states.insert(name.clone(), scheme_dir.join(state.name));
                 }
                 for (command_name, command) in device_trait.commands.into_iter() {
-                    commands.insert(command_name, scheme_dir.join(command.params.name));
+                    command_shortnames.insert(command_name.clone(), command.shortname);
+                    commands.insert(
+                        command_name,
+                        (name.clone(), scheme_dir.join(command.params.name)),
+                    );
                 }
 
                 Ok(quote::quote! {
-                    #[doc = #comment]
+                    #doc
                     #[serde(rename = #trait_name)]
                     #ident
                 })
@@ -839,6 +1106,7 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
             out,
             "{}",
             quote::quote! {
+                /// Trait of a device, which sets the attributes, states and commands it supports
                 #[derive(Debug, Clone, Copy, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
                 pub enum Trait {
                     #(#traits,)*
@@ -853,8 +1121,14 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
             .into_iter()
             .collect(),
         nested_types: Default::default(),
+        remaps: Default::default(),
+        cargo_warnings,
     };
 
+    let mut state_fields = BTreeMap::new();
+    let mut attribute_fields = BTreeMap::new();
+    let mut command_fields = BTreeMap::new();
+
     // generate State
     {
         let state_variants =
@@ -862,16 +1136,22 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
                 let state_scheme = File::open(path)?;
                 let schema: JSONSchemaObject = serde_json::from_reader(state_scheme)?;
 
-                let struct_content = generator.generate_type_struct(
+                let fields = generator.generate_type_fields(
                     name,
                     &schema,
                     false,
                     schema.definitions.as_ref(),
                 )?;
+                let struct_content = fields.iter().map(|field| &field.tokens);
                 let ident = quote::format_ident!("{}", name);
-                Ok(quote::quote! {
+                let summary = format!("States of {}", trait_names[name]);
+                let doc = schema_doc_attribute(Some(&summary), &schema);
+                let variant = quote::quote! {
+                    #doc
                     #ident{#(#struct_content,)*}
-                })
+                };
+                state_fields.insert(name.clone(), fields);
+                Ok(variant)
             }))
             .collect::<Vec<_>>()?;
 
@@ -879,7 +1159,8 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
             out,
             "{}",
             quote::quote! {
-                #[derive(Debug, Clone, serde::Serialize)]
+                /// State of a trait, reported by QUERY and EXECUTE
+                #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
                 #[serde(rename_all = "snake_case")]
                 pub enum State {
                     #(#state_variants,)*
@@ -901,16 +1182,22 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
                 let state_scheme = File::open(path)?;
                 let schema: JSONSchemaObject = serde_json::from_reader(state_scheme)?;
 
-                let struct_content = generator.generate_type_struct(
+                let fields = generator.generate_type_fields(
                     name,
                     &schema,
                     false,
                     schema.definitions.as_ref(),
                 )?;
+                let struct_content = fields.iter().map(|field| &field.tokens);
                 let ident = quote::format_ident!("{}", name);
-                Ok(quote::quote! {
+                let summary = format!("Attributes of {}", trait_names[name]);
+                let doc = schema_doc_attribute(Some(&summary), &schema);
+                let variant = quote::quote! {
+                    #doc
                     #ident{#(#struct_content,)*}
-                })
+                };
+                attribute_fields.insert(name.clone(), fields);
+                Ok(variant)
             },
         ))
         .collect::<Vec<_>>()?;
@@ -919,13 +1206,14 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
             out,
             "{}",
             quote::quote! {
+                /// Attributes of a trait, reported by SYNC
                 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
                 #[serde(rename_all = "snake_case")]
                 pub enum Attribute {
                     #(#attribute_variants,)*
                 }
 
-                #[derive(Debug, Clone)]
+                #[derive(Debug, Clone, Default)]
                 #[repr(transparent)]
                 pub struct Attributes(
                     pub Vec<Attribute>
@@ -937,22 +1225,27 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
     // generate Command
     {
         let command_variants =
-            fallible_iterator::convert(commands.iter().map(|(full_name, path)| -> anyhow::Result<_> {
+            fallible_iterator::convert(commands.iter().map(|(full_name, (_, path))| -> anyhow::Result<_> {
                 let state_scheme = File::open(path)?;
                 let schema: JSONSchemaObject = serde_json::from_reader(state_scheme)?;
 
                 let name = full_name.rsplit_once(".").unwrap().1.to_pascal_case();
-                let struct_content = generator.generate_type_struct(
+                let fields = generator.generate_type_fields(
                     &name,
                     &schema,
                     false,
                     schema.definitions.as_ref(),
                 )?;
+                let struct_content = fields.iter().map(|field| &field.tokens);
                 let ident = quote::format_ident!("{}", name);
-                Ok(quote::quote! {
+                let doc = schema_doc_attribute(Some(&command_shortnames[full_name]), &schema);
+                let variant = quote::quote! {
+                    #doc
                     #[serde(rename = #full_name)]
                     #ident{#(#struct_content,)*}
-                })
+                };
+                command_fields.insert(full_name.clone(), fields);
+                Ok(variant)
             }))
             .collect::<Vec<_>>()?;
 
@@ -960,6 +1253,7 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
             out,
             "{}",
             quote::quote! {
+                /// Command of a trait with its params, sent by EXECUTE
                 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
                 #[serde(tag = "command", content = "params")]
                 pub enum Command {
@@ -969,12 +1263,255 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
         )?;
     };
 
+    // generate relations of traits to their states, attributes and commands
+    {
+        let trait_ident = |name: &str| quote::format_ident!("{}", name);
+        let state_traits = states.keys().map(|name| {
+            let ident = trait_ident(name);
+            quote::quote! { State::#ident { .. } => Trait::#ident }
+        });
+        let attribute_traits = attributes.keys().map(|name| {
+            let ident = trait_ident(name);
+            quote::quote! { Attribute::#ident { .. } => Trait::#ident }
+        });
+        let command_traits = commands.iter().map(|(full_name, (trait_name, _))| {
+            let ident = trait_ident(&full_name.rsplit_once(".").unwrap().1.to_pascal_case());
+            let trait_ident = trait_ident(trait_name);
+            quote::quote! { Command::#ident { .. } => Trait::#trait_ident }
+        });
+        let required_attributes = fallible_iterator::convert(attributes.iter().map(
+            |(name, path)| -> anyhow::Result<_> {
+                let schema: JSONSchemaObject = serde_json::from_reader(File::open(path)?)?;
+                Ok((name, schema.required.map_or(false, |required| !required.is_empty())))
+            },
+        ))
+        .filter(|(_, required)| Ok(*required))
+        .map(|(name, _)| Ok(trait_ident(name)))
+        .collect::<Vec<_>>()?;
+        let requires_attributes = if required_attributes.is_empty() {
+            quote::quote!(false)
+        } else {
+            quote::quote!(matches!(self, #(Trait::#required_attributes)|*))
+        };
+
+        writeln!(
+            out,
+            "{}",
+            quote::quote! {
+                impl Trait {
+                    /// Whether SYNC has to report the attributes of the trait, as some are required
+                    pub fn requires_attributes(&self) -> bool {
+                        #requires_attributes
+                    }
+                }
+
+                impl State {
+                    /// Trait the state belongs to
+                    pub fn device_trait(&self) -> Trait {
+                        match self {
+                            #(#state_traits,)*
+                        }
+                    }
+                }
+
+                impl Attribute {
+                    /// Trait the attribute belongs to
+                    pub fn device_trait(&self) -> Trait {
+                        match self {
+                            #(#attribute_traits,)*
+                        }
+                    }
+                }
+
+                impl Command {
+                    /// Trait the command belongs to
+                    pub fn device_trait(&self) -> Trait {
+                        match self {
+                            #(#command_traits,)*
+                        }
+                    }
+                }
+            }
+        )?;
+    };
+
+    // generate builders of traits, keeping each trait with its attributes and states, and
+    // commands grouped by trait
+    {
+        let field_idents = |fields: &[Field]| {
+            fields
+                .iter()
+                .map(|field| field.ident.clone())
+                .collect::<Vec<_>>()
+        };
+        // declaration without the serde attributes of `Field::tokens`
+        let field_declaration = |field: &Field| {
+            let ident = &field.ident;
+            let ty = &field.ty;
+            if field.optional {
+                quote::quote! { #ident: Option<#ty> }
+            } else {
+                quote::quote! { #ident: #ty }
+            }
+        };
+        let builder = |ident: &proc_macro2::Ident, comment: &str, fields: &[Field]| {
+            let declarations = fields.iter().map(field_declaration);
+            let constructor = constructor(ident, fields);
+
+            quote::quote! {
+                #[doc = #comment]
+                #[derive(Debug, Clone)]
+                pub struct #ident {
+                    #(pub #declarations,)*
+                }
+
+                #constructor
+            }
+        };
+
+        let trait_builders = trait_names.iter().map(|(name, shortname)| {
+            let trait_ident = quote::format_ident!("{}", name);
+            let ident = quote::format_ident!("{}Trait", name);
+            let comment = format!("{} trait with its SYNC attributes", shortname);
+            let fields = attribute_fields
+                .get(name)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let declaration = builder(&ident, &comment, fields);
+            let into_attribute = if attribute_fields.contains_key(name) {
+                let idents = field_idents(fields);
+                quote::quote! {
+                    Some(Attribute::#trait_ident { #(#idents: self.#idents,)* })
+                }
+            } else {
+                quote::quote!(None)
+            };
+
+            quote::quote! {
+                #declaration
+
+                impl TraitBuilder for #ident {
+                    const TRAIT: Trait = Trait::#trait_ident;
+
+                    fn into_attribute(self) -> Option<Attribute> {
+                        #into_attribute
+                    }
+                }
+            }
+        });
+
+        let state_builders = state_fields.iter().map(|(name, fields)| {
+            let trait_ident = quote::format_ident!("{}", name);
+            let ident = quote::format_ident!("{}State", name);
+            let comment = format!("States of the {} trait", trait_names[name]);
+            let declaration = builder(&ident, &comment, fields);
+            let idents = field_idents(fields);
+
+            quote::quote! {
+                #declaration
+
+                impl From<#ident> for State {
+                    fn from(#ident { #(#idents,)* }: #ident) -> Self {
+                        State::#trait_ident { #(#idents,)* }
+                    }
+                }
+            }
+        });
+
+        let mut trait_commands = BTreeMap::new();
+        for (full_name, (trait_name, _)) in commands.iter() {
+            let ident =
+                quote::format_ident!("{}", full_name.rsplit_once(".").unwrap().1.to_pascal_case());
+            let fields = &command_fields[full_name];
+            trait_commands
+                .entry(trait_name.clone())
+                .or_insert_with(Vec::new)
+                .push((
+                    ident,
+                    field_idents(fields),
+                    fields.iter().map(field_declaration).collect::<Vec<_>>(),
+                ));
+        }
+        let command_enums = trait_commands.iter().map(|(trait_name, variants)| {
+            let ident = quote::format_ident!("{}Command", trait_name);
+            let comment = format!("Commands of the {} trait", trait_names[trait_name]);
+            let variants = variants.iter().map(|(variant, _, declarations)| {
+                quote::quote! { #variant { #(#declarations,)* } }
+            });
+            quote::quote! {
+                #[doc = #comment]
+                #[derive(Debug, Clone)]
+                pub enum #ident {
+                    #(#variants,)*
+                }
+            }
+        });
+        let trait_command_variants = trait_commands.keys().map(|trait_name| {
+            let trait_ident = quote::format_ident!("{}", trait_name);
+            let ident = quote::format_ident!("{}Command", trait_name);
+            quote::quote! { #trait_ident(#ident) }
+        });
+        let trait_command_conversions = trait_commands.iter().flat_map(|(trait_name, variants)| {
+            let trait_ident = quote::format_ident!("{}", trait_name);
+            let ident = quote::format_ident!("{}Command", trait_name);
+            variants.iter().map(move |(variant, idents, _)| {
+                quote::quote! {
+                    Command::#variant { #(#idents,)* } =>
+                        TraitCommand::#trait_ident(#ident::#variant { #(#idents,)* })
+                }
+            })
+        });
+
+        writeln!(
+            out,
+            "{}",
+            quote::quote! {
+                /// Builder of a trait, which declares the trait together with its attributes
+                pub trait TraitBuilder: Sized {
+                    const TRAIT: Trait;
+
+                    /// Attributes of the trait, `None` for traits without attributes
+                    fn into_attribute(self) -> Option<Attribute>;
+
+                    /// Declare the trait and its attributes on a device
+                    fn add_to(self, traits: &mut Vec<Trait>, attributes: &mut Attributes) {
+                        traits.push(Self::TRAIT);
+                        if let Some(attribute) = self.into_attribute() {
+                            attributes.0.push(attribute);
+                        }
+                    }
+                }
+
+                #(#trait_builders)*
+
+                #(#state_builders)*
+
+                #(#command_enums)*
+
+                /// Commands grouped by the trait they belong to
+                #[derive(Debug, Clone)]
+                pub enum TraitCommand {
+                    #(#trait_command_variants,)*
+                }
+
+                impl From<Command> for TraitCommand {
+                    fn from(command: Command) -> Self {
+                        match command {
+                            #(#trait_command_conversions,)*
+                        }
+                    }
+                }
+            }
+        )?;
+    };
+
     //generate errors
     {
         let scheme_path = schema_root.join("platform/errors.schema.json");
 
         let scheme_file = File::open(scheme_path)?;
         let schema: JSONSchemaObject = serde_json::from_reader(scheme_file)?;
+        let doc = schema_doc_attribute(Some("Error code of a device or of the request"), &schema);
         let variants = schema._enum.unwrap();
         let variants = variants
             .iter()
@@ -984,6 +1521,7 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
             out,
             "{}",
             quote::quote! {
+                #doc
                 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
                 #[serde(rename_all = "camelCase")]
                 pub enum Error {
@@ -997,5 +1535,30 @@ pub fn generate<PS: AsRef<Path>, PO: AsRef<Path>>(schema_root: PS, out: PO) -> a
         writeln!(out, "{}", nested_type)?;
     }
 
-    Ok(())
+    Ok(generator.remaps.into_inner())
+}
+
+#[test]
+fn doc_markdown_code_blocks() {
+    assert_eq!(
+        doc_markdown("  Summary\n\n      indented\n\n  ~~~json\n    {}\n  ~~~\n"),
+        "Summary\n\nindented\n\n~~~text\n    {}\n~~~"
+    );
+}
+
+#[test]
+fn doc_of_fixture_tree() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+    let mut out = Vec::new();
+    generate_to(&fixtures.join("new"), &mut out, false).unwrap();
+    let generated = String::from_utf8(out).unwrap();
+
+    // trait description, with its indented block unindented and its fenced block as text
+    assert!(generated.contains(
+        r#"On/Off\n\nTurns the device on or off.\n\nIndented, which is not code\n\n```text\n{\"on\": true}\n```"#
+    ));
+    assert!(!generated.contains("```json"));
+    // property description, and examples of a command
+    assert!(generated.contains(r#"Whether the device is on.\n\n`true` when on"#));
+    assert!(generated.contains(r#"On/Off\n\nExamples: `{\"on\":true}`"#));
 }
@@ -3,8 +3,23 @@ use std::path::Path;
 fn main() -> anyhow::Result<()> {
     let mut args = std::env::args();
     args.next().unwrap();
-    let schema_root = args.next().expect("First argument should be root of schema");
-    let schema_root = Path::new(&schema_root);
+    let first = args
+        .next()
+        .expect("First argument should be root of schema, or diff");
+
+    if first == "diff" {
+        // compatibility report of a newer schema against an older one
+        let old_root = args
+            .next()
+            .expect("Second argument should be root of old schema");
+        let new_root = args
+            .next()
+            .expect("Third argument should be root of new schema");
+        print!("{}", generator::diff(old_root, new_root)?);
+        return Ok(());
+    }
+
+    let schema_root = Path::new(&first);
     let out_path = args.next().expect("Second argument should be output path");
     let out_path = Path::new(&out_path);
 
@@ -8,82 +8,280 @@ mod gen {
 #[cfg(not(feature = "generate"))]
 mod gen;
 pub use gen::*;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod validation;
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Request {
     pub request_id: String,
     pub inputs: Vec<Intent>,
+    /// devices of the user, sent along IDENTIFY requests of Local Home
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<Device>,
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[serde(untagged)]
 pub enum Response {
-    EmptyResponse,
     ResponseWithPayload(ResponseWithPayload),
+    /// `{}`, answering DISCONNECT
+    EmptyResponse {},
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ResponseWithPayload {
     pub request_id: String,
     pub payload: ResponsePayload,
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[serde(untagged)]
 pub enum ResponsePayload {
     Sync(SyncResponse),
     Query(QueryResponse),
     Execute(ExecuteResponse),
+    Identify(IdentifyResponse),
+    ReachableDevices(ReachableDevicesResponse),
+    Error(ErrorResponse),
+}
+
+/// Failure of the whole request
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorResponse {
+    pub error_code: Error,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_string: Option<String>,
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SyncResponse {
     pub agent_user_id: String,
     pub devices: Vec<DeviceWithDetail>,
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct QueryResponse {
-    pub devices: HashMap<String, StateOrError>,
+    #[serde(serialize_with = "serialize_query_devices")]
+    #[serde(deserialize_with = "deserialize_query_devices")]
+    pub devices: HashMap<String, DeviceStatus>,
 }
 
-#[derive(Debug)]
-pub enum StateOrError {
-    State(States),
-    Error(Error),
+/// QUERY reports the states merged into the entry of each device
+fn serialize_query_devices<S>(
+    devices: &HashMap<String, DeviceStatus>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    #[derive(serde::Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct QueryDevice<'a> {
+        status: &'a Status,
+        #[serde(flatten)]
+        states: Option<&'a States>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error_code: Option<&'a Error>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        debug_string: Option<&'a String>,
+    }
+
+    serializer.collect_map(devices.iter().map(|(id, device)| {
+        (
+            id,
+            QueryDevice {
+                status: &device.status,
+                states: device.states.as_ref(),
+                error_code: device.error_code.as_ref(),
+                debug_string: device.debug_string.as_ref(),
+            },
+        )
+    }))
 }
 
-impl serde::Serialize for StateOrError {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        use serde::ser::SerializeMap;
-
-        match self {
-            StateOrError::State(state) => state.serialize(serializer),
-            StateOrError::Error(error) => {
-                let mut map = serializer.serialize_map(Some(2))?;
-                map.serialize_entry("status", &Status::Error)?;
-                map.serialize_entry("errorCode", error)?;
-                map.end()
-            }
+fn deserialize_query_devices<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, DeviceStatus>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct QueryDevice {
+        status: Option<Status>,
+        error_code: Option<Error>,
+        debug_string: Option<String>,
+        #[serde(flatten)]
+        states: States,
+    }
+
+    let devices = HashMap::<String, QueryDevice>::deserialize(deserializer)?;
+    Ok(devices
+        .into_iter()
+        .map(|(id, device)| {
+            let status = DeviceStatus {
+                status: device.status.unwrap_or(Status::Success),
+                states: if device.states.0.is_empty() {
+                    None
+                } else {
+                    Some(device.states)
+                },
+                error_code: device.error_code,
+                debug_string: device.debug_string,
+                challenge_needed: None,
+            };
+            (id, status)
+        })
+        .collect())
+}
+
+/// Result of QUERY or EXECUTE for a device
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceStatus {
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub states: Option<States>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<Error>,
+    /// Shown in the logs of the project, not to the user
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_string: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenge_needed: Option<ChallengeNeeded>,
+}
+
+impl DeviceStatus {
+    fn new(status: Status) -> Self {
+        Self {
+            status,
+            states: None,
+            error_code: None,
+            debug_string: None,
+            challenge_needed: None,
+        }
+    }
+
+    pub fn success(states: States) -> Self {
+        Self {
+            states: Some(states),
+            ..Self::new(Status::Success)
+        }
+    }
+
+    /// The command is accepted, but the device hasn't applied it yet
+    pub fn pending() -> Self {
+        Self::new(Status::Pending)
+    }
+
+    pub fn offline() -> Self {
+        Self {
+            error_code: Some(Error::DeviceOffline),
+            ..Self::new(Status::Offline)
         }
     }
+
+    /// Failure of the device. `deviceOffline` is reported as OFFLINE.
+    pub fn error(error: Error) -> Self {
+        match error {
+            Error::DeviceOffline => Self::offline(),
+            error => Self {
+                error_code: Some(error),
+                ..Self::new(Status::Error)
+            },
+        }
+    }
+
+    /// The device succeeded, but needs attention, like `lowBattery`
+    pub fn exception(states: States, error: Error) -> Self {
+        Self {
+            states: Some(states),
+            error_code: Some(error),
+            ..Self::new(Status::Exceptions)
+        }
+    }
+
+    /// The command needs to be confirmed or protected by a PIN
+    pub fn challenge(challenge_type: ChallengeType) -> Self {
+        Self {
+            error_code: Some(Error::ChallengeNeeded),
+            challenge_needed: Some(ChallengeNeeded {
+                r#type: challenge_type,
+            }),
+            ..Self::new(Status::Error)
+        }
+    }
+
+    pub fn with_debug_string(self, debug_string: impl Into<String>) -> Self {
+        Self {
+            debug_string: Some(debug_string.into()),
+            ..self
+        }
+    }
+}
+
+impl From<States> for DeviceStatus {
+    fn from(states: States) -> Self {
+        Self::success(states)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChallengeType {
+    /// The user has to confirm the command
+    AckNeeded,
+    /// The user has to answer the PIN
+    PinNeeded,
+    /// The PIN answered was wrong, and the user has to answer again
+    ChallengeFailedPinNeeded,
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ChallengeNeeded {
+    pub r#type: ChallengeType,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ExecuteResponse {
     pub commands: Vec<StatusReport>,
 }
 
+impl ExecuteResponse {
+    /// Group devices with the same result into one report, keeping the order they come first
+    pub fn grouped(results: impl IntoIterator<Item = (String, DeviceStatus)>) -> Self {
+        let mut commands: Vec<(serde_json::Value, StatusReport)> = vec![];
+        for (id, result) in results {
+            let key = serde_json::to_value(&result).unwrap_or_default();
+            match commands.iter_mut().find(|(other, _)| other == &key) {
+                Some((_, report)) => report.ids.push(id),
+                None => commands.push((
+                    key,
+                    StatusReport {
+                        ids: vec![id],
+                        result,
+                    },
+                )),
+            }
+        }
+        Self {
+            commands: commands.into_iter().map(|(_, report)| report).collect(),
+        }
+    }
+}
+
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Device {
@@ -93,65 +291,118 @@ pub struct Device {
     pub custom_data: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeviceWithDetail {
     #[serde(flatten)]
     pub basic: Device,
     pub r#type: Type,
     pub traits: Vec<Trait>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Attributes::is_empty")]
     pub attributes: Attributes,
     pub name: DeviceName,
     pub will_report_state: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub room_hint: Option<String>,
-    pub device_info: Option<()>,
-    pub other_device_ids: Vec<()>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_info: Option<DeviceInfo>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub other_device_ids: Vec<OtherDeviceId>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manufacturer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hw_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sw_version: Option<String>,
+}
+
+/// Id of the device for another agent, or for local execution when `agent_id` is omitted
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OtherDeviceId {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    pub device_id: String,
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeviceName {
+    #[serde(default)]
     pub default_names: Vec<String>,
     pub name: String,
+    #[serde(default)]
     pub nicknames: Vec<String>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct QueryRequest {
     pub devices: Vec<Device>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CommandsForDevices {
     pub devices: Vec<Device>,
-    pub execution: Vec<Command>,
+    pub execution: Vec<Execution>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Execution {
+    #[serde(flatten)]
+    pub command: Command,
+    /// Answer of the user to the challenge of a previous attempt
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<Challenge>,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct Challenge {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub ack: bool,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pin: Option<String>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ExecuteRequest {
     pub commands: Vec<CommandsForDevices>,
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum Status {
     Success,
+    /// EXECUTE only
+    Pending,
     Offline,
     Exceptions,
     Error,
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StatusReport {
     pub ids: Vec<String>,
-    pub status: StateOrError,
+    #[serde(flatten)]
+    pub result: DeviceStatus,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(tag = "intent", content = "payload")]
 pub enum Intent {
     #[serde(rename = "action.devices.SYNC")]
@@ -162,6 +413,92 @@ pub enum Intent {
     Disconnect,
     #[serde(rename = "action.devices.EXECUTE")]
     Execute(ExecuteRequest),
+    /// Local Home found a device on the LAN and asks which one it is
+    #[serde(rename = "action.devices.IDENTIFY")]
+    Identify(IdentifyRequest),
+    /// Local Home asks which devices are reachable through a proxy device, like a hub
+    #[serde(rename = "action.devices.REACHABLE_DEVICES")]
+    ReachableDevices(ReachableDevicesRequest),
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentifyRequest {
+    pub device: ScanData,
+    #[serde(default)]
+    pub structure_data: HashMap<String, serde_json::Value>,
+}
+
+/// What the scan of Local Home found, by the scan configured for the project
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanData {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub radio_types: Vec<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mdns_scan_data: Option<serde_json::Value>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp_scan_data: Option<UdpScanData>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upnp_scan_data: Option<serde_json::Value>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct UdpScanData {
+    /// hex encoded payload the device answered with
+    pub data: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct IdentifyResponse {
+    pub device: IdentifiedDevice,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentifiedDevice {
+    pub id: String,
+    /// whether the device controls others, which are then asked by REACHABLE_DEVICES
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_proxy: Option<bool>,
+    /// whether the device is left out of SYNC, like a hub only Local Home talks to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_local_only: Option<bool>,
+    /// matched with `otherDeviceIds` of the devices in SYNC
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_info: Option<DeviceInfo>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReachableDevicesRequest {
+    pub device: ProxyDevice,
+    #[serde(default)]
+    pub structure_data: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyDevice {
+    pub proxy_device: Device,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ReachableDevicesResponse {
+    pub devices: Vec<ReachableDevice>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReachableDevice {
+    /// one of `otherDeviceIds` of the device in SYNC
+    pub verification_id: String,
 }
 
 mod serialize_helper {
@@ -671,6 +1008,89 @@ mod serialize_helper {
     }
 }
 
+mod deserialize_helper {
+    use serde::{
+        de::{value::Error as ProbeError, DeserializeOwned, Error as _, Visitor},
+        Deserialize, Deserializer, Serialize,
+    };
+    use serde_json::{Map, Value};
+
+    /// Names of the variants of the enum `T`, which `T` tells to `deserialize_enum`
+    struct VariantsProbe<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de, 'a> Deserializer<'de> for VariantsProbe<'a> {
+        type Error = ProbeError;
+
+        fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            Err(ProbeError::custom("not an enum"))
+        }
+
+        fn deserialize_enum<V>(
+            self,
+            _name: &'static str,
+            variants: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            *self.0 = variants;
+            Err(ProbeError::custom("probed"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map struct identifier ignored_any
+        }
+    }
+
+    fn variants<T: DeserializeOwned>() -> &'static [&'static str] {
+        let mut variants: &'static [&'static str] = &[];
+        let _ = T::deserialize(VariantsProbe(&mut variants));
+        variants
+    }
+
+    /// Reverse of `serialize_as_merged_struct`. Every variant of `T` is read from the whole
+    /// map, and kept when it has any field to serialize back.
+    pub fn deserialize_merged_struct<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: Serialize + DeserializeOwned,
+        D: Deserializer<'de>,
+    {
+        let map = Map::<String, Value>::deserialize(deserializer)?;
+        let mut items = vec![];
+        let mut unknown_keys: Vec<_> = map.keys().cloned().collect();
+        for variant in variants::<T>() {
+            let tagged = Value::Object(
+                std::iter::once((variant.to_string(), Value::Object(map.clone()))).collect(),
+            );
+            let item = match T::deserialize(tagged) {
+                Ok(item) => item,
+                Err(_) => continue,
+            };
+            let fields = match serde_json::to_value(&item).map_err(D::Error::custom)? {
+                Value::Object(mut tagged) => match tagged.remove(*variant) {
+                    Some(Value::Object(fields)) => fields,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            if !fields.is_empty() {
+                unknown_keys.retain(|key| !fields.contains_key(key));
+                items.push(item);
+            }
+        }
+        if !unknown_keys.is_empty() {
+            log::warn!("unknown fields are ignored - {:?}", unknown_keys);
+        }
+        Ok(items)
+    }
+}
+
 impl serde::Serialize for States {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -680,6 +1100,15 @@ impl serde::Serialize for States {
     }
 }
 
+impl<'de> serde::Deserialize<'de> for States {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserialize_helper::deserialize_merged_struct(deserializer).map(States)
+    }
+}
+
 impl serde::Serialize for Attributes {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -689,6 +1118,21 @@ impl serde::Serialize for Attributes {
     }
 }
 
+impl<'de> serde::Deserialize<'de> for Attributes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserialize_helper::deserialize_merged_struct(deserializer).map(Attributes)
+    }
+}
+
+impl Attributes {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
 #[test]
 fn parse_sync_request() {
     let req: Request = serde_json::from_str(
@@ -760,6 +1204,200 @@ fn parse_execute_request() {
     .unwrap();
 }
 
+#[test]
+fn serialize_device_with_detail() {
+    let device = DeviceWithDetail {
+        basic: Device {
+            id: "plant_led_0".to_string(),
+            custom_data: Default::default(),
+        },
+        r#type: Type::Light,
+        traits: vec![Trait::OnOff],
+        attributes: Attributes(vec![]),
+        name: DeviceName {
+            default_names: vec![],
+            name: "Plant LED".to_string(),
+            nicknames: vec![],
+        },
+        will_report_state: false,
+        room_hint: None,
+        device_info: Some(DeviceInfo {
+            manufacturer: Some("perlmint".to_string()),
+            model: Some("plant_care".to_string()),
+            hw_version: None,
+            sw_version: Some("1.1.0".to_string()),
+        }),
+        other_device_ids: vec![OtherDeviceId {
+            agent_id: None,
+            device_id: "192.168.1.10/0".to_string(),
+        }],
+    };
+
+    let value = serde_json::to_value(&device).unwrap();
+    assert_eq!(
+        value["deviceInfo"],
+        serde_json::json!({
+            "manufacturer": "perlmint",
+            "model": "plant_care",
+            "swVersion": "1.1.0"
+        })
+    );
+    assert_eq!(
+        value["otherDeviceIds"],
+        serde_json::json!([{ "deviceId": "192.168.1.10/0" }])
+    );
+
+    let value = serde_json::to_value(&DeviceWithDetail {
+        device_info: None,
+        other_device_ids: vec![],
+        ..device
+    })
+    .unwrap();
+    assert!(value.get("deviceInfo").is_none());
+    assert!(value.get("otherDeviceIds").is_none());
+}
+
+#[test]
+fn parse_local_requests() {
+    let req: Request = serde_json::from_str(
+        r#"{
+            "requestId": "default-request-id",
+            "inputs": [{
+                "intent": "action.devices.IDENTIFY",
+                "payload": {
+                    "device": {
+                        "udpScanData": { "data": "A3B4C5" }
+                    },
+                    "structureData": {}
+                }
+            }],
+            "devices": [{
+                "id": "plant_led_0",
+                "customData": { "host": "192.168.1.10" }
+            }]
+        }"#,
+    )
+    .unwrap();
+    match &req.inputs[0] {
+        Intent::Identify(identify) => {
+            assert_eq!(
+                identify.device.udp_scan_data.as_ref().unwrap().data,
+                "A3B4C5"
+            )
+        }
+        intent => panic!("unexpected intent {:?}", intent),
+    }
+    assert_eq!(req.devices[0].id, "plant_led_0");
+
+    let req: Request = serde_json::from_str(
+        r#"{
+            "requestId": "default-request-id",
+            "inputs": [{
+                "intent": "action.devices.REACHABLE_DEVICES",
+                "payload": {
+                    "device": {
+                        "proxyDevice": { "id": "hub" }
+                    },
+                    "structureData": {}
+                }
+            }]
+        }"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        &req.inputs[0],
+        Intent::ReachableDevices(ReachableDevicesRequest { device, .. })
+            if device.proxy_device.id == "hub"
+    ));
+}
+
+#[test]
+fn test_challenge() {
+    let req: ExecuteRequest = serde_json::from_str(
+        r#"{
+            "commands": [{
+                "devices": [{ "id": "123" }],
+                "execution": [{
+                    "command": "action.devices.commands.OnOff",
+                    "params": { "on": true },
+                    "challenge": { "pin": "1234" }
+                }]
+            }]
+        }"#,
+    )
+    .unwrap();
+    let execution = &req.commands[0].execution[0];
+    assert!(matches!(execution.command, Command::OnOff { on: true }));
+    let challenge = execution.challenge.as_ref().unwrap();
+    assert_eq!(challenge.pin.as_deref(), Some("1234"));
+    assert!(!challenge.ack);
+
+    assert_eq!(
+        serde_json::to_value(StatusReport {
+            ids: vec!["123".to_string()],
+            result: DeviceStatus::challenge(ChallengeType::ChallengeFailedPinNeeded),
+        })
+        .unwrap(),
+        serde_json::json!({
+            "ids": ["123"],
+            "status": "ERROR",
+            "errorCode": "challengeNeeded",
+            "challengeNeeded": { "type": "challengeFailedPinNeeded" }
+        })
+    );
+}
+
+#[test]
+fn serialize_device_status() {
+    let on = || States(vec![State::OnOff { on: Some(true) }]);
+
+    assert_eq!(
+        serde_json::to_value(ExecuteResponse::grouped(vec![
+            ("1".to_string(), DeviceStatus::success(on())),
+            ("2".to_string(), DeviceStatus::pending()),
+            ("3".to_string(), DeviceStatus::success(on())),
+            ("4".to_string(), DeviceStatus::error(Error::DeviceOffline)),
+            (
+                "5".to_string(),
+                DeviceStatus::exception(on(), Error::LowBattery).with_debug_string("12%"),
+            ),
+        ]))
+        .unwrap(),
+        serde_json::json!({
+            "commands": [
+                { "ids": ["1", "3"], "status": "SUCCESS", "states": { "on": true } },
+                { "ids": ["2"], "status": "PENDING" },
+                { "ids": ["4"], "status": "OFFLINE", "errorCode": "deviceOffline" },
+                {
+                    "ids": ["5"],
+                    "status": "EXCEPTIONS",
+                    "states": { "on": true },
+                    "errorCode": "lowBattery",
+                    "debugString": "12%"
+                }
+            ]
+        })
+    );
+
+    assert_eq!(
+        serde_json::to_value(QueryResponse {
+            devices: [
+                ("1".to_string(), DeviceStatus::success(on())),
+                ("2".to_string(), DeviceStatus::error(Error::DeviceNotFound)),
+            ]
+            .into_iter()
+            .collect(),
+        })
+        .unwrap(),
+        serde_json::json!({
+            "devices": {
+                "1": { "status": "SUCCESS", "on": true },
+                "2": { "status": "ERROR", "errorCode": "deviceNotFound" }
+            }
+        })
+    );
+}
+
 #[test]
 fn serialize_attributes() {
     assert_eq!(serde_json::to_string(&Attributes(vec![])).unwrap(), "{}");
@@ -796,3 +1434,50 @@ fn serialize_states() {
         })
     );
 }
+
+#[test]
+fn build_traits() {
+    let mut traits = vec![];
+    let mut attributes = Attributes::default();
+    OnOffTrait::new()
+        .query_only_on_off(true)
+        .add_to(&mut traits, &mut attributes);
+    TemperatureSettingTrait::new(
+        vec![ThermostatMode::Cool],
+        TemperatureSetting_thermostatTemperatureUnit::C,
+    )
+    .set_thermostat_temperature_range(None)
+    .add_to(&mut traits, &mut attributes);
+    SensorStateTrait::new(vec![SensorStateSupported::Pm25 {
+        numeric_capabilities: PmSensorStateSupported::new()
+            .raw_value_unit(PmSensorStateSupported_rawValueUnit::MicrogramsPerCubicMeter),
+    }])
+    .add_to(&mut traits, &mut attributes);
+    assert_eq!(
+        traits,
+        vec![Trait::OnOff, Trait::TemperatureSetting, Trait::SensorState]
+    );
+    assert_eq!(
+        serde_json::to_value(&attributes).unwrap(),
+        serde_json::json!({
+            "queryOnlyOnOff": true,
+            "availableThermostatModes": ["cool"],
+            "thermostatTemperatureUnit": "C",
+            "sensorStatesSupported": [{
+                "name": "PM2.5",
+                "numericCapabilities": { "rawValueUnit": "MICROGRAMS_PER_CUBIC_METER" },
+            }],
+        })
+    );
+
+    let state: State = OnOffState::new().on(false).into();
+    assert_eq!(state.device_trait(), Trait::OnOff);
+    assert!(matches!(
+        TraitCommand::from(Command::ThermostatSetMode {
+            thermostat_mode: ThermostatMode::Cool
+        }),
+        TraitCommand::TemperatureSetting(TemperatureSettingCommand::ThermostatSetMode {
+            thermostat_mode: ThermostatMode::Cool
+        })
+    ));
+}
@@ -0,0 +1,544 @@
+//! Fulfillment service answering SYNC, QUERY, EXECUTE and DISCONNECT intents, and the
+//! IDENTIFY and REACHABLE_DEVICES intents of Local Home.
+//!
+//! Implement [`Fulfillment`] and route the fulfillment URL to [`service`]. Queries and
+//! executions run concurrently per device, and failures of a device are reported in its
+//! own entry of the response.
+//!
+//! Google sends IDENTIFY and REACHABLE_DEVICES to the Local Home app running on the user's
+//! speaker rather than to the fulfillment URL, so they reach [`service`] only when that app
+//! forwards them. That app answers EXECUTE on the LAN itself, so executions reaching here are
+//! cloud intents.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{http::StatusCode, response::IntoResponse};
+
+use crate::{
+    Challenge, ChallengeType, Command, Device, DeviceStatus, Error, ErrorResponse, ExecuteRequest,
+    ExecuteResponse, IdentifyRequest, IdentifyResponse, Intent, QueryRequest, QueryResponse,
+    ReachableDevicesRequest, ReachableDevicesResponse, Request, Response, ResponsePayload,
+    ResponseWithPayload, SyncResponse,
+};
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+pub enum FulfillmentError {
+    /// Failure of a single device, reported to Google as its `errorCode`
+    Device(Error),
+    /// The request can't be handled, answered with 400 and `protocolError`
+    BadRequest(BoxError),
+    /// Answered with 500 and `unknownError`
+    Internal(BoxError),
+    /// The command has to be confirmed by the user, reported as `challengeNeeded` of the device
+    Challenge(ChallengeType),
+}
+
+impl std::fmt::Debug for FulfillmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FulfillmentError::Device(e) => write!(f, "device error {:?}", e),
+            FulfillmentError::BadRequest(e) => write!(f, "bad request - {}", e),
+            FulfillmentError::Internal(e) => write!(f, "internal error - {}", e),
+            FulfillmentError::Challenge(t) => write!(f, "challenge needed {:?}", t),
+        }
+    }
+}
+
+impl From<Error> for FulfillmentError {
+    fn from(e: Error) -> Self {
+        Self::Device(e)
+    }
+}
+
+/// Hooks of the intents. `query` and `execute` are called once per device, and answer
+/// [`DeviceStatus`], which [`States`](crate::States) convert into as SUCCESS.
+#[async_trait::async_trait]
+pub trait Fulfillment: Send + Sync + 'static {
+    async fn sync(&self) -> Result<SyncResponse, FulfillmentError>;
+    async fn query(&self, device: &Device) -> Result<DeviceStatus, FulfillmentError>;
+    /// `challenge` is the answer of the user, when a previous attempt asked one
+    async fn execute(
+        &self,
+        device: &Device,
+        executions: &[Command],
+        challenge: Option<&Challenge>,
+    ) -> Result<DeviceStatus, FulfillmentError>;
+
+    /// The user unlinked the account
+    async fn disconnect(&self) -> Result<(), FulfillmentError> {
+        Ok(())
+    }
+
+    /// Local Home scanned a device on the LAN. `devices` are the devices of the user.
+    async fn identify(
+        &self,
+        _request: &IdentifyRequest,
+        _devices: &[Device],
+    ) -> Result<IdentifyResponse, FulfillmentError> {
+        Err(Error::NotSupported.into())
+    }
+
+    /// Local Home asks the devices controlled through a proxy device
+    async fn reachable_devices(
+        &self,
+        _request: &ReachableDevicesRequest,
+    ) -> Result<ReachableDevicesResponse, FulfillmentError> {
+        Err(Error::NotSupported.into())
+    }
+}
+
+/// Device failures become the status of the device, other errors fail the whole request
+fn device_status(
+    device_id: &str,
+    result: Result<DeviceStatus, FulfillmentError>,
+) -> Result<DeviceStatus, FulfillmentError> {
+    match result {
+        Ok(status) => Ok(status),
+        Err(FulfillmentError::Device(e)) => {
+            log::warn!("device {} failed - {:?}", device_id, e);
+            Ok(DeviceStatus::error(e))
+        }
+        Err(FulfillmentError::Challenge(challenge_type)) => {
+            log::info!("device {} needs {:?}", device_id, challenge_type);
+            Ok(DeviceStatus::challenge(challenge_type))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+type DeviceTask = tokio::task::JoinHandle<Result<DeviceStatus, FulfillmentError>>;
+
+/// Status of every device. A device whose task panicked is reported as `unknownError`.
+async fn join_devices(
+    tasks: Vec<(String, DeviceTask)>,
+) -> Result<Vec<(String, DeviceStatus)>, FulfillmentError> {
+    let (ids, tasks): (Vec<_>, Vec<_>) = tasks.into_iter().unzip();
+    futures::future::join_all(tasks)
+        .await
+        .into_iter()
+        .zip(ids)
+        .map(|(result, id)| {
+            let status = result.unwrap_or_else(|e| {
+                log::error!("device {} failed - {}", &id, e);
+                Ok(DeviceStatus::error(Error::UnknownError))
+            })?;
+            Ok((id, status))
+        })
+        .collect()
+}
+
+async fn handle_query<F: Fulfillment>(
+    fulfillment: &Arc<F>,
+    query: QueryRequest,
+) -> Result<QueryResponse, FulfillmentError> {
+    let tasks = query
+        .devices
+        .into_iter()
+        .map(|device| {
+            let fulfillment = fulfillment.clone();
+            let id = device.id.clone();
+            let task =
+                tokio::spawn(
+                    async move { device_status(&device.id, fulfillment.query(&device).await) },
+                );
+            (id, task)
+        })
+        .collect();
+
+    Ok(QueryResponse {
+        devices: join_devices(tasks)
+            .await?
+            .into_iter()
+            .collect::<HashMap<_, _>>(),
+    })
+}
+
+async fn handle_execute<F: Fulfillment>(
+    fulfillment: &Arc<F>,
+    execute: ExecuteRequest,
+) -> Result<ExecuteResponse, FulfillmentError> {
+    let tasks = execute
+        .commands
+        .into_iter()
+        .flat_map(|command| {
+            let challenge = Arc::new(
+                command
+                    .execution
+                    .iter()
+                    .find_map(|execution| execution.challenge.clone()),
+            );
+            let execution = Arc::new(
+                command
+                    .execution
+                    .into_iter()
+                    .map(|execution| execution.command)
+                    .collect::<Vec<_>>(),
+            );
+            command.devices.into_iter().map(move |device| {
+                let fulfillment = fulfillment.clone();
+                let execution = execution.clone();
+                let challenge = challenge.clone();
+                let id = device.id.clone();
+                let task = tokio::spawn(async move {
+                    let result = fulfillment
+                        .execute(&device, &execution, challenge.as_ref().as_ref())
+                        .await;
+                    device_status(&device.id, result)
+                });
+                (id, task)
+            })
+        })
+        .collect();
+
+    Ok(ExecuteResponse::grouped(join_devices(tasks).await?))
+}
+
+/// Answer `request`. Only the first input is handled, as Google sends one per request.
+pub async fn fulfill<F: Fulfillment>(
+    fulfillment: &Arc<F>,
+    mut request: Request,
+) -> Result<Response, FulfillmentError> {
+    if request.inputs.is_empty() {
+        return Err(FulfillmentError::BadRequest("request has no inputs".into()));
+    }
+
+    let payload = match request.inputs.remove(0) {
+        Intent::Sync => ResponsePayload::Sync(fulfillment.sync().await?),
+        Intent::Query(query) => ResponsePayload::Query(handle_query(fulfillment, query).await?),
+        Intent::Execute(execute) => {
+            ResponsePayload::Execute(handle_execute(fulfillment, execute).await?)
+        }
+        Intent::Disconnect => {
+            fulfillment.disconnect().await?;
+            return Ok(Response::EmptyResponse {});
+        }
+        Intent::Identify(identify) => {
+            ResponsePayload::Identify(fulfillment.identify(&identify, &request.devices).await?)
+        }
+        Intent::ReachableDevices(reachable) => {
+            ResponsePayload::ReachableDevices(fulfillment.reachable_devices(&reachable).await?)
+        }
+    };
+
+    Ok(Response::ResponseWithPayload(ResponseWithPayload {
+        request_id: request.request_id,
+        payload,
+    }))
+}
+
+/// Response of a failed request, echoing its id when it could be read
+fn error_response(request_id: Option<String>, error: FulfillmentError) -> axum::response::Response {
+    log::error!("fulfillment failed - {:?}", &error);
+    let (code, error_code) = match error {
+        FulfillmentError::BadRequest(_) => (StatusCode::BAD_REQUEST, Error::ProtocolError),
+        FulfillmentError::Device(e) => (StatusCode::INTERNAL_SERVER_ERROR, e),
+        FulfillmentError::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, Error::UnknownError),
+        FulfillmentError::Challenge(_) => {
+            (StatusCode::INTERNAL_SERVER_ERROR, Error::ChallengeNeeded)
+        }
+    };
+
+    (
+        code,
+        axum::Json(ResponseWithPayload {
+            request_id: request_id.unwrap_or_default(),
+            payload: ResponsePayload::Error(ErrorResponse {
+                error_code,
+                debug_string: None,
+            }),
+        }),
+    )
+        .into_response()
+}
+
+/// Parse a fulfillment request body and answer it
+pub async fn handle_body<F: Fulfillment>(
+    fulfillment: &Arc<F>,
+    body: &[u8],
+) -> axum::response::Response {
+    let request: serde_json::Value = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => return error_response(None, FulfillmentError::BadRequest(Box::new(e))),
+    };
+    let request_id = request
+        .get("requestId")
+        .and_then(|o| o.as_str())
+        .map(str::to_string);
+    let request: Request = match serde_json::from_value(request) {
+        Ok(request) => request,
+        Err(e) => return error_response(request_id, FulfillmentError::BadRequest(Box::new(e))),
+    };
+    log::trace!("{:?}", &request);
+
+    match fulfill(fulfillment, request).await {
+        Ok(response) => axum::Json(response).into_response(),
+        Err(e) => error_response(request_id, e),
+    }
+}
+
+/// Service to route the fulfillment URL to, like `.route("/fulfillment", service(fulfillment))`
+pub fn service<F: Fulfillment>(fulfillment: Arc<F>) -> axum::routing::MethodRouter {
+    axum::routing::post(move |body: axum::body::Bytes| async move {
+        handle_body(&fulfillment, &body).await
+    })
+}
+
+/// Requests as Google sends them, for tests of fulfillments
+pub mod testing {
+    use std::collections::HashMap;
+
+    use crate::{
+        Challenge, Command, CommandsForDevices, Device, ExecuteRequest, Execution, IdentifyRequest,
+        Intent, ProxyDevice, QueryRequest, ReachableDevicesRequest, Request, ScanData,
+    };
+
+    fn request(intent: Intent) -> Request {
+        Request {
+            request_id: "ff36a3cc-ec34-11e6-b1a0-64510650abcf".to_string(),
+            inputs: vec![intent],
+            devices: vec![],
+        }
+    }
+
+    fn devices(ids: &[&str]) -> Vec<Device> {
+        ids.iter()
+            .map(|id| Device {
+                id: id.to_string(),
+                custom_data: HashMap::new(),
+            })
+            .collect()
+    }
+
+    pub fn sync_request() -> Request {
+        request(Intent::Sync)
+    }
+
+    pub fn query_request(ids: &[&str]) -> Request {
+        request(Intent::Query(QueryRequest {
+            devices: devices(ids),
+        }))
+    }
+
+    pub fn execute_request(ids: &[&str], execution: Vec<Command>) -> Request {
+        execute_request_with_challenge(ids, execution, None)
+    }
+
+    /// Retry of an execution, answering the challenge asked by the previous attempt
+    pub fn execute_request_with_challenge(
+        ids: &[&str],
+        execution: Vec<Command>,
+        challenge: Option<Challenge>,
+    ) -> Request {
+        request(Intent::Execute(ExecuteRequest {
+            commands: vec![CommandsForDevices {
+                devices: devices(ids),
+                execution: execution
+                    .into_iter()
+                    .map(|command| Execution {
+                        command,
+                        challenge: challenge.clone(),
+                    })
+                    .collect(),
+            }],
+        }))
+    }
+
+    pub fn disconnect_request() -> Request {
+        request(Intent::Disconnect)
+    }
+
+    pub fn identify_request(scan: ScanData, ids: &[&str]) -> Request {
+        Request {
+            devices: devices(ids),
+            ..request(Intent::Identify(IdentifyRequest {
+                device: scan,
+                structure_data: HashMap::new(),
+            }))
+        }
+    }
+
+    pub fn reachable_devices_request(proxy_id: &str) -> Request {
+        request(Intent::ReachableDevices(ReachableDevicesRequest {
+            device: ProxyDevice {
+                proxy_device: devices(&[proxy_id]).remove(0),
+            },
+            structure_data: HashMap::new(),
+        }))
+    }
+}
+
+#[cfg(test)]
+struct Lamp;
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl Fulfillment for Lamp {
+    async fn sync(&self) -> Result<SyncResponse, FulfillmentError> {
+        Ok(SyncResponse {
+            agent_user_id: "user".to_string(),
+            devices: vec![],
+        })
+    }
+
+    async fn query(&self, device: &Device) -> Result<DeviceStatus, FulfillmentError> {
+        match device.id.as_str() {
+            "lamp" => Ok(crate::States(vec![crate::State::OnOff { on: Some(true) }]).into()),
+            "broken" => panic!("broken lamp"),
+            _ => Err(Error::DeviceNotFound.into()),
+        }
+    }
+
+    async fn execute(
+        &self,
+        device: &Device,
+        executions: &[Command],
+        challenge: Option<&Challenge>,
+    ) -> Result<DeviceStatus, FulfillmentError> {
+        match (device.id.as_str(), executions) {
+            ("lamp", [Command::OnOff { on: true }])
+                if !matches!(challenge, Some(Challenge { ack: true, .. })) =>
+            {
+                Err(FulfillmentError::Challenge(ChallengeType::AckNeeded))
+            }
+            ("lamp", [Command::OnOff { on }]) => {
+                Ok(crate::States(vec![crate::State::OnOff { on: Some(*on) }]).into())
+            }
+            ("lamp", _) => Err(FulfillmentError::Internal("unexpected command".into())),
+            _ => Err(Error::DeviceOffline.into()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_fulfill() {
+    let lamp = Arc::new(Lamp);
+    let to_json = |response| serde_json::to_value(&response).unwrap();
+
+    let response = fulfill(
+        &lamp,
+        testing::query_request(&["lamp", "unknown", "broken"]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        to_json(response),
+        serde_json::json!({
+            "requestId": "ff36a3cc-ec34-11e6-b1a0-64510650abcf",
+            "payload": {
+                "devices": {
+                    "lamp": { "status": "SUCCESS", "on": true },
+                    "unknown": { "status": "ERROR", "errorCode": "deviceNotFound" },
+                    "broken": { "status": "ERROR", "errorCode": "unknownError" }
+                }
+            }
+        })
+    );
+
+    let response = fulfill(
+        &lamp,
+        testing::execute_request(
+            &["porch", "lamp", "garage"],
+            vec![Command::OnOff { on: false }],
+        ),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        to_json(response)["payload"]["commands"],
+        serde_json::json!([
+            { "ids": ["porch", "garage"], "status": "OFFLINE", "errorCode": "deviceOffline" },
+            { "ids": ["lamp"], "status": "SUCCESS", "states": { "on": false } }
+        ])
+    );
+
+    let response = fulfill(
+        &lamp,
+        testing::execute_request(&["lamp"], vec![Command::OnOff { on: true }]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        to_json(response)["payload"]["commands"][0]["challengeNeeded"],
+        serde_json::json!({ "type": "ackNeeded" })
+    );
+
+    let response = fulfill(
+        &lamp,
+        testing::execute_request_with_challenge(
+            &["lamp"],
+            vec![Command::OnOff { on: true }],
+            Some(Challenge {
+                ack: true,
+                pin: None,
+            }),
+        ),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        to_json(response)["payload"]["commands"][0]["states"],
+        serde_json::json!({ "on": true })
+    );
+
+    assert!(matches!(
+        fulfill(&lamp, testing::disconnect_request()).await,
+        Ok(Response::EmptyResponse {})
+    ));
+
+    assert!(matches!(
+        fulfill(&lamp, testing::reachable_devices_request("lamp")).await,
+        Err(FulfillmentError::Device(Error::NotSupported))
+    ));
+}
+
+#[tokio::test]
+async fn test_handle_body() {
+    let lamp = Arc::new(Lamp);
+    let body = |response: axum::response::Response| async move {
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+    };
+
+    let response = handle_body(&lamp, br#"{"requestId": "1", "inputs": []}"#).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body(response).await,
+        serde_json::json!({ "requestId": "1", "payload": { "errorCode": "protocolError" } })
+    );
+
+    let response = handle_body(
+        &lamp,
+        serde_json::to_vec(&serde_json::json!({
+            "requestId": "2",
+            "inputs": [{
+                "intent": "action.devices.EXECUTE",
+                "payload": {
+                    "commands": [{
+                        "devices": [{ "id": "lamp" }],
+                        "execution": [{
+                            "command": "action.devices.commands.OnOff",
+                            "params": { "on": true }
+                        }, {
+                            "command": "action.devices.commands.OnOff",
+                            "params": { "on": false }
+                        }]
+                    }]
+                }
+            }]
+        }))
+        .unwrap()
+        .as_slice(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body(response).await["payload"]["errorCode"], "unknownError");
+
+    let response = handle_body(
+        &lamp,
+        br#"{"requestId": "3", "inputs": [{"intent": "action.devices.SYNC"}]}"#,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(response).await["requestId"], "3");
+}
@@ -0,0 +1,192 @@
+//! Checks of SYNC device definitions, and of the states and commands of a device, against
+//! the relations of traits generated from the schema.
+
+use crate::{Command, DeviceWithDetail, States, Trait};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    DuplicateTrait(Trait),
+    /// The trait has required attributes, but the device reports none of it
+    MissingAttributes(Trait),
+    DuplicateAttributes(Trait),
+    /// Attributes of a trait the device doesn't declare
+    UndeclaredAttributes(Trait),
+    /// State of a trait the device doesn't declare
+    UndeclaredState(Trait),
+    /// Command of a trait the device doesn't declare
+    UndeclaredCommand(Trait),
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::DuplicateTrait(t) => write!(f, "{:?} is declared twice", t),
+            ValidationError::MissingAttributes(t) => write!(f, "{:?} has no attributes", t),
+            ValidationError::DuplicateAttributes(t) => {
+                write!(f, "attributes of {:?} are reported twice", t)
+            }
+            ValidationError::UndeclaredAttributes(t) => {
+                write!(f, "attributes of undeclared {:?}", t)
+            }
+            ValidationError::UndeclaredState(t) => write!(f, "state of undeclared {:?}", t),
+            ValidationError::UndeclaredCommand(t) => write!(f, "command of undeclared {:?}", t),
+        }
+    }
+}
+
+/// Every problem found in a device
+#[derive(Debug, Clone)]
+pub struct ValidationErrors {
+    pub device_id: String,
+    pub errors: Vec<ValidationError>,
+}
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid device {}", &self.device_id)?;
+        for (idx, error) in self.errors.iter().enumerate() {
+            write!(f, "{} {}", if idx == 0 { ":" } else { "," }, error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+impl DeviceWithDetail {
+    fn result(&self, errors: Vec<ValidationError>) -> Result<(), ValidationErrors> {
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors {
+                device_id: self.basic.id.clone(),
+                errors,
+            })
+        }
+    }
+
+    /// Check the traits against each other and against the attributes
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = vec![];
+
+        for (idx, device_trait) in self.traits.iter().enumerate() {
+            if self.traits[..idx].contains(device_trait) {
+                errors.push(ValidationError::DuplicateTrait(*device_trait));
+            }
+            if device_trait.requires_attributes()
+                && !self
+                    .attributes
+                    .0
+                    .iter()
+                    .any(|attribute| attribute.device_trait() == *device_trait)
+            {
+                errors.push(ValidationError::MissingAttributes(*device_trait));
+            }
+        }
+
+        for (idx, attribute) in self.attributes.0.iter().enumerate() {
+            let device_trait = attribute.device_trait();
+            if self.attributes.0[..idx]
+                .iter()
+                .any(|other| other.device_trait() == device_trait)
+            {
+                errors.push(ValidationError::DuplicateAttributes(device_trait));
+            }
+            if !self.traits.contains(&device_trait) {
+                errors.push(ValidationError::UndeclaredAttributes(device_trait));
+            }
+        }
+
+        self.result(errors)
+    }
+
+    /// Check that `states` reported by QUERY or EXECUTE belong to declared traits
+    pub fn validate_states(&self, states: &States) -> Result<(), ValidationErrors> {
+        let errors = states
+            .0
+            .iter()
+            .map(|state| state.device_trait())
+            .filter(|device_trait| !self.traits.contains(device_trait))
+            .map(ValidationError::UndeclaredState)
+            .collect();
+        self.result(errors)
+    }
+
+    /// Check that `commands` belong to declared traits
+    pub fn validate_commands(&self, commands: &[Command]) -> Result<(), ValidationErrors> {
+        let errors = commands
+            .iter()
+            .map(|command| command.device_trait())
+            .filter(|device_trait| !self.traits.contains(device_trait))
+            .map(ValidationError::UndeclaredCommand)
+            .collect();
+        self.result(errors)
+    }
+}
+
+#[test]
+fn test_validate() {
+    use crate::{
+        Attribute, Attributes, Device, DeviceName, State,
+        TemperatureSetting_thermostatTemperatureUnit, ThermostatMode, Type,
+    };
+
+    let mut device = DeviceWithDetail {
+        basic: Device {
+            id: "ac".to_string(),
+            custom_data: Default::default(),
+        },
+        r#type: Type::AcUnit,
+        traits: vec![Trait::OnOff, Trait::TemperatureSetting],
+        attributes: Attributes(vec![Attribute::TemperatureSetting {
+            available_thermostat_modes: vec![ThermostatMode::Cool],
+            buffer_range_celsius: None,
+            command_only_temperature_setting: None,
+            query_only_temperature_setting: None,
+            thermostat_temperature_range: None,
+            thermostat_temperature_unit: TemperatureSetting_thermostatTemperatureUnit::C,
+        }]),
+        name: DeviceName {
+            default_names: vec![],
+            name: "AC".to_string(),
+            nicknames: vec![],
+        },
+        will_report_state: false,
+        room_hint: None,
+        device_info: None,
+        other_device_ids: vec![],
+    };
+    device.validate().unwrap();
+    device
+        .validate_states(&States(vec![State::OnOff { on: Some(true) }]))
+        .unwrap();
+    device
+        .validate_commands(&[Command::ThermostatSetMode {
+            thermostat_mode: ThermostatMode::Cool,
+        }])
+        .unwrap();
+
+    let errors = device
+        .validate_states(&States(vec![State::Brightness {
+            brightness: Some(10),
+        }]))
+        .unwrap_err();
+    assert_eq!(
+        errors.errors,
+        vec![ValidationError::UndeclaredState(Trait::Brightness)]
+    );
+    assert_eq!(
+        errors.to_string(),
+        "invalid device ac: state of undeclared Brightness"
+    );
+
+    device.traits = vec![Trait::OnOff, Trait::OnOff, Trait::SensorState];
+    assert_eq!(
+        device.validate().unwrap_err().errors,
+        vec![
+            ValidationError::DuplicateTrait(Trait::OnOff),
+            ValidationError::MissingAttributes(Trait::SensorState),
+            ValidationError::UndeclaredAttributes(Trait::TemperatureSetting),
+        ]
+    );
+}
@@ -0,0 +1,112 @@
+//! Sample payloads of the Smart Home documentation, trimmed to the traits and fields this
+//! crate models, must read and write back unchanged.
+
+use google_smart_home::{Intent, Request, Response, ResponsePayload};
+
+fn sample(name: &str) -> serde_json::Value {
+    let path = format!("{}/tests/samples/{}.json", env!("CARGO_MANIFEST_DIR"), name);
+    serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap()
+}
+
+fn round_trip_request(name: &str) -> Request {
+    let sample = sample(&format!("{}_request", name));
+    let request: Request = serde_json::from_value(sample.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&request).unwrap(), sample, "{}", name);
+    request
+}
+
+fn round_trip_response(name: &str) -> Response {
+    let sample = sample(&format!("{}_response", name));
+    let response: Response = serde_json::from_value(sample.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&response).unwrap(), sample, "{}", name);
+    response
+}
+
+fn payload(response: Response) -> ResponsePayload {
+    match response {
+        Response::ResponseWithPayload(response) => response.payload,
+        response => panic!("unexpected response {:?}", response),
+    }
+}
+
+#[test]
+fn test_sync() {
+    assert!(matches!(
+        round_trip_request("sync").inputs[..],
+        [Intent::Sync]
+    ));
+    match payload(round_trip_response("sync")) {
+        ResponsePayload::Sync(sync) => {
+            assert_eq!(sync.devices.len(), 2);
+            assert_eq!(sync.devices[1].attributes.0.len(), 1);
+        }
+        payload => panic!("unexpected payload {:?}", payload),
+    }
+}
+
+#[test]
+fn test_query() {
+    assert!(matches!(
+        round_trip_request("query").inputs[..],
+        [Intent::Query(_)]
+    ));
+    match payload(round_trip_response("query")) {
+        ResponsePayload::Query(query) => {
+            assert_eq!(query.devices["123"].states.as_ref().unwrap().0.len(), 2)
+        }
+        payload => panic!("unexpected payload {:?}", payload),
+    }
+}
+
+#[test]
+fn test_execute() {
+    match &round_trip_request("execute").inputs[..] {
+        [Intent::Execute(execute)] => {
+            let challenge = execute.commands[0].execution[0].challenge.as_ref();
+            assert_eq!(challenge.unwrap().pin.as_deref(), Some("333222"));
+        }
+        inputs => panic!("unexpected inputs {:?}", inputs),
+    }
+    match payload(round_trip_response("execute")) {
+        ResponsePayload::Execute(execute) => assert_eq!(execute.commands.len(), 4),
+        payload => panic!("unexpected payload {:?}", payload),
+    }
+}
+
+#[test]
+fn test_disconnect() {
+    assert!(matches!(
+        round_trip_request("disconnect").inputs[..],
+        [Intent::Disconnect]
+    ));
+    assert!(matches!(
+        round_trip_response("disconnect"),
+        Response::EmptyResponse {}
+    ));
+}
+
+#[test]
+fn test_local_home() {
+    assert_eq!(round_trip_request("identify").devices.len(), 1);
+    assert!(matches!(
+        payload(round_trip_response("identify")),
+        ResponsePayload::Identify(_)
+    ));
+
+    assert!(matches!(
+        round_trip_request("reachable_devices").inputs[..],
+        [Intent::ReachableDevices(_)]
+    ));
+    match payload(round_trip_response("reachable_devices")) {
+        ResponsePayload::ReachableDevices(reachable) => assert_eq!(reachable.devices.len(), 2),
+        payload => panic!("unexpected payload {:?}", payload),
+    }
+}
+
+#[test]
+fn test_error() {
+    assert!(matches!(
+        payload(round_trip_response("error")),
+        ResponsePayload::Error(_)
+    ));
+}
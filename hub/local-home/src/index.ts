@@ -0,0 +1,158 @@
+/// <reference types="@google/local-home-sdk" />
+// Local Home app of the hub. A plant LED board answers the UDP scan with its address and is the
+// proxy of its lights, which are executed on the LAN through the API the hub calls.
+
+import App = smarthome.App;
+import Constants = smarthome.Constants;
+import DataFlow = smarthome.DataFlow;
+import Execute = smarthome.Execute;
+import Intents = smarthome.Intents;
+import IntentFlow = smarthome.IntentFlow;
+
+const PLANT_LED_PORT = 80;
+
+/** `customData` of a plant LED in SYNC of the hub */
+interface PlantLedCustomData {
+  host: string;
+  /** IPv4 address of the board, as it answers the scan */
+  address: string;
+  internalId: number;
+}
+
+interface Execution {
+  command: string;
+  params?: unknown;
+}
+
+/** The firmware answers the scan with its address in ASCII, which Local Home hex encodes */
+function boardFromScanData(data: string): string | undefined {
+  if (data.length === 0 || data.length % 2 !== 0 || !/^[0-9a-fA-F]*$/.test(data)) {
+    return undefined;
+  }
+  let address = "";
+  for (let i = 0; i < data.length; i += 2) {
+    address += String.fromCharCode(parseInt(data.slice(i, i + 2), 16));
+  }
+  return address;
+}
+
+/** `{address}/{internalId}`, the id of the light in `otherDeviceIds` */
+function localId(customData: PlantLedCustomData): string {
+  return `${customData.address}/${customData.internalId}`;
+}
+
+/** `/lights/{internalId}/power` answers the raw power of the light, 0 to 255 */
+function parsePower(requestId: string, body: string): { on: boolean; brightness: number } {
+  const power = parseInt(body.trim(), 10);
+  if (isNaN(power)) {
+    throw new IntentFlow.HandlerError(requestId, "hardError", `Brightness parse failed - ${body}`);
+  }
+  return { on: power !== 0, brightness: Math.floor((power / 255) * 100) };
+}
+
+async function setPower(
+  requestId: string,
+  deviceId: string,
+  customData: PlantLedCustomData,
+  execution: Execution
+) {
+  const params = (execution.params || {}) as { on?: boolean; brightness?: number };
+  let power: number;
+  switch (execution.command) {
+    case "action.devices.commands.OnOff":
+      power = params.on ? 128 : 0;
+      break;
+    case "action.devices.commands.BrightnessAbsolute":
+      power = Math.floor(((params.brightness || 0) * 255) / 100);
+      break;
+    default:
+      throw new IntentFlow.HandlerError(
+        requestId,
+        "functionNotSupported",
+        `plant led doesn't support ${execution.command}`
+      );
+  }
+
+  const command = new DataFlow.HttpRequestData();
+  command.requestId = requestId;
+  command.deviceId = deviceId;
+  command.port = PLANT_LED_PORT;
+  command.method = Constants.HttpOperation.PUT;
+  command.path = `/lights/${customData.internalId}/power`;
+  command.dataType = "text/plain";
+  command.data = power.toString();
+  const result = (await app.getDeviceManager().send(command)) as DataFlow.HttpResponseData;
+  return parsePower(requestId, String(result.httpResponse.body));
+}
+
+/** Boards are proxies of their lights, and aren't in SYNC themselves */
+async function identifyHandler(
+  request: IntentFlow.IdentifyRequest
+): Promise<IntentFlow.IdentifyResponse> {
+  const scanData = request.inputs[0].payload.device.udpScanData;
+  const board = scanData && boardFromScanData(scanData.data);
+  if (!board) {
+    throw new IntentFlow.HandlerError(request.requestId, "invalid_request", "unknown device is scanned");
+  }
+  return {
+    intent: Intents.IDENTIFY,
+    requestId: request.requestId,
+    payload: {
+      device: {
+        id: board,
+        isProxy: true,
+        isLocalOnly: true,
+      },
+    },
+  };
+}
+
+/** Lights whose board is the scanned one */
+async function reachableDevicesHandler(
+  request: IntentFlow.ReachableDevicesRequest
+): Promise<IntentFlow.ReachableDevicesResponse> {
+  const board = request.inputs[0].payload.device.id;
+  const devices = request.devices
+    .map((device) => device.customData as unknown as PlantLedCustomData | undefined)
+    .filter((customData): customData is PlantLedCustomData => customData?.address === board)
+    .map((customData) => ({ verificationId: localId(customData) }));
+  return {
+    intent: Intents.REACHABLE_DEVICES,
+    requestId: request.requestId,
+    payload: { devices },
+  };
+}
+
+/** Only the first execution of a command is run, as the hub does */
+async function executeHandler(
+  request: IntentFlow.ExecuteRequest
+): Promise<IntentFlow.ExecuteResponse> {
+  const response = new Execute.Response.Builder().setRequestId(request.requestId);
+  const results = request.inputs[0].payload.commands.map((command) =>
+    Promise.all(
+      command.devices.map(async (device) => {
+        const customData = device.customData as unknown as PlantLedCustomData;
+        try {
+          const state = await setPower(request.requestId, device.id, customData, command.execution[0]);
+          response.setSuccessState(device.id, state);
+        } catch (e) {
+          console.error(`execution of ${device.id} failed`, e);
+          response.setErrorState(
+            device.id,
+            e instanceof IntentFlow.HandlerError ? e.errorCode : "hardError"
+          );
+        }
+      })
+    )
+  );
+  await Promise.all(results);
+  return response.build();
+}
+
+const app = new App("1.0.0");
+app
+  .onIdentify(identifyHandler)
+  .onReachableDevices(reachableDevicesHandler)
+  .onExecute(executeHandler)
+  .listen()
+  .then(() => console.log("ready"));
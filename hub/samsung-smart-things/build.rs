@@ -0,0 +1,14 @@
+use std::path::Path;
+
+fn main() -> anyhow::Result<()> {
+    if cfg!(feature = "generate") {
+        let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")?;
+        println!("cargo:rerun-if-changed=capabilities");
+        capability_generator::generate(
+            Path::new(&manifest_dir).join("capabilities"),
+            Path::new(&std::env::var("OUT_DIR")?).join("capabilities.rs"),
+        )
+    } else {
+        Ok(())
+    }
+}
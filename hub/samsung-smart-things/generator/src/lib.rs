@@ -0,0 +1,477 @@
+use anyhow::Context;
+use inflector::Inflector;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{read_dir, File};
+use std::io::Write;
+use std::path::Path;
+
+/// Capability definition as served by `GET /capabilities/{id}/{version}`
+#[derive(serde::Deserialize)]
+struct Capability {
+    id: String,
+    version: u8,
+    name: String,
+    #[serde(default)]
+    attributes: BTreeMap<String, Attribute>,
+    #[serde(default)]
+    commands: BTreeMap<String, Command>,
+}
+
+#[derive(serde::Deserialize)]
+struct Attribute {
+    schema: Schema,
+}
+
+#[derive(serde::Deserialize)]
+struct Command {
+    name: String,
+    #[serde(default)]
+    arguments: Vec<Argument>,
+}
+
+#[derive(serde::Deserialize)]
+struct Argument {
+    name: String,
+    #[serde(default)]
+    optional: bool,
+    schema: Schema,
+}
+
+/// The part of JSON schema capability definitions use
+#[derive(Clone, PartialEq, serde::Deserialize)]
+struct Schema {
+    title: Option<String>,
+    #[serde(rename = "type")]
+    ty: Option<String>,
+    #[serde(rename = "enum")]
+    _enum: Option<Vec<String>>,
+    #[serde(default)]
+    properties: BTreeMap<String, Schema>,
+    #[serde(default)]
+    required: Vec<String>,
+    items: Option<Box<Schema>>,
+}
+
+/// Titles SmartThings puts on plain values, which make poor type names
+const GENERIC_TITLES: &[&str] = &["String", "Number", "Integer", "PositiveInteger", "Percent"];
+
+/// Units, which are symbols rather than names
+const UNIT_NAMES: &[(&str, &str)] = &[
+    ("%", "Percent"),
+    ("C", "Celsius"),
+    ("F", "Fahrenheit"),
+    ("W", "Watt"),
+    ("Wh", "WattHour"),
+    ("kWh", "KilowattHour"),
+    ("mWh", "MilliwattHour"),
+    ("kVAh", "KilovoltAmpereHour"),
+    ("μg/m^3", "MicrogramPerCubicMeter"),
+];
+
+fn variant_name(value: &str) -> anyhow::Result<String> {
+    let name = match UNIT_NAMES.iter().find(|(unit, _)| *unit == value) {
+        Some((_, name)) => name.to_string(),
+        None => value.to_pascal_case(),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(anyhow::anyhow!("no variant name for {}", value));
+    }
+    Ok(name)
+}
+
+/// Types of one capability module
+struct ModuleGenerator {
+    types: Vec<proc_macro2::TokenStream>,
+    names: HashSet<String>,
+    /// already generated enums and structs, by schema
+    generated: Vec<(Schema, String)>,
+}
+
+impl ModuleGenerator {
+    fn new() -> Self {
+        Self {
+            types: Vec::new(),
+            // taken by the types every module has
+            names: ["Status", "Command"]
+                .iter()
+                .map(|o| o.to_string())
+                .collect(),
+            generated: Vec::new(),
+        }
+    }
+
+    fn reserve_name(&mut self, name: &str) -> anyhow::Result<()> {
+        if !self.names.insert(name.to_string()) {
+            return Err(anyhow::anyhow!("{} is generated twice", name));
+        }
+        Ok(())
+    }
+
+    /// Name for a new type, `fallback` if `schema` has no telling title or it is taken
+    fn type_name(&mut self, schema: &Schema, fallback: &str) -> String {
+        let name = match &schema.title {
+            Some(title) if !GENERIC_TITLES.contains(&title.as_str()) => title.to_pascal_case(),
+            _ => fallback.to_string(),
+        };
+        let name = if self.names.contains(&name) {
+            format!("{}Value", name)
+        } else {
+            name
+        };
+        self.names.insert(name.clone());
+        name
+    }
+
+    fn generate_enum(&mut self, name: &str, values: &[String]) -> anyhow::Result<()> {
+        let variants = values
+            .iter()
+            .map(|value| -> anyhow::Result<_> {
+                let ident = quote::format_ident!("{}", variant_name(value)?);
+                Ok(quote::quote! {
+                    #[serde(rename = #value)]
+                    #ident
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let ident = quote::format_ident!("{}", name);
+
+        self.types.push(quote::quote! {
+            #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+            pub enum #ident {
+                #(#variants,)*
+            }
+        });
+        Ok(())
+    }
+
+    fn generate_struct(&mut self, name: &str, schema: &Schema) -> anyhow::Result<()> {
+        let fields = schema
+            .properties
+            .iter()
+            .map(|(field_name, field)| -> anyhow::Result<_> {
+                let ident = quote::format_ident!("{}", field_name.to_snake_case());
+                let ty = self.rust_type(field, &field_name.to_pascal_case())?;
+                Ok(if schema.required.contains(field_name) {
+                    quote::quote! {
+                        #[serde(rename = #field_name)]
+                        pub #ident: #ty
+                    }
+                } else {
+                    quote::quote! {
+                        #[serde(rename = #field_name)]
+                        #[serde(default, skip_serializing_if = "Option::is_none")]
+                        pub #ident: Option<#ty>
+                    }
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let ident = quote::format_ident!("{}", name);
+
+        self.types.push(quote::quote! {
+            #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+            pub struct #ident {
+                #(#fields,)*
+            }
+        });
+        Ok(())
+    }
+
+    /// Type of values matching `schema`, generating it when it is an enum or an object
+    fn rust_type(
+        &mut self,
+        schema: &Schema,
+        fallback_name: &str,
+    ) -> anyhow::Result<proc_macro2::TokenStream> {
+        if let Some(values) = &schema._enum {
+            let name = self.reuse_or_generate(schema, fallback_name, |generator, name| {
+                generator.generate_enum(name, values)
+            })?;
+            let ident = quote::format_ident!("{}", name);
+            return Ok(quote::quote! { #ident });
+        }
+
+        Ok(match schema.ty.as_deref() {
+            Some("string") => quote::quote! { String },
+            Some("integer") => quote::quote! { i64 },
+            Some("number") => quote::quote! { f64 },
+            Some("boolean") => quote::quote! { bool },
+            Some("array") => {
+                let items = schema
+                    .items
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("{} has no items", fallback_name))?;
+                let item = self.rust_type(items, fallback_name)?;
+                quote::quote! { Vec<#item> }
+            }
+            Some("object") if !schema.properties.is_empty() => {
+                let name = self.reuse_or_generate(schema, fallback_name, |generator, name| {
+                    generator.generate_struct(name, schema)
+                })?;
+                let ident = quote::format_ident!("{}", name);
+                quote::quote! { #ident }
+            }
+            _ => quote::quote! { serde_json::Value },
+        })
+    }
+
+    fn reuse_or_generate<F>(
+        &mut self,
+        schema: &Schema,
+        fallback_name: &str,
+        generate: F,
+    ) -> anyhow::Result<String>
+    where
+        F: FnOnce(&mut Self, &str) -> anyhow::Result<()>,
+    {
+        if let Some((_, name)) = self.generated.iter().find(|(o, _)| o == schema) {
+            return Ok(name.clone());
+        }
+        let name = self.type_name(schema, fallback_name);
+        self.generated.push((schema.clone(), name.clone()));
+        generate(self, &name)?;
+        Ok(name)
+    }
+}
+
+fn module_name(capability_id: &str) -> String {
+    capability_id.replace('.', "_").to_snake_case()
+}
+
+fn generate_capability(capability: &Capability) -> anyhow::Result<proc_macro2::TokenStream> {
+    let mut generator = ModuleGenerator::new();
+    let capability_id = &capability.id;
+    let version = capability.version;
+
+    // units are named `Unit` when every attribute of the capability has the same ones
+    let units = capability
+        .attributes
+        .values()
+        .filter_map(|attribute| attribute.schema.properties.get("unit"))
+        .collect::<Vec<_>>();
+    if let [first, rest @ ..] = &units[..] {
+        if rest.iter().all(|unit| unit == first) {
+            generator.reuse_or_generate(first, "Unit", |generator, name| {
+                generator.generate_enum(name, first._enum.as_deref().unwrap_or_default())
+            })?;
+        }
+    }
+
+    // attributes keep their names, other types make way
+    for attribute_name in capability.attributes.keys() {
+        generator.reserve_name(&attribute_name.to_pascal_case())?;
+    }
+
+    let mut status_fields = Vec::new();
+    for (attribute_name, attribute) in &capability.attributes {
+        let name = attribute_name.to_pascal_case();
+
+        let value = attribute
+            .schema
+            .properties
+            .get("value")
+            .ok_or_else(|| anyhow::anyhow!("{} has no value", attribute_name))?;
+        let value_type = generator.rust_type(value, &format!("{}Value", name))?;
+        let unit = match attribute.schema.properties.get("unit") {
+            Some(unit) => {
+                let unit_type = generator.rust_type(unit, &format!("{}Unit", name))?;
+                quote::quote! {
+                    #[serde(default)]
+                    pub unit: Option<#unit_type>,
+                }
+            }
+            None => quote::quote! {},
+        };
+
+        let ident = quote::format_ident!("{}", name);
+        generator.types.push(quote::quote! {
+            #[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+            pub struct #ident {
+                /// `None` until the device reports it
+                #[serde(default)]
+                pub value: Option<#value_type>,
+                #unit
+                #[serde(default)]
+                pub timestamp: Option<String>,
+            }
+        });
+
+        let field = quote::format_ident!("{}", attribute_name.to_snake_case());
+        status_fields.push(quote::quote! {
+            #[serde(rename = #attribute_name)]
+            pub #field: Option<#ident>
+        });
+    }
+
+    let mut variants = Vec::new();
+    let mut names = Vec::new();
+    let mut arguments = Vec::new();
+    let mut builders = Vec::new();
+    for (command_name, command) in &capability.commands {
+        let ident = quote::format_ident!("{}", command_name.to_pascal_case());
+        let builder = quote::format_ident!("{}", command_name.to_snake_case());
+        let wire_name = &command.name;
+        let doc = format!("`{}` command", wire_name);
+
+        let params = command
+            .arguments
+            .iter()
+            .map(|argument| -> anyhow::Result<_> {
+                let ty = generator.rust_type(
+                    &argument.schema,
+                    &format!("{}{}", ident, argument.name.to_pascal_case()),
+                )?;
+                let ty = if argument.optional {
+                    quote::quote! { Option<#ty> }
+                } else {
+                    ty
+                };
+                Ok((
+                    quote::format_ident!("{}", argument.name.to_snake_case()),
+                    ty,
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let param_names = params.iter().map(|(name, _)| name).collect::<Vec<_>>();
+        let param_types = params.iter().map(|(_, ty)| ty).collect::<Vec<_>>();
+
+        if params.is_empty() {
+            variants.push(quote::quote! { #ident });
+            names.push(quote::quote! { Self::#ident => #wire_name });
+            arguments.push(quote::quote! { Self::#ident => vec![] });
+            builders.push(quote::quote! {
+                #[doc = #doc]
+                pub fn #builder() -> Self {
+                    Self::#ident
+                }
+            });
+        } else {
+            variants.push(quote::quote! {
+                #ident { #(#param_names: #param_types,)* }
+            });
+            names.push(quote::quote! { Self::#ident { .. } => #wire_name });
+            arguments.push(quote::quote! {
+                Self::#ident { #(#param_names,)* } => vec![
+                    #(serde_json::to_value(#param_names).unwrap(),)*
+                ]
+            });
+            builders.push(quote::quote! {
+                #[doc = #doc]
+                pub fn #builder(#(#param_names: #param_types,)*) -> Self {
+                    Self::#ident { #(#param_names,)* }
+                }
+            });
+        }
+    }
+
+    let command = if variants.is_empty() {
+        quote::quote! {}
+    } else {
+        quote::quote! {
+            #[derive(Debug, Clone, PartialEq)]
+            pub enum Command {
+                #(#variants,)*
+            }
+
+            impl Command {
+                #(#builders)*
+
+                pub fn name(&self) -> &'static str {
+                    match self {
+                        #(#names,)*
+                    }
+                }
+
+                pub fn arguments(&self) -> Vec<serde_json::Value> {
+                    match self {
+                        #(#arguments,)*
+                    }
+                }
+
+                /// Command for `component`, to send with `ApiClient::commands`
+                pub fn to_component(&self, component: &str) -> crate::RawCommand {
+                    crate::RawCommand {
+                        component: component.to_string(),
+                        capability: ID.to_string(),
+                        command: self.name().to_string(),
+                        arguments: self.arguments(),
+                    }
+                }
+            }
+        }
+    };
+
+    let doc = &capability.name;
+    let module = quote::format_ident!("{}", module_name(capability_id));
+    let types = &generator.types;
+    Ok(quote::quote! {
+        #[doc = #doc]
+        pub mod #module {
+            pub const ID: &str = #capability_id;
+            pub const VERSION: u8 = #version;
+
+            #(#types)*
+
+            #[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+            pub struct Status {
+                #(
+                    #[serde(default)]
+                    #status_fields,
+                )*
+            }
+
+            #command
+        }
+    })
+}
+
+/// Generate a module per capability definition in `definitions`, and `ComponentStatus` of
+/// all of them.
+pub fn generate<PD: AsRef<Path>, PO: AsRef<Path>>(definitions: PD, out: PO) -> anyhow::Result<()> {
+    let mut capabilities = read_dir(definitions.as_ref())?
+        .map(|entry| -> anyhow::Result<_> {
+            let path = entry?.path();
+            let capability: Capability = serde_json::from_reader(File::open(&path)?)
+                .with_context(|| format!("while parsing {}", path.display()))?;
+            Ok(capability)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    capabilities.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let mut modules = HashMap::new();
+    let mut out = File::create(out)?;
+    for capability in &capabilities {
+        if let Some(other) = modules.insert(module_name(&capability.id), &capability.id) {
+            return Err(anyhow::anyhow!(
+                "{} and {} have the same module name",
+                other,
+                &capability.id
+            ));
+        }
+        let module = generate_capability(capability)
+            .with_context(|| format!("while generating {}", &capability.id))?;
+        writeln!(out, "{}", module)?;
+    }
+
+    let fields = capabilities.iter().map(|capability| {
+        let capability_id = &capability.id;
+        let module = quote::format_ident!("{}", module_name(capability_id));
+        quote::quote! {
+            #[serde(rename = #capability_id)]
+            #[serde(default)]
+            pub #module: Option<#module::Status>
+        }
+    });
+    writeln!(
+        out,
+        "{}",
+        quote::quote! {
+            /// Status of a component, by capability. Capabilities without a definition are skipped.
+            #[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+            pub struct ComponentStatus {
+                #(#fields,)*
+            }
+        }
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,14 @@
+use std::path::Path;
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args();
+    args.next().unwrap();
+    let definitions = args
+        .next()
+        .expect("First argument should be directory of capability definitions");
+    let definitions = Path::new(&definitions);
+    let out_path = args.next().expect("Second argument should be output path");
+    let out_path = Path::new(&out_path);
+
+    capability_generator::generate(definitions, out_path)
+}
@@ -0,0 +1,145 @@
+//! Scenes and rules, the automations made in the SmartThings app
+
+use crate::{ApiClient, Error};
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scene {
+    pub scene_id: String,
+    pub scene_name: String,
+    pub location_id: String,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum RuleStatus {
+    Enabled,
+    Disabled,
+}
+
+/// Fields of a rule SmartThings sets by itself, and rejects in updates
+const RULE_READ_ONLY_FIELDS: &[&str] = &[
+    "id",
+    "ownerId",
+    "ownerType",
+    "executionLocation",
+    "creator",
+    "dateCreated",
+    "dateUpdated",
+];
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    pub id: String,
+    pub name: String,
+    /// missing for rules made before rules could be disabled, which are enabled
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<RuleStatus>,
+    /// actions and the rest, kept as is to send them back on update
+    #[serde(flatten)]
+    pub definition: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Rule {
+    pub fn is_enabled(&self) -> bool {
+        self.status != Some(RuleStatus::Disabled)
+    }
+
+    fn update_request(&self) -> serde_json::Value {
+        let mut request = serde_json::to_value(self).unwrap();
+        if let Some(request) = request.as_object_mut() {
+            for field in RULE_READ_ONLY_FIELDS {
+                request.remove(*field);
+            }
+        }
+        request
+    }
+}
+
+impl ApiClient {
+    pub async fn list_scenes(&self, location_id: Option<&str>) -> Result<Vec<Scene>, Error> {
+        let mut url = self.url("scenes")?;
+        if let Some(location_id) = location_id {
+            url.query_pairs_mut().append_pair("locationId", location_id);
+        }
+        self.get_all(url).await
+    }
+
+    pub async fn execute_scene(&self, scene_id: &str) -> Result<(), Error> {
+        let url = self.url(&format!("scenes/{}/execute", scene_id))?;
+        self.send(|client| client.post(url.clone())).await?;
+        Ok(())
+    }
+
+    pub async fn list_rules(&self, location_id: &str) -> Result<Vec<Rule>, Error> {
+        let mut url = self.url("rules")?;
+        url.query_pairs_mut().append_pair("locationId", location_id);
+        self.get_all(url).await
+    }
+
+    pub async fn rule(&self, location_id: &str, rule_id: &str) -> Result<Rule, Error> {
+        let mut url = self.url(&format!("rules/{}", rule_id))?;
+        url.query_pairs_mut().append_pair("locationId", location_id);
+        self.get(url).await
+    }
+
+    /// Enable or disable a rule, leaving the rest of it as is
+    pub async fn set_rule_enabled(
+        &self,
+        location_id: &str,
+        rule_id: &str,
+        enabled: bool,
+    ) -> Result<Rule, Error> {
+        let mut rule = self.rule(location_id, rule_id).await?;
+        rule.status = Some(if enabled {
+            RuleStatus::Enabled
+        } else {
+            RuleStatus::Disabled
+        });
+
+        let mut url = self.url(&format!("rules/{}", rule_id))?;
+        url.query_pairs_mut().append_pair("locationId", location_id);
+        let body = rule.update_request();
+        Ok(self
+            .send(|client| client.put(url.clone()).json(&body))
+            .await?
+            .json()
+            .await?)
+    }
+}
+
+#[test]
+fn test_rule_update_request() {
+    let mut rule: Rule = serde_json::from_value(serde_json::json!({
+        "id": "c4a3c5c4-8b1d-4f57-a3a4-3d0c2c1f2a6b",
+        "name": "Cool down at night",
+        "actions": [
+            {
+                "every": {
+                    "specific": { "reference": "Sunset", "offset": { "value": { "integer": 0 }, "unit": "Minute" } },
+                    "actions": []
+                }
+            }
+        ],
+        "timeZoneId": "Asia/Seoul",
+        "ownerId": "6b3d1909-1e1c-43ec-adc2-5f941de4fbf9",
+        "ownerType": "Location",
+        "executionLocation": "Cloud",
+        "dateCreated": "2022-06-01T10:00:11.000+00:00",
+        "dateUpdated": "2022-06-01T10:00:11.000+00:00"
+    }))
+    .unwrap();
+    assert!(rule.is_enabled());
+
+    rule.status = Some(RuleStatus::Disabled);
+    assert!(!rule.is_enabled());
+    let request = rule.update_request();
+    assert_eq!(request["status"], "Disabled");
+    assert_eq!(request["name"], "Cool down at night");
+    assert_eq!(request["timeZoneId"], "Asia/Seoul");
+    assert!(request["actions"][0]["every"].is_object());
+    assert!(request.get("id").is_none());
+    assert!(request.get("ownerId").is_none());
+    assert!(request.get("dateUpdated").is_none());
+}
@@ -0,0 +1,59 @@
+use samsung_smart_things as samsung;
+use std::io::BufRead;
+
+const DEFAULT_SCOPES: &[&str] = &[
+    "r:devices:*",
+    "x:devices:*",
+    "r:locations:*",
+    "r:scenes:*",
+    "x:scenes:*",
+];
+
+/// One-time authorization of a SmartThings OAuth app.
+///
+/// Usage: smart-things-authorize <state file> [scope...]
+/// with SMART_THINGS_CLIENT_ID, SMART_THINGS_CLIENT_SECRET and SMART_THINGS_REDIRECT_URI set
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let mut args = std::env::args();
+    args.next().unwrap();
+    let state_path = args
+        .next()
+        .expect("First argument should be path of state file");
+    let scopes: Vec<String> = args.collect();
+    let scopes: Vec<&str> = if scopes.is_empty() {
+        DEFAULT_SCOPES.to_vec()
+    } else {
+        scopes.iter().map(String::as_str).collect()
+    };
+
+    let app = samsung::OAuthApp {
+        client_id: std::env::var("SMART_THINGS_CLIENT_ID")?,
+        client_secret: std::env::var("SMART_THINGS_CLIENT_SECRET")?,
+        redirect_uri: std::env::var("SMART_THINGS_REDIRECT_URI")?,
+    };
+
+    println!("Open the URL below and authorize the app:");
+    println!("{}", app.authorize_url(&scopes));
+    println!("Then paste the URL you are redirected to (or just its `code` parameter):");
+
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    let line = line.trim();
+    let code = match reqwest::Url::parse(line) {
+        Ok(url) => url
+            .query_pairs()
+            .find(|(key, _)| key == "code")
+            .map(|(_, code)| code.into_owned())
+            .ok_or_else(|| anyhow::anyhow!("Redirected URL has no code"))?,
+        Err(_) => line.to_string(),
+    };
+
+    let tokens = app.exchange_code(&reqwest::Client::new(), &code).await?;
+    tokens.save(&state_path).await?;
+    println!("Tokens are saved to {}", &state_path);
+
+    Ok(())
+}
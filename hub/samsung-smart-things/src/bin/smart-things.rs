@@ -0,0 +1,139 @@
+use clap::Parser;
+use samsung_smart_things as samsung;
+
+/// Look into SmartThings devices and send them commands.
+///
+/// Built with the `cli` feature: cargo run --features cli --bin smart-things -- devices
+#[derive(Parser)]
+#[clap(name = "smart-things")]
+struct Args {
+    /// personal access token from https://account.smartthings.com/tokens
+    #[clap(long, env = "SMART_THINGS_TOKEN", hide_env_values = true)]
+    token: String,
+    /// root of the API, like the URL of a mock server
+    #[clap(
+        long,
+        env = "SMART_THINGS_API_URL",
+        default_value = "https://api.smartthings.com/v1/"
+    )]
+    api_url: reqwest::Url,
+    #[clap(subcommand)]
+    action: Action,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// List devices with their components
+    Devices {
+        #[clap(long)]
+        location: Option<String>,
+    },
+    /// Print the descriptor of a device
+    Descriptor { device_id: String },
+    /// Print the status of a device, or of one of its components or capabilities
+    Status {
+        device_id: String,
+        #[clap(long)]
+        component: Option<String>,
+        #[clap(long, requires = "component")]
+        capability: Option<String>,
+    },
+    /// Send a command, like `command <device id> thermostatCoolingSetpoint setCoolingSetpoint 22`
+    Command {
+        device_id: String,
+        capability: String,
+        command: String,
+        /// numbers, booleans and JSON are sent as such and anything else as a string.
+        /// `str:` in front forces a string, like `str:22`.
+        #[clap(value_parser = parse_argument)]
+        arguments: Vec<serde_json::Value>,
+        #[clap(long, default_value = "main")]
+        component: String,
+    },
+}
+
+fn parse_argument(argument: &str) -> Result<serde_json::Value, String> {
+    Ok(match argument.strip_prefix("str:") {
+        Some(string) => serde_json::Value::String(string.to_string()),
+        None => serde_json::from_str(argument)
+            .unwrap_or_else(|_| serde_json::Value::String(argument.to_string())),
+    })
+}
+
+fn print_json(value: &serde_json::Value) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let args = Args::parse();
+    let client = samsung::ApiClientBuilder::with_token(&args.token)
+        .api_url(args.api_url)
+        .user_agent("smart-things-cli")
+        .build()?;
+
+    match args.action {
+        Action::Devices { location } => {
+            for device in client.list_devices(location.as_deref()).await? {
+                let components: Vec<_> = device
+                    .components
+                    .iter()
+                    .map(|component| component.id.as_str())
+                    .collect();
+                println!(
+                    "{}\t{}\t{}",
+                    device.device_id,
+                    device.label,
+                    components.join(",")
+                );
+            }
+        }
+        Action::Descriptor { device_id } => {
+            print_json(&client.get_json(&format!("devices/{}", device_id)).await?)?;
+        }
+        Action::Status {
+            device_id,
+            component,
+            capability,
+        } => {
+            let path = match (component, capability) {
+                (Some(component), Some(capability)) => format!(
+                    "devices/{}/components/{}/capabilities/{}/status",
+                    device_id, component, capability
+                ),
+                (Some(component), None) => {
+                    format!("devices/{}/components/{}/status", device_id, component)
+                }
+                (None, _) => format!("devices/{}/status", device_id),
+            };
+            print_json(&client.get_json(&path).await?)?;
+        }
+        Action::Command {
+            device_id,
+            capability,
+            command,
+            arguments,
+            component,
+        } => {
+            let results = client
+                .commands(
+                    &device_id,
+                    vec![samsung::RawCommand {
+                        component,
+                        capability,
+                        command,
+                        arguments,
+                    }],
+                )
+                .await?;
+            for result in results {
+                println!("{}\t{:?}", result.id, result.status);
+            }
+        }
+    }
+
+    Ok(())
+}
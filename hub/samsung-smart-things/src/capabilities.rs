@@ -0,0 +1,728 @@
+#[doc = "Air Conditioner Fan Mode"]
+pub mod air_conditioner_fan_mode {
+    pub const ID: &str = "airConditionerFanMode";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct AvailableAcFanModes {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<Vec<String>>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct FanMode {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<String>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct SupportedAcFanModes {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<Vec<String>>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "availableAcFanModes")]
+        pub available_ac_fan_modes: Option<AvailableAcFanModes>,
+        #[serde(default)]
+        #[serde(rename = "fanMode")]
+        pub fan_mode: Option<FanMode>,
+        #[serde(default)]
+        #[serde(rename = "supportedAcFanModes")]
+        pub supported_ac_fan_modes: Option<SupportedAcFanModes>,
+    }
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Command {
+        SetFanMode { fan_mode: String },
+    }
+    impl Command {
+        #[doc = "`setFanMode` command"]
+        pub fn set_fan_mode(fan_mode: String) -> Self {
+            Self::SetFanMode { fan_mode }
+        }
+        pub fn name(&self) -> &'static str {
+            match self {
+                Self::SetFanMode { .. } => "setFanMode",
+            }
+        }
+        pub fn arguments(&self) -> Vec<serde_json::Value> {
+            match self {
+                Self::SetFanMode { fan_mode } => vec![serde_json::to_value(fan_mode).unwrap()],
+            }
+        }
+        #[doc = r" Command for `component`, to send with `ApiClient::commands`"]
+        pub fn to_component(&self, component: &str) -> crate::RawCommand {
+            crate::RawCommand {
+                component: component.to_string(),
+                capability: ID.to_string(),
+                command: self.name().to_string(),
+                arguments: self.arguments(),
+            }
+        }
+    }
+}
+#[doc = "Air Conditioner Mode"]
+pub mod air_conditioner_mode {
+    pub const ID: &str = "airConditionerMode";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct AirConditionerMode {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<String>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct AvailableAcModes {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<Vec<String>>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct SupportedAcModes {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<Vec<String>>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "airConditionerMode")]
+        pub air_conditioner_mode: Option<AirConditionerMode>,
+        #[serde(default)]
+        #[serde(rename = "availableAcModes")]
+        pub available_ac_modes: Option<AvailableAcModes>,
+        #[serde(default)]
+        #[serde(rename = "supportedAcModes")]
+        pub supported_ac_modes: Option<SupportedAcModes>,
+    }
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Command {
+        SetAirConditionerMode { mode: String },
+    }
+    impl Command {
+        #[doc = "`setAirConditionerMode` command"]
+        pub fn set_air_conditioner_mode(mode: String) -> Self {
+            Self::SetAirConditionerMode { mode }
+        }
+        pub fn name(&self) -> &'static str {
+            match self {
+                Self::SetAirConditionerMode { .. } => "setAirConditionerMode",
+            }
+        }
+        pub fn arguments(&self) -> Vec<serde_json::Value> {
+            match self {
+                Self::SetAirConditionerMode { mode } => vec![serde_json::to_value(mode).unwrap()],
+            }
+        }
+        #[doc = r" Command for `component`, to send with `ApiClient::commands`"]
+        pub fn to_component(&self, component: &str) -> crate::RawCommand {
+            crate::RawCommand {
+                component: component.to_string(),
+                capability: ID.to_string(),
+                command: self.name().to_string(),
+                arguments: self.arguments(),
+            }
+        }
+    }
+}
+#[doc = "Air Quality Sensor"]
+pub mod air_quality_sensor {
+    pub const ID: &str = "airQualitySensor";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde :: Serialize, serde :: Deserialize)]
+    pub enum Unit {
+        #[serde(rename = "CAQI")]
+        Caqi,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct AirQuality {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<i64>,
+        #[serde(default)]
+        pub unit: Option<Unit>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "airQuality")]
+        pub air_quality: Option<AirQuality>,
+    }
+}
+#[doc = "Thermostat Setpoint Control"]
+pub mod custom_thermostat_setpoint_control {
+    pub const ID: &str = "custom.thermostatSetpointControl";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde :: Serialize, serde :: Deserialize)]
+    pub enum Unit {
+        #[serde(rename = "F")]
+        Fahrenheit,
+        #[serde(rename = "C")]
+        Celsius,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct MaximumSetpoint {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<f64>,
+        #[serde(default)]
+        pub unit: Option<Unit>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct MinimumSetpoint {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<f64>,
+        #[serde(default)]
+        pub unit: Option<Unit>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "maximumSetpoint")]
+        pub maximum_setpoint: Option<MaximumSetpoint>,
+        #[serde(default)]
+        #[serde(rename = "minimumSetpoint")]
+        pub minimum_setpoint: Option<MinimumSetpoint>,
+    }
+}
+#[doc = "Dust Sensor"]
+pub mod dust_sensor {
+    pub const ID: &str = "dustSensor";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde :: Serialize, serde :: Deserialize)]
+    pub enum Unit {
+        #[serde(rename = "μg/m^3")]
+        MicrogramPerCubicMeter,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct DustLevel {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<i64>,
+        #[serde(default)]
+        pub unit: Option<Unit>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct FineDustLevel {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<i64>,
+        #[serde(default)]
+        pub unit: Option<Unit>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "dustLevel")]
+        pub dust_level: Option<DustLevel>,
+        #[serde(default)]
+        #[serde(rename = "fineDustLevel")]
+        pub fine_dust_level: Option<FineDustLevel>,
+    }
+}
+#[doc = "Energy Meter"]
+pub mod energy_meter {
+    pub const ID: &str = "energyMeter";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde :: Serialize, serde :: Deserialize)]
+    pub enum Unit {
+        #[serde(rename = "Wh")]
+        WattHour,
+        #[serde(rename = "kWh")]
+        KilowattHour,
+        #[serde(rename = "mWh")]
+        MilliwattHour,
+        #[serde(rename = "kVAh")]
+        KilovoltAmpereHour,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct Energy {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<f64>,
+        #[serde(default)]
+        pub unit: Option<Unit>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "energy")]
+        pub energy: Option<Energy>,
+    }
+}
+#[doc = "Fan Oscillation Mode"]
+pub mod fan_oscillation_mode {
+    pub const ID: &str = "fanOscillationMode";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde :: Serialize, serde :: Deserialize)]
+    pub enum FanOscillationModeValue {
+        #[serde(rename = "off")]
+        Off,
+        #[serde(rename = "individual")]
+        Individual,
+        #[serde(rename = "fixed")]
+        Fixed,
+        #[serde(rename = "vertical")]
+        Vertical,
+        #[serde(rename = "horizontal")]
+        Horizontal,
+        #[serde(rename = "all")]
+        All,
+        #[serde(rename = "indirect")]
+        Indirect,
+        #[serde(rename = "direct")]
+        Direct,
+        #[serde(rename = "fixedCenter")]
+        FixedCenter,
+        #[serde(rename = "fixedLeft")]
+        FixedLeft,
+        #[serde(rename = "fixedRight")]
+        FixedRight,
+        #[serde(rename = "far")]
+        Far,
+        #[serde(rename = "wide")]
+        Wide,
+        #[serde(rename = "mid")]
+        Mid,
+        #[serde(rename = "spot")]
+        Spot,
+        #[serde(rename = "swing")]
+        Swing,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct AvailableFanOscillationModes {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<Vec<FanOscillationModeValue>>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct FanOscillationMode {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<FanOscillationModeValue>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct SupportedFanOscillationModes {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<Vec<FanOscillationModeValue>>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "availableFanOscillationModes")]
+        pub available_fan_oscillation_modes: Option<AvailableFanOscillationModes>,
+        #[serde(default)]
+        #[serde(rename = "fanOscillationMode")]
+        pub fan_oscillation_mode: Option<FanOscillationMode>,
+        #[serde(default)]
+        #[serde(rename = "supportedFanOscillationModes")]
+        pub supported_fan_oscillation_modes: Option<SupportedFanOscillationModes>,
+    }
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Command {
+        SetFanOscillationMode {
+            fan_oscillation_mode: FanOscillationModeValue,
+        },
+    }
+    impl Command {
+        #[doc = "`setFanOscillationMode` command"]
+        pub fn set_fan_oscillation_mode(fan_oscillation_mode: FanOscillationModeValue) -> Self {
+            Self::SetFanOscillationMode {
+                fan_oscillation_mode,
+            }
+        }
+        pub fn name(&self) -> &'static str {
+            match self {
+                Self::SetFanOscillationMode { .. } => "setFanOscillationMode",
+            }
+        }
+        pub fn arguments(&self) -> Vec<serde_json::Value> {
+            match self {
+                Self::SetFanOscillationMode {
+                    fan_oscillation_mode,
+                } => vec![serde_json::to_value(fan_oscillation_mode).unwrap()],
+            }
+        }
+        #[doc = r" Command for `component`, to send with `ApiClient::commands`"]
+        pub fn to_component(&self, component: &str) -> crate::RawCommand {
+            crate::RawCommand {
+                component: component.to_string(),
+                capability: ID.to_string(),
+                command: self.name().to_string(),
+                arguments: self.arguments(),
+            }
+        }
+    }
+}
+#[doc = "Odor Sensor"]
+pub mod odor_sensor {
+    pub const ID: &str = "odorSensor";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct OdorLevel {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<i64>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "odorLevel")]
+        pub odor_level: Option<OdorLevel>,
+    }
+}
+#[doc = "Power Consumption Report"]
+pub mod power_consumption_report {
+    pub const ID: &str = "powerConsumptionReport";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Clone, PartialEq, serde :: Serialize, serde :: Deserialize)]
+    pub struct PowerConsumptionValue {
+        #[serde(rename = "deltaEnergy")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub delta_energy: Option<f64>,
+        #[serde(rename = "end")]
+        pub end: String,
+        #[serde(rename = "energy")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub energy: Option<f64>,
+        #[serde(rename = "energySaved")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub energy_saved: Option<f64>,
+        #[serde(rename = "persistedEnergy")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub persisted_energy: Option<f64>,
+        #[serde(rename = "power")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub power: Option<f64>,
+        #[serde(rename = "powerEnergy")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub power_energy: Option<f64>,
+        #[serde(rename = "start")]
+        pub start: String,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct PowerConsumption {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<PowerConsumptionValue>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "powerConsumption")]
+        pub power_consumption: Option<PowerConsumption>,
+    }
+}
+#[doc = "Power Meter"]
+pub mod power_meter {
+    pub const ID: &str = "powerMeter";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde :: Serialize, serde :: Deserialize)]
+    pub enum Unit {
+        #[serde(rename = "W")]
+        Watt,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct Power {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<f64>,
+        #[serde(default)]
+        pub unit: Option<Unit>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "power")]
+        pub power: Option<Power>,
+    }
+}
+#[doc = "Relative Humidity Measurement"]
+pub mod relative_humidity_measurement {
+    pub const ID: &str = "relativeHumidityMeasurement";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde :: Serialize, serde :: Deserialize)]
+    pub enum Unit {
+        #[serde(rename = "%")]
+        Percent,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct Humidity {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<f64>,
+        #[serde(default)]
+        pub unit: Option<Unit>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "humidity")]
+        pub humidity: Option<Humidity>,
+    }
+}
+#[doc = "Switch"]
+pub mod switch {
+    pub const ID: &str = "switch";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde :: Serialize, serde :: Deserialize)]
+    pub enum SwitchState {
+        #[serde(rename = "on")]
+        On,
+        #[serde(rename = "off")]
+        Off,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct Switch {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<SwitchState>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "switch")]
+        pub switch: Option<Switch>,
+    }
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Command {
+        Off,
+        On,
+    }
+    impl Command {
+        #[doc = "`off` command"]
+        pub fn off() -> Self {
+            Self::Off
+        }
+        #[doc = "`on` command"]
+        pub fn on() -> Self {
+            Self::On
+        }
+        pub fn name(&self) -> &'static str {
+            match self {
+                Self::Off => "off",
+                Self::On => "on",
+            }
+        }
+        pub fn arguments(&self) -> Vec<serde_json::Value> {
+            match self {
+                Self::Off => vec![],
+                Self::On => vec![],
+            }
+        }
+        #[doc = r" Command for `component`, to send with `ApiClient::commands`"]
+        pub fn to_component(&self, component: &str) -> crate::RawCommand {
+            crate::RawCommand {
+                component: component.to_string(),
+                capability: ID.to_string(),
+                command: self.name().to_string(),
+                arguments: self.arguments(),
+            }
+        }
+    }
+}
+#[doc = "Temperature Measurement"]
+pub mod temperature_measurement {
+    pub const ID: &str = "temperatureMeasurement";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde :: Serialize, serde :: Deserialize)]
+    pub enum Unit {
+        #[serde(rename = "F")]
+        Fahrenheit,
+        #[serde(rename = "C")]
+        Celsius,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct Temperature {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<f64>,
+        #[serde(default)]
+        pub unit: Option<Unit>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "temperature")]
+        pub temperature: Option<Temperature>,
+    }
+}
+#[doc = "Thermostat Cooling Setpoint"]
+pub mod thermostat_cooling_setpoint {
+    pub const ID: &str = "thermostatCoolingSetpoint";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde :: Serialize, serde :: Deserialize)]
+    pub enum Unit {
+        #[serde(rename = "F")]
+        Fahrenheit,
+        #[serde(rename = "C")]
+        Celsius,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct CoolingSetpoint {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<f64>,
+        #[serde(default)]
+        pub unit: Option<Unit>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "coolingSetpoint")]
+        pub cooling_setpoint: Option<CoolingSetpoint>,
+    }
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Command {
+        SetCoolingSetpoint { setpoint: f64 },
+    }
+    impl Command {
+        #[doc = "`setCoolingSetpoint` command"]
+        pub fn set_cooling_setpoint(setpoint: f64) -> Self {
+            Self::SetCoolingSetpoint { setpoint }
+        }
+        pub fn name(&self) -> &'static str {
+            match self {
+                Self::SetCoolingSetpoint { .. } => "setCoolingSetpoint",
+            }
+        }
+        pub fn arguments(&self) -> Vec<serde_json::Value> {
+            match self {
+                Self::SetCoolingSetpoint { setpoint } => {
+                    vec![serde_json::to_value(setpoint).unwrap()]
+                }
+            }
+        }
+        #[doc = r" Command for `component`, to send with `ApiClient::commands`"]
+        pub fn to_component(&self, component: &str) -> crate::RawCommand {
+            crate::RawCommand {
+                component: component.to_string(),
+                capability: ID.to_string(),
+                command: self.name().to_string(),
+                arguments: self.arguments(),
+            }
+        }
+    }
+}
+#[doc = "Very Fine Dust Sensor"]
+pub mod very_fine_dust_sensor {
+    pub const ID: &str = "veryFineDustSensor";
+    pub const VERSION: u8 = 1u8;
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde :: Serialize, serde :: Deserialize)]
+    pub enum Unit {
+        #[serde(rename = "μg/m^3")]
+        MicrogramPerCubicMeter,
+    }
+    #[derive(Debug, Clone, PartialEq, serde :: Deserialize)]
+    pub struct VeryFineDustLevel {
+        #[doc = r" `None` until the device reports it"]
+        #[serde(default)]
+        pub value: Option<i64>,
+        #[serde(default)]
+        pub unit: Option<Unit>,
+        #[serde(default)]
+        pub timestamp: Option<String>,
+    }
+    #[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        #[serde(rename = "veryFineDustLevel")]
+        pub very_fine_dust_level: Option<VeryFineDustLevel>,
+    }
+}
+#[doc = r" Status of a component, by capability. Capabilities without a definition are skipped."]
+#[derive(Debug, Clone, Default, PartialEq, serde :: Deserialize)]
+pub struct ComponentStatus {
+    #[serde(rename = "airConditionerFanMode")]
+    #[serde(default)]
+    pub air_conditioner_fan_mode: Option<air_conditioner_fan_mode::Status>,
+    #[serde(rename = "airConditionerMode")]
+    #[serde(default)]
+    pub air_conditioner_mode: Option<air_conditioner_mode::Status>,
+    #[serde(rename = "airQualitySensor")]
+    #[serde(default)]
+    pub air_quality_sensor: Option<air_quality_sensor::Status>,
+    #[serde(rename = "custom.thermostatSetpointControl")]
+    #[serde(default)]
+    pub custom_thermostat_setpoint_control: Option<custom_thermostat_setpoint_control::Status>,
+    #[serde(rename = "dustSensor")]
+    #[serde(default)]
+    pub dust_sensor: Option<dust_sensor::Status>,
+    #[serde(rename = "energyMeter")]
+    #[serde(default)]
+    pub energy_meter: Option<energy_meter::Status>,
+    #[serde(rename = "fanOscillationMode")]
+    #[serde(default)]
+    pub fan_oscillation_mode: Option<fan_oscillation_mode::Status>,
+    #[serde(rename = "odorSensor")]
+    #[serde(default)]
+    pub odor_sensor: Option<odor_sensor::Status>,
+    #[serde(rename = "powerConsumptionReport")]
+    #[serde(default)]
+    pub power_consumption_report: Option<power_consumption_report::Status>,
+    #[serde(rename = "powerMeter")]
+    #[serde(default)]
+    pub power_meter: Option<power_meter::Status>,
+    #[serde(rename = "relativeHumidityMeasurement")]
+    #[serde(default)]
+    pub relative_humidity_measurement: Option<relative_humidity_measurement::Status>,
+    #[serde(rename = "switch")]
+    #[serde(default)]
+    pub switch: Option<switch::Status>,
+    #[serde(rename = "temperatureMeasurement")]
+    #[serde(default)]
+    pub temperature_measurement: Option<temperature_measurement::Status>,
+    #[serde(rename = "thermostatCoolingSetpoint")]
+    #[serde(default)]
+    pub thermostat_cooling_setpoint: Option<thermostat_cooling_setpoint::Status>,
+    #[serde(rename = "veryFineDustSensor")]
+    #[serde(default)]
+    pub very_fine_dust_sensor: Option<very_fine_dust_sensor::Status>,
+}
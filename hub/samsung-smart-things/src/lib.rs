@@ -1,19 +1,84 @@
 #[cfg(test)]
 use serde_json::json;
 
-pub const BASE_URL: once_cell::sync::Lazy<reqwest::Url> = once_cell::sync::Lazy::new(|| {
-    reqwest::Url::parse("https://api.smartthings.com/v1/devices/").unwrap()
-});
+mod automation;
+pub use automation::*;
+/// Types generated from the capability definitions in `capabilities`
+#[cfg(feature = "generate")]
+pub mod capabilities {
+    include! {concat!(env!("OUT_DIR"), "/capabilities.rs")}
+}
+/// Types generated from the capability definitions in `capabilities`
+#[cfg(not(feature = "generate"))]
+pub mod capabilities;
+mod oauth;
+pub use oauth::*;
+mod scheduler;
+pub use scheduler::RateLimit;
+use scheduler::Scheduler;
+mod smartapp;
+pub use smartapp::*;
+#[cfg(feature = "mock")]
+pub mod mock;
+
+pub const API_URL: once_cell::sync::Lazy<reqwest::Url> =
+    once_cell::sync::Lazy::new(|| reqwest::Url::parse("https://api.smartthings.com/v1/").unwrap());
 
 #[derive(serde::Deserialize)]
 pub struct AirconditionerStatus {}
 
 #[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DeviceDescriptor {
+    pub device_id: String,
+    pub name: String,
     pub label: String,
+    pub location_id: Option<String>,
+    pub room_id: Option<String>,
+    #[serde(default)]
+    pub manufacturer_name: Option<String>,
+    /// identifies the model, like `DA-AC-RAC-000001`
+    #[serde(default)]
+    pub presentation_id: Option<String>,
+    /// details reported by OCF devices, like Samsung appliances
+    #[serde(default)]
+    pub ocf: Option<OcfDescriptor>,
     pub components: Vec<ComponentDescriptor>,
 }
 
+impl DeviceDescriptor {
+    pub fn component(&self, id: &str) -> Option<&ComponentDescriptor> {
+        self.components.iter().find(|component| component.id == id)
+    }
+
+    pub fn manufacturer(&self) -> Option<&str> {
+        self.ocf
+            .as_ref()
+            .and_then(|ocf| ocf.manufacturer_name.as_deref())
+            .or(self.manufacturer_name.as_deref())
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.ocf
+            .as_ref()
+            .and_then(|ocf| ocf.model_number.as_deref())
+            .or(self.presentation_id.as_deref())
+    }
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OcfDescriptor {
+    #[serde(default)]
+    pub manufacturer_name: Option<String>,
+    #[serde(default)]
+    pub model_number: Option<String>,
+    #[serde(default)]
+    pub hw_version: Option<String>,
+    #[serde(default)]
+    pub firmware_version: Option<String>,
+}
+
 #[derive(serde::Deserialize)]
 pub struct ComponentDescriptor {
     pub id: String,
@@ -21,6 +86,12 @@ pub struct ComponentDescriptor {
     pub capabilities: Vec<CapabilityWithVersion>,
 }
 
+impl ComponentDescriptor {
+    pub fn has_capability(&self, capability: Capability) -> bool {
+        self.capabilities.iter().any(|o| o.id == capability)
+    }
+}
+
 #[derive(serde::Deserialize)]
 pub struct CapabilityWithVersion {
     pub id: Capability,
@@ -28,7 +99,7 @@ pub struct CapabilityWithVersion {
 }
 
 #[derive(Clone, Copy, PartialEq, Hash, Eq, serde::Serialize, serde::Deserialize)]
-#[serde(rename = "camelCase")]
+#[serde(rename_all = "camelCase")]
 pub enum Capability {
     Switch,
     AirConditionerMode,
@@ -45,6 +116,116 @@ pub enum Capability {
     Other,
 }
 
+#[test]
+fn test_device_descriptor_deserialization() {
+    let descriptor: DeviceDescriptor = serde_json::from_value(json!({
+        "deviceId": "cb2eddac-bfd2-1057-7493-3a0a573e507a",
+        "name": "Room A/C",
+        "label": "Air conditioner",
+        "locationId": "6b3d1909-1e1c-43ec-adc2-5f941de4fbf9",
+        "roomId": "9b3b6f4e-5b1a-4c1a-9d53-0b8c3f3f9f2d",
+        "manufacturerName": "Samsung Electronics",
+        "presentationId": "DA-AC-RAC-000001",
+        "ocf": {
+            "ocfDeviceType": "oic.d.airconditioner",
+            "manufacturerName": "Samsung Electronics",
+            "modelNumber": "ARTIK051_PRAC_20K|10256941|60010523001411014A00",
+            "firmwareVersion": "0.1.0"
+        },
+        "components": [
+            {
+                "id": "main",
+                "label": "main",
+                "capabilities": [
+                    { "id": "switch", "version": 1 },
+                    { "id": "airConditionerMode", "version": 1 },
+                    { "id": "custom.thermostatSetpointControl", "version": 1 }
+                ]
+            }
+        ]
+    }))
+    .unwrap();
+    let main = descriptor.component("main").unwrap();
+    assert!(main.has_capability(Capability::Switch));
+    assert!(main.has_capability(Capability::AirConditionerMode));
+    assert!(main.has_capability(Capability::Other));
+    assert!(!main.has_capability(Capability::DustSensor));
+    assert!(descriptor.component("sub").is_none());
+    assert_eq!(descriptor.manufacturer(), Some("Samsung Electronics"));
+    assert_eq!(
+        descriptor.model(),
+        Some("ARTIK051_PRAC_20K|10256941|60010523001411014A00")
+    );
+    assert_eq!(
+        descriptor.ocf.unwrap().firmware_version.as_deref(),
+        Some("0.1.0")
+    );
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    pub location_id: String,
+    pub name: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Room {
+    pub room_id: String,
+    pub location_id: String,
+    pub name: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Link {
+    href: String,
+}
+
+#[derive(Default, serde::Deserialize)]
+struct PageLinks {
+    next: Option<Link>,
+}
+
+/// Envelope of list APIs. Remaining items are fetched by following `_links.next`.
+#[derive(serde::Deserialize)]
+struct Page<T> {
+    items: Vec<T>,
+    #[serde(rename = "_links")]
+    #[serde(default)]
+    links: Option<PageLinks>,
+}
+
+#[test]
+fn test_page_deserialization() {
+    let page: Page<Room> = serde_json::from_value(json!({
+        "items": [
+            {
+                "roomId": "9b3b6f4e-5b1a-4c1a-9d53-0b8c3f3f9f2d",
+                "locationId": "6b3d1909-1e1c-43ec-adc2-5f941de4fbf9",
+                "name": "Living room",
+                "backgroundImage": null
+            }
+        ],
+        "_links": {
+            "next": {
+                "href": "https://api.smartthings.com/v1/locations/6b3d1909-1e1c-43ec-adc2-5f941de4fbf9/rooms?page=1"
+            },
+            "previous": null
+        }
+    }))
+    .unwrap();
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items[0].name, "Living room");
+    assert_eq!(
+        page.links.unwrap().next.unwrap().href,
+        "https://api.smartthings.com/v1/locations/6b3d1909-1e1c-43ec-adc2-5f941de4fbf9/rooms?page=1"
+    );
+
+    let last_page: Page<Room> = serde_json::from_value(json!({ "items": [] })).unwrap();
+    assert!(last_page.links.and_then(|links| links.next).is_none());
+}
+
 pub struct EnumMapIgnoreUnknown;
 static END_OF_MAP_IDENTIFIER: &'static str = "__PRIVATE_MARKER__";
 
@@ -284,315 +465,444 @@ where
     }
 }
 
-#[serde_with::serde_as]
-#[derive(Clone, serde::Deserialize)]
-pub struct ComponentStatus(#[serde_as(as = "EnumMapIgnoreUnknown")] pub Vec<CapabilityStatus>);
-
-#[test]
-fn test_component_status_deserialization() {
-    let statuses: ComponentStatus =
-        serde_json::from_str(include_str!("./components_main_status.json")).unwrap();
-    for status in statuses.0 {
-        match status {
-            CapabilityStatus::RelativeHumidityMeasurement { humidity } => {
-                assert_eq!(humidity.value, 55);
-                assert_eq!(humidity.unit, enums::HumidityUnit::Percent);
-            }
-            CapabilityStatus::ThermostatSetpointControl {
-                minimum_setpoint,
-                maximum_setpoint,
-            } => {
-                assert_eq!(minimum_setpoint.value, 16);
-                assert_eq!(minimum_setpoint.unit, enums::TemperatureUnit::Celsius);
-                assert_eq!(maximum_setpoint.value, 30);
-                assert_eq!(maximum_setpoint.unit, enums::TemperatureUnit::Celsius);
-            }
-            CapabilityStatus::AirConditionerMode {
-                supported_ac_modes,
-                air_conditioner_mode,
-            } => {
-                assert_eq!(
-                    supported_ac_modes.value,
-                    [
-                        enums::AirConditionerMode::Cool,
-                        enums::AirConditionerMode::Dry,
-                        enums::AirConditionerMode::Wind,
-                        enums::AirConditionerMode::Auto,
-                        enums::AirConditionerMode::AIComfort,
-                    ]
-                    .into()
-                );
-                assert_eq!(air_conditioner_mode.value, enums::AirConditionerMode::Wind);
-            }
-            _ => {}
+pub use capabilities::ComponentStatus;
+
+impl capabilities::energy_meter::Energy {
+    /// `None` until the device reports it, or when it isn't in Wh, kWh or mWh
+    pub fn kilowatt_hours(&self) -> Option<f64> {
+        use capabilities::energy_meter::Unit;
+        let value = self.value?;
+        match self.unit? {
+            Unit::WattHour => Some(value / 1000.0),
+            Unit::KilowattHour => Some(value),
+            Unit::MilliwattHour => Some(value / 1_000_000.0),
+            Unit::KilovoltAmpereHour => None,
         }
     }
 }
 
-pub mod enums {
-    use std::hash::Hash;
+/// Raw attribute values of a component, which can be kept up to date with `DeviceEvent`s
+/// and parsed into `ComponentStatus` at any time.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(transparent)]
+pub struct RawComponentStatus(pub serde_json::Map<String, serde_json::Value>);
 
-    #[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    pub enum OnOff {
-        On,
-        Off,
+impl RawComponentStatus {
+    pub fn parse(&self) -> Result<ComponentStatus, serde_json::Error> {
+        serde_json::from_value(serde_json::Value::Object(self.0.clone()))
     }
 
-    impl From<bool> for OnOff {
-        fn from(v: bool) -> Self {
-            match v {
-                true => Self::On,
-                false => Self::Off,
-            }
+    /// Store the new value of the event, and return the updated status of its capability,
+    /// or `None` for a capability without a type in `capabilities`
+    pub fn apply(&mut self, event: &DeviceEvent) -> Option<ComponentStatus> {
+        let capability = self
+            .0
+            .entry(event.capability.clone())
+            .or_insert_with(|| serde_json::Value::Object(Default::default()));
+        let mut attribute = serde_json::Map::new();
+        attribute.insert("value".to_string(), event.value.clone());
+        if let Some(unit) = &event.unit {
+            attribute.insert("unit".to_string(), serde_json::Value::String(unit.clone()));
         }
+        capability.as_object_mut()?.insert(
+            event.attribute.clone(),
+            serde_json::Value::Object(attribute),
+        );
+
+        let mut single = serde_json::Map::new();
+        single.insert(event.capability.clone(), capability.clone());
+        let status: ComponentStatus = serde_json::from_value(serde_json::Value::Object(single))
+            .map_err(|e| log::warn!("failed to parse {} - {}", &event.capability, e))
+            .ok()?;
+        (status != ComponentStatus::default()).then_some(status)
     }
+}
 
-    impl From<&bool> for OnOff {
-        fn from(v: &bool) -> Self {
-            (*v).into()
-        }
-    }
+/// Status of every component of a device, by component id
+#[derive(Clone, serde::Deserialize)]
+pub struct DeviceStatus {
+    pub components: std::collections::HashMap<String, ComponentStatus>,
+}
 
-    impl From<OnOff> for bool {
-        fn from(v: OnOff) -> Self {
-            v == OnOff::On
-        }
-    }
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct RawDeviceStatus {
+    pub components: std::collections::HashMap<String, RawComponentStatus>,
+}
 
-    impl From<&OnOff> for bool {
-        fn from(v: &OnOff) -> Self {
-            (*v).into()
-        }
+impl RawDeviceStatus {
+    pub fn parse(&self) -> Result<DeviceStatus, serde_json::Error> {
+        Ok(DeviceStatus {
+            components: self
+                .components
+                .iter()
+                .map(|(id, raw)| Ok((id.clone(), raw.parse()?)))
+                .collect::<Result<_, serde_json::Error>>()?,
+        })
     }
+}
 
-    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    pub enum AcOptionalMode {
-        Off,
-        WindFree,
-    }
+/// Whether SmartThings can reach the device. Status of an offline device is the last one
+/// it reported.
+#[derive(Debug, Copy, Clone, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HealthState {
+    Online,
+    Offline,
+    #[serde(other)]
+    Unknown,
+}
 
-    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    pub enum AirConditionerMode {
-        AIComfort,
-        Cool,
-        Dry,
-        Wind,
-        Auto,
-    }
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceHealth {
+    pub device_id: String,
+    pub state: HealthState,
+    #[serde(default)]
+    pub last_updated_date: Option<String>,
+}
 
-    #[derive(Debug, Clone, PartialEq, serde::Deserialize)]
-    pub enum TemperatureUnit {
-        #[serde(rename = "C")]
-        Celsius,
-        #[serde(rename = "F")]
-        Farenheit,
-    }
+#[test]
+fn test_device_health_deserialization() {
+    let health: DeviceHealth = serde_json::from_value(json!({
+        "deviceId": "cb2eddac-bfd2-1057-7493-3a0a573e507a",
+        "state": "OFFLINE",
+        "lastUpdatedDate": "2026-10-17T09:30:00.000Z"
+    }))
+    .unwrap();
+    assert_eq!(health.state, HealthState::Offline);
+
+    let health: DeviceHealth = serde_json::from_value(json!({
+        "deviceId": "cb2eddac-bfd2-1057-7493-3a0a573e507a",
+        "state": "UNHEALTHY"
+    }))
+    .unwrap();
+    assert_eq!(health.state, HealthState::Unknown);
+}
 
-    #[derive(Debug, Clone, PartialEq, serde::Deserialize)]
-    pub enum HumidityUnit {
-        #[serde(rename = "%")]
-        Percent,
-    }
+#[test]
+fn test_device_status_deserialization() {
+    let raw: RawDeviceStatus = serde_json::from_value(json!({
+        "components": {
+            "main": {
+                "switch": { "switch": { "value": "on" } }
+            },
+            "zone2": {
+                "switch": { "switch": { "value": "off" } },
+                "thermostatCoolingSetpoint": {
+                    "coolingSetpoint": { "value": 26, "unit": "C" }
+                }
+            }
+        }
+    }))
+    .unwrap();
+    let status = raw.parse().unwrap();
+    assert_eq!(status.components.len(), 2);
+    assert!(status.components["main"].switch.is_some());
+    assert!(status.components["main"]
+        .thermostat_cooling_setpoint
+        .is_none());
+    let cooling_setpoint = status.components["zone2"]
+        .thermostat_cooling_setpoint
+        .as_ref()
+        .and_then(|status| status.cooling_setpoint.as_ref())
+        .unwrap();
+    assert_eq!(cooling_setpoint.value, Some(26.0));
+}
 
-    #[derive(Debug, Clone, PartialEq, serde::Deserialize)]
-    pub enum DustLevelUnit {
-        #[serde(rename = "μg/m^3")]
-        MicroGramPerSquareMeter,
-    }
+#[test]
+fn test_air_quality_status_deserialization() {
+    let status: ComponentStatus = serde_json::from_value(json!({
+        "veryFineDustSensor": {
+            "veryFineDustLevel": { "value": 5, "unit": "μg/m^3" }
+        },
+        "airQualitySensor": {
+            "airQuality": { "value": 32, "unit": "CAQI" }
+        },
+        "odorSensor": {
+            "odorLevel": { "value": 1 }
+        }
+    }))
+    .unwrap();
+
+    let very_fine_dust_level = status
+        .very_fine_dust_sensor
+        .and_then(|status| status.very_fine_dust_level)
+        .unwrap();
+    assert_eq!(very_fine_dust_level.value, Some(5));
+    let air_quality = status
+        .air_quality_sensor
+        .and_then(|status| status.air_quality)
+        .unwrap();
+    assert_eq!(air_quality.value, Some(32));
+    assert_eq!(
+        air_quality.unit,
+        Some(capabilities::air_quality_sensor::Unit::Caqi)
+    );
+    let odor_level = status.odor_sensor.and_then(|status| status.odor_level);
+    assert_eq!(odor_level.unwrap().value, Some(1));
+}
 
-    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
-    pub enum AirConditionerFanMode {
-        Auto,
-        Low,
-        Medium,
-        High,
-        Turbo,
-    }
+#[test]
+fn test_energy_status_deserialization() {
+    let status: ComponentStatus = serde_json::from_value(json!({
+        "powerMeter": {
+            "power": { "value": 830.5, "unit": "W" }
+        },
+        "energyMeter": {
+            "energy": { "value": 1234567, "unit": "Wh" }
+        },
+        "powerConsumptionReport": {
+            "powerConsumption": {
+                "value": {
+                    "energy": 1234567,
+                    "deltaEnergy": 15,
+                    "power": 830.5,
+                    "powerEnergy": 14.8,
+                    "persistedEnergy": 0,
+                    "energySaved": 0,
+                    "start": "2022-07-01T10:00:11Z",
+                    "end": "2022-07-01T10:10:11Z"
+                },
+                "timestamp": "2022-07-01T10:10:12.482Z"
+            }
+        }
+    }))
+    .unwrap();
+
+    let energy = status
+        .energy_meter
+        .and_then(|status| status.energy)
+        .unwrap();
+    assert_eq!(energy.kilowatt_hours(), Some(1234.567));
+    let power_consumption = status
+        .power_consumption_report
+        .and_then(|status| status.power_consumption)
+        .and_then(|power_consumption| power_consumption.value)
+        .unwrap();
+    assert_eq!(power_consumption.delta_energy, Some(15.0));
+    assert_eq!(power_consumption.end, "2022-07-01T10:10:11Z");
+    let power = status.power_meter.and_then(|status| status.power).unwrap();
+    assert_eq!(power.value, Some(830.5));
 }
 
-pub mod status {
-    use super::enums;
-    use std::{collections::HashSet, hash::Hash};
+#[test]
+fn test_raw_component_status_apply() {
+    let mut raw: RawComponentStatus = serde_json::from_value(json!({
+        "switch": { "switch": { "value": "off" } },
+        "airConditionerMode": {
+            "supportedAcModes": { "value": ["cool", "dry", "wind", "auto"] },
+            "airConditionerMode": { "value": "cool" }
+        }
+    }))
+    .unwrap();
+    let event = |capability: &str, attribute: &str, value| DeviceEvent {
+        subscription_name: None,
+        device_id: "cb2eddac-bfd2-1057-7493-3a0a573e507a".to_string(),
+        component_id: "main".to_string(),
+        capability: capability.to_string(),
+        attribute: attribute.to_string(),
+        value,
+        unit: None,
+        state_change: true,
+    };
 
-    #[derive(Debug, Clone, PartialEq, serde::Deserialize)]
-    pub struct Switch {
-        pub value: enums::OnOff,
-    }
+    let status = raw
+        .apply(&event(
+            "airConditionerMode",
+            "airConditionerMode",
+            json!("dry"),
+        ))
+        .unwrap();
+    assert!(status.switch.is_none());
+    let air_conditioner_mode = status.air_conditioner_mode.unwrap();
+    assert_eq!(
+        air_conditioner_mode
+            .supported_ac_modes
+            .unwrap()
+            .value
+            .unwrap()
+            .len(),
+        4
+    );
+    assert_eq!(
+        air_conditioner_mode
+            .air_conditioner_mode
+            .unwrap()
+            .value
+            .as_deref(),
+        Some("dry")
+    );
+    assert!(raw.apply(&event("ocf", "n", json!("AC"))).is_none());
 
-    #[derive(Debug, Clone, PartialEq, serde::Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct AcMode {
-        pub value: enums::AirConditionerMode,
-    }
+    raw.apply(&event("switch", "switch", json!("on")));
+    let status = raw.parse().unwrap();
+    assert_eq!(
+        status
+            .switch
+            .and_then(|status| status.switch)
+            .unwrap()
+            .value,
+        Some(capabilities::switch::SwitchState::On)
+    );
+}
 
-    #[derive(Debug, Clone, PartialEq, serde::Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct AcOptionalMode {
-        pub value: enums::AcOptionalMode,
-    }
+#[test]
+fn test_component_status_deserialization() {
+    use capabilities::custom_thermostat_setpoint_control::Unit;
 
-    #[derive(Debug, Clone, PartialEq, serde::Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct FanMode {
-        pub value: enums::AirConditionerFanMode,
+    let status: ComponentStatus =
+        serde_json::from_str(include_str!("./components_main_status.json")).unwrap();
+    if let Some(humidity) = status
+        .relative_humidity_measurement
+        .and_then(|status| status.humidity)
+    {
+        assert_eq!(humidity.value, Some(55.0));
+        assert_eq!(
+            humidity.unit,
+            Some(capabilities::relative_humidity_measurement::Unit::Percent)
+        );
     }
-
-    #[derive(Debug, Clone, serde::Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct SupportedModes<M: Clone + PartialEq + Eq + Hash> {
-        pub value: HashSet<M>,
+    if let Some(setpoint_control) = status.custom_thermostat_setpoint_control {
+        let minimum_setpoint = setpoint_control.minimum_setpoint.unwrap();
+        assert_eq!(minimum_setpoint.value, Some(16.0));
+        assert_eq!(minimum_setpoint.unit, Some(Unit::Celsius));
+        let maximum_setpoint = setpoint_control.maximum_setpoint.unwrap();
+        assert_eq!(maximum_setpoint.value, Some(30.0));
+        assert_eq!(maximum_setpoint.unit, Some(Unit::Celsius));
     }
-
-    pub type SupportedAcFanModes = SupportedModes<enums::AirConditionerFanMode>;
-    pub type SupportedAcModes = SupportedModes<enums::AirConditionerMode>;
-    pub type SupportedAcOptionalModes = SupportedModes<enums::AcOptionalMode>;
-
-    #[derive(Debug, Clone, PartialEq, serde::Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct ValueWithUnit<V: Clone + PartialEq, U: Clone + PartialEq> {
-        pub value: V,
-        pub unit: U,
+    if let Some(air_conditioner_mode) = status.air_conditioner_mode {
+        assert_eq!(
+            air_conditioner_mode.supported_ac_modes.unwrap().value,
+            Some(
+                ["cool", "dry", "wind", "auto", "aIComfort"]
+                    .map(str::to_string)
+                    .to_vec()
+            )
+        );
+        assert_eq!(
+            air_conditioner_mode
+                .air_conditioner_mode
+                .unwrap()
+                .value
+                .as_deref(),
+            Some("wind")
+        );
     }
-
-    pub type Temperature = ValueWithUnit<i16, enums::TemperatureUnit>;
-    pub type DustLevel = ValueWithUnit<u16, enums::DustLevelUnit>;
-    pub type Humidity = ValueWithUnit<u8, enums::HumidityUnit>;
 }
 
-#[derive(Debug, Clone, serde::Deserialize)]
+/// Capability command addressed to a component of the device. Commands of capabilities in
+/// `capabilities` are built with their `Command::to_component`.
+#[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
-pub enum CapabilityStatus {
-    #[serde(rename_all = "camelCase")]
-    Switch { switch: status::Switch },
-    #[serde(rename_all = "camelCase")]
-    AirConditionerMode {
-        supported_ac_modes: status::SupportedAcModes,
-        air_conditioner_mode: status::AcMode,
-    },
-    #[serde(rename = "custom.thermostatSetpointControl")]
-    #[serde(rename_all = "camelCase")]
-    ThermostatSetpointControl {
-        minimum_setpoint: status::Temperature,
-        maximum_setpoint: status::Temperature,
-    },
-    #[serde(rename_all = "camelCase")]
-    TemperatureMeasurement { temperature: status::Temperature },
-    #[serde(rename_all = "camelCase")]
-    ThermostatCoolingSetpoint {
-        cooling_setpoint: status::Temperature,
-    },
-    #[serde(rename_all = "camelCase")]
-    DustSensor {
-        dust_level: status::DustLevel,
-        fine_dust_level: status::DustLevel,
-    },
-    #[serde(rename_all = "camelCase")]
-    RelativeHumidityMeasurement { humidity: status::Humidity },
-    #[serde(rename_all = "camelCase")]
-    VeryFineDustSensor {
-        very_fine_dust_level: status::DustLevel,
-    },
-    #[serde(other)]
-    Unknown,
+pub struct RawCommand {
+    pub component: String,
+    pub capability: String,
+    pub command: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<serde_json::Value>,
 }
 
-pub mod command {
-    use crate::enums;
-
-    #[derive(Debug, Clone, serde::Serialize)]
-    pub struct CommandWithoutArguments<T: serde::Serialize + std::fmt::Debug + Clone> {
-        command: T,
-    }
-
-    impl<T: serde::Serialize + std::fmt::Debug + Clone> CommandWithoutArguments<T> {
-        pub fn new<I: Into<T>>(command: I) -> Self {
-            Self {
-                command: command.into(),
+impl RawCommand {
+    /// Whether `status` of the component shows the value set by this command.
+    /// `None` for commands without an attribute to check.
+    pub fn is_reflected_in(&self, status: &ComponentStatus) -> Option<bool> {
+        use capabilities::{air_conditioner_mode, switch, thermostat_cooling_setpoint};
+
+        let argument = self.arguments.first();
+        match (self.capability.as_str(), self.command.as_str()) {
+            (switch::ID, "on" | "off") => {
+                let value = status
+                    .switch
+                    .as_ref()
+                    .and_then(|status| status.switch.as_ref()?.value);
+                Some(
+                    value.map(|value| value == switch::SwitchState::On)
+                        == Some(self.command == "on"),
+                )
             }
+            (air_conditioner_mode::ID, "setAirConditionerMode") => {
+                let value = status
+                    .air_conditioner_mode
+                    .as_ref()
+                    .and_then(|status| status.air_conditioner_mode.as_ref()?.value.as_deref());
+                Some(value.is_some() && value == argument?.as_str())
+            }
+            (thermostat_cooling_setpoint::ID, "setCoolingSetpoint") => {
+                let value = status
+                    .thermostat_cooling_setpoint
+                    .as_ref()
+                    .and_then(|status| status.cooling_setpoint.as_ref()?.value);
+                Some(value.is_some() && value == argument?.as_f64())
+            }
+            _ => None,
         }
     }
-
-    pub type Switch = CommandWithoutArguments<enums::OnOff>;
-
-    struct AsArguments<T>(std::marker::PhantomData<T>);
-
-    impl<T: serde::Serialize> serde_with::SerializeAs<T> for AsArguments<T> {
-        fn serialize_as<S>(source: &T, serializer: S) -> Result<S::Ok, S::Error>
-        where
-            S: serde::Serializer,
-        {
-            use serde::ser::SerializeStruct;
-            let mut strct = serializer.serialize_struct("", 1)?;
-            strct.serialize_field("arguments", &(source,))?;
-            strct.end()
-        }
-    }
-
-    #[derive(Debug, Clone, serde::Serialize)]
-    #[serde(rename_all = "camelCase")]
-    #[serde(tag = "command")]
-    pub enum AirConditionerMode {
-        #[serde(with = "serde_with::As::<AsArguments::<_>>")]
-        SetAirConditionerMode(enums::AirConditionerMode),
-    }
-
-    #[derive(Debug, Clone, serde::Serialize)]
-    #[serde(rename_all = "camelCase")]
-    #[serde(tag = "command")]
-    pub enum ThermostatCoolingSetpoint {
-        #[serde(with = "serde_with::As::<AsArguments::<_>>")]
-        SetCoolingSetpoint(i16),
-    }
 }
 
-macro_rules! enum_of_types {
-    ($(#[$attrs:meta])*
-    $visability:vis enum $name:ident {
-        $($item_name:ident($ty:path),)*
-    }) => {
-        $(#[$attrs])*
-        $visability enum $name {
-            $($item_name($ty),)*
+#[test]
+fn test_command_reflection() {
+    use capabilities::{air_conditioner_mode, switch, thermostat_cooling_setpoint};
+
+    let status: ComponentStatus = serde_json::from_value(json!({
+        "switch": {
+            "switch": { "value": "on" }
+        },
+        "thermostatCoolingSetpoint": {
+            "coolingSetpoint": { "value": 24, "unit": "C" }
         }
+    }))
+    .unwrap();
+    let is_reflected = |command: RawCommand| command.is_reflected_in(&status);
 
-        $(impl From<$ty> for $name {
-            fn from(v: $ty) -> Self {
-                Self::$item_name(v)
-            }
-        })*
-    };
-}
-
-enum_of_types! {
-    #[derive(Debug, Clone, serde::Serialize)]
-    #[serde(rename_all = "camelCase")]
-    #[serde(tag = "capability")]
-    pub enum CapabilityCommand {
-        Switch(command::Switch),
-        AirConditionerMode(command::AirConditionerMode),
-        ThermostatCoolingSetpoint(command::ThermostatCoolingSetpoint),
-    }
+    assert_eq!(
+        is_reflected(switch::Command::on().to_component("main")),
+        Some(true)
+    );
+    assert_eq!(
+        is_reflected(switch::Command::off().to_component("main")),
+        Some(false)
+    );
+    assert_eq!(
+        is_reflected(
+            thermostat_cooling_setpoint::Command::set_cooling_setpoint(24.0).to_component("main")
+        ),
+        Some(true)
+    );
+    assert_eq!(
+        is_reflected(
+            air_conditioner_mode::Command::set_air_conditioner_mode("cool".to_string())
+                .to_component("main")
+        ),
+        Some(false)
+    );
+    assert_eq!(
+        is_reflected(RawCommand {
+            component: "main".to_string(),
+            capability: "refresh".to_string(),
+            command: "refresh".to_string(),
+            arguments: vec![],
+        }),
+        None
+    );
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 struct CapabilityCommandRequest {
-    commands: Vec<CapabilityCommand>,
+    commands: Vec<RawCommand>,
 }
 
 #[test]
 fn test_command_serialization() {
     assert_eq!(
         serde_json::to_value(&CapabilityCommandRequest {
-            commands: vec![command::Switch::new(true).into()],
+            commands: vec![capabilities::switch::Command::on().to_component("main")],
         })
         .unwrap(),
         json!({
             "commands": [
                 {
+                    "component": "main",
                     "capability": "switch",
                     "command": "on"
                 }
@@ -602,15 +912,158 @@ fn test_command_serialization() {
 
     assert_eq!(
         serde_json::to_value(&CapabilityCommandRequest {
-            commands: vec![command::ThermostatCoolingSetpoint::SetCoolingSetpoint(18).into()],
+            commands: vec![
+                capabilities::thermostat_cooling_setpoint::Command::set_cooling_setpoint(18.0)
+                    .to_component("main")
+            ],
+        })
+        .unwrap(),
+        json!({
+            "commands": [
+                {
+                    "component": "main",
+                    "capability": "thermostatCoolingSetpoint",
+                    "command": "setCoolingSetpoint",
+                    "arguments": [18.0]
+                }
+            ]
+        })
+    );
+
+    assert_eq!(
+        serde_json::to_value(&CapabilityCommandRequest {
+            commands: vec![
+                capabilities::switch::Command::on().to_component("main"),
+                capabilities::air_conditioner_mode::Command::set_air_conditioner_mode(
+                    "cool".to_string()
+                )
+                .to_component("sub"),
+            ],
+        })
+        .unwrap(),
+        json!({
+            "commands": [
+                {
+                    "component": "main",
+                    "capability": "switch",
+                    "command": "on"
+                },
+                {
+                    "component": "sub",
+                    "capability": "airConditionerMode",
+                    "command": "setAirConditionerMode",
+                    "arguments": ["cool"]
+                }
+            ]
+        })
+    );
+    assert_eq!(
+        serde_json::to_value(&CapabilityCommandRequest {
+            commands: vec![
+                RawCommand {
+                    component: "main".to_string(),
+                    capability: "fanOscillationMode".to_string(),
+                    command: "setFanOscillationMode".to_string(),
+                    arguments: vec![json!("vertical")],
+                },
+                RawCommand {
+                    component: "main".to_string(),
+                    capability: "refresh".to_string(),
+                    command: "refresh".to_string(),
+                    arguments: vec![],
+                },
+            ],
+        })
+        .unwrap(),
+        json!({
+            "commands": [
+                {
+                    "component": "main",
+                    "capability": "fanOscillationMode",
+                    "command": "setFanOscillationMode",
+                    "arguments": ["vertical"]
+                },
+                {
+                    "component": "main",
+                    "capability": "refresh",
+                    "command": "refresh"
+                }
+            ]
+        })
+    );
+}
+
+#[test]
+fn test_generated_capabilities() {
+    use capabilities::{fan_oscillation_mode, switch, thermostat_cooling_setpoint};
+
+    let status: capabilities::ComponentStatus = serde_json::from_value(json!({
+        "switch": {
+            "switch": { "value": "on", "timestamp": "2026-10-17T09:30:00.000Z" }
+        },
+        "thermostatCoolingSetpoint": {
+            "coolingSetpoint": { "value": 24, "unit": "C" }
+        },
+        "fanOscillationMode": {
+            "supportedFanOscillationModes": { "value": ["fixed", "vertical"] },
+            "fanOscillationMode": { "value": null }
+        },
+        "ocf": {
+            "n": { "value": "[room a/c] Samsung" }
+        }
+    }))
+    .unwrap();
+
+    let switch = status.switch.unwrap().switch.unwrap();
+    assert_eq!(switch.value, Some(switch::SwitchState::On));
+    let cooling_setpoint = status
+        .thermostat_cooling_setpoint
+        .unwrap()
+        .cooling_setpoint
+        .unwrap();
+    assert_eq!(cooling_setpoint.value, Some(24.0));
+    assert_eq!(
+        cooling_setpoint.unit,
+        Some(thermostat_cooling_setpoint::Unit::Celsius)
+    );
+    let fan_oscillation_mode = status.fan_oscillation_mode.unwrap();
+    assert_eq!(
+        fan_oscillation_mode
+            .supported_fan_oscillation_modes
+            .unwrap()
+            .value,
+        Some(vec![
+            fan_oscillation_mode::FanOscillationModeValue::Fixed,
+            fan_oscillation_mode::FanOscillationModeValue::Vertical
+        ])
+    );
+    assert_eq!(
+        fan_oscillation_mode.fan_oscillation_mode.unwrap().value,
+        None
+    );
+    assert!(status.dust_sensor.is_none());
+
+    assert_eq!(
+        serde_json::to_value(&CapabilityCommandRequest {
+            commands: vec![
+                switch::Command::off().to_component("main"),
+                thermostat_cooling_setpoint::Command::set_cooling_setpoint(18.5)
+                    .to_component("main"),
+            ],
         })
         .unwrap(),
         json!({
             "commands": [
                 {
+                    "component": "main",
+                    "capability": "switch",
+                    "command": "off"
+                },
+                {
+                    "component": "main",
                     "capability": "thermostatCoolingSetpoint",
                     "command": "setCoolingSetpoint",
-                    "arguments": [18]
+                    "arguments": [18.5]
                 }
             ]
         })
@@ -625,12 +1078,202 @@ pub enum Error {
     RequestError(#[from] reqwest::Error),
     #[error("Failed while parsing response")]
     ParseError(#[from] serde_json::Error),
+    #[error("OAuth token request rejected - {0}")]
+    OAuthError(String),
+    #[error("Failed while access OAuth state file")]
+    StateFileError(#[from] std::io::Error),
+    #[error("Unauthorized - {0}")]
+    Unauthorized(ApiError),
+    #[error("Forbidden - {0}")]
+    Forbidden(ApiError),
+    #[error("Not found - {0}")]
+    NotFound(ApiError),
+    #[error("Too many requests, retry after {retry_after:?} - {error}")]
+    TooManyRequests {
+        retry_after: Option<std::time::Duration>,
+        error: ApiError,
+    },
+    #[error("Request failed - {0}")]
+    ApiError(ApiError),
+    #[error("Command {} is {:?}", .0.id, .0.status)]
+    CommandFailed(CommandResult),
+    #[error("Device didn't reflect the command in time")]
+    CommandNotConfirmed,
+    #[error("Invalid SmartApp request signature - {0}")]
+    InvalidSignature(String),
+    #[error("SmartApp confirmation URL {0} isn't on SmartThings")]
+    InvalidConfirmationUrl(String),
+    /// Failure of a request merged with concurrent identical ones
+    #[error("{0}")]
+    Shared(std::sync::Arc<Error>),
 }
 
-#[derive(Debug, serde::Deserialize)]
+impl Error {
+    /// The error itself, or the one it shares
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::Shared(e) => e.inner(),
+            e => e,
+        }
+    }
+
+    /// Whether the same request may succeed later
+    pub fn is_transient(&self) -> bool {
+        match self.inner() {
+            Error::RequestError(e) => e.is_timeout() || e.is_connect(),
+            Error::TooManyRequests { .. } => true,
+            Error::ApiError(e) => e.status.is_server_error(),
+            _ => false,
+        }
+    }
+}
+
+/// Error envelope of SmartThings API responses
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorResponse {
+    request_id: Option<String>,
+    error: ApiErrorDetail,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiErrorDetail {
+    pub code: String,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub details: Vec<ApiErrorDetail>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: reqwest::StatusCode,
+    pub request_id: Option<String>,
+    /// `None` when the body is not a SmartThings error envelope
+    pub error: Option<ApiErrorDetail>,
+    pub body: String,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.error {
+            Some(error) => {
+                write!(f, "{} {}: {}", self.status, error.code, error.message)?;
+                for detail in &error.details {
+                    write!(f, ", {}: {}", detail.code, detail.message)?;
+                }
+                Ok(())
+            }
+            None => write!(f, "{} {}", self.status, self.body),
+        }
+    }
+}
+
+impl Error {
+    fn from_response(
+        status: reqwest::StatusCode,
+        headers: &reqwest::header::HeaderMap,
+        body: String,
+    ) -> Self {
+        let envelope = serde_json::from_str::<ErrorResponse>(&body).ok();
+        let error = ApiError {
+            status,
+            request_id: envelope.as_ref().and_then(|o| o.request_id.clone()),
+            error: envelope.map(|o| o.error),
+            body,
+        };
+
+        match status {
+            reqwest::StatusCode::UNAUTHORIZED => Error::Unauthorized(error),
+            reqwest::StatusCode::FORBIDDEN => Error::Forbidden(error),
+            reqwest::StatusCode::NOT_FOUND => Error::NotFound(error),
+            reqwest::StatusCode::TOO_MANY_REQUESTS => Error::TooManyRequests {
+                retry_after: retry_after(headers),
+                error,
+            },
+            _ => Error::ApiError(error),
+        }
+    }
+}
+
+/// `Retry-After` in seconds, or `X-RateLimit-Reset` in milliseconds which SmartThings sends instead
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<std::time::Duration> {
+    let header =
+        |name: &str| -> Option<u64> { headers.get(name)?.to_str().ok()?.trim().parse().ok() };
+
+    header("retry-after")
+        .map(std::time::Duration::from_secs)
+        .or_else(|| header("x-ratelimit-reset").map(std::time::Duration::from_millis))
+}
+
+#[test]
+fn test_error_response() {
+    let mut headers = reqwest::header::HeaderMap::new();
+    let error = Error::from_response(
+        reqwest::StatusCode::NOT_FOUND,
+        &headers,
+        json!({
+            "requestId": "3a4b1c2d-0f9e-4d8c-b7a6-5e4f3d2c1b0a",
+            "error": {
+                "code": "NotFoundError",
+                "message": "The requested resource was not found.",
+                "details": []
+            }
+        })
+        .to_string(),
+    );
+    match error {
+        Error::NotFound(error) => {
+            assert_eq!(
+                error.request_id.as_deref(),
+                Some("3a4b1c2d-0f9e-4d8c-b7a6-5e4f3d2c1b0a")
+            );
+            assert_eq!(error.error.unwrap().code, "NotFoundError");
+        }
+        e => panic!("unexpected error - {:?}", e),
+    }
+
+    headers.insert("x-ratelimit-reset", "1500".parse().unwrap());
+    let error = Error::from_response(
+        reqwest::StatusCode::TOO_MANY_REQUESTS,
+        &headers,
+        "Too Many Requests".to_string(),
+    );
+    assert!(error.is_transient());
+    match error {
+        Error::TooManyRequests { retry_after, error } => {
+            assert_eq!(retry_after, Some(std::time::Duration::from_millis(1500)));
+            assert!(error.error.is_none());
+            assert_eq!(error.body, "Too Many Requests");
+        }
+        e => panic!("unexpected error - {:?}", e),
+    }
+
+    headers.insert("retry-after", "3".parse().unwrap());
+    assert_eq!(
+        retry_after(&headers),
+        Some(std::time::Duration::from_secs(3))
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum CommandStatus {
+    Accepted,
+    Completed,
+    Failed,
+    Rejected,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct CommandResult {
     pub id: String,
-    pub status: String,
+    pub status: CommandStatus,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -638,28 +1281,330 @@ pub struct CommandResponse {
     pub results: Vec<CommandResult>,
 }
 
+impl CommandResponse {
+    /// Fail with the first result which is neither accepted nor completed
+    pub fn check(self) -> Result<Vec<CommandResult>, Error> {
+        if let Some(failed) = self.results.iter().find(|result| {
+            !matches!(
+                result.status,
+                CommandStatus::Accepted | CommandStatus::Completed
+            )
+        }) {
+            log::warn!("SmartThings command {} is {:?}", &failed.id, failed.status);
+            return Err(Error::CommandFailed(failed.clone()));
+        }
+
+        Ok(self.results)
+    }
+}
+
+#[test]
+fn test_command_response_check() {
+    let response: CommandResponse = serde_json::from_value(json!({
+        "results": [
+            { "id": "2a8f1c3e-4b5d-4e6f-8a7b-9c0d1e2f3a4b", "status": "ACCEPTED" }
+        ]
+    }))
+    .unwrap();
+    assert_eq!(response.check().unwrap().len(), 1);
+
+    let response: CommandResponse = serde_json::from_value(json!({
+        "results": [
+            { "id": "2a8f1c3e-4b5d-4e6f-8a7b-9c0d1e2f3a4b", "status": "COMPLETED" },
+            { "id": "5e6f7a8b-9c0d-4e1f-a2b3-c4d5e6f7a8b9", "status": "FAILED" }
+        ]
+    }))
+    .unwrap();
+    match response.check() {
+        Err(Error::CommandFailed(result)) => {
+            assert_eq!(result.id, "5e6f7a8b-9c0d-4e1f-a2b3-c4d5e6f7a8b9");
+            assert_eq!(result.status, CommandStatus::Failed);
+        }
+        r => panic!("unexpected result - {:?}", r.map(|_| ())),
+    }
+}
+
+/// Interval of status polling while waiting for a command to be reflected
+const CONFIRM_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+#[derive(Clone)]
+enum Credentials {
+    /// personal access token from https://account.smartthings.com/tokens
+    Token(String),
+    OAuth(OAuthSession),
+}
+
+impl Credentials {
+    /// Same for every client sending requests as the same user
+    fn scheduler_key(&self, api_url: &reqwest::Url) -> String {
+        match self {
+            Credentials::Token(token) => format!("{} token {}", api_url, token),
+            Credentials::OAuth(session) => {
+                format!("{} oauth {}", api_url, session.state_path().display())
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct ApiClient {
-    pub token: String,
+    credentials: Credentials,
     http_client: reqwest::Client,
+    api_url: reqwest::Url,
+    scheduler: std::sync::Arc<Scheduler>,
+}
+
+pub struct ApiClientBuilder {
+    credentials: Credentials,
+    api_url: reqwest::Url,
+    timeout: Option<std::time::Duration>,
+    user_agent: Option<String>,
+    rate_limit: RateLimit,
+    shared: bool,
+}
+
+impl ApiClientBuilder {
+    pub fn with_token(token: &str) -> Self {
+        Self::with_credentials(Credentials::Token(token.to_string()))
+    }
+
+    pub fn with_oauth(session: OAuthSession) -> Self {
+        Self::with_credentials(Credentials::OAuth(session))
+    }
+
+    fn with_credentials(credentials: Credentials) -> Self {
+        Self {
+            credentials,
+            api_url: API_URL.clone(),
+            timeout: None,
+            user_agent: None,
+            rate_limit: RateLimit::default(),
+            shared: true,
+        }
+    }
+
+    /// Root of the API, `API_URL` by default. Paths like `devices/{id}` are resolved against it.
+    pub fn api_url(mut self, mut api_url: reqwest::Url) -> Self {
+        if !api_url.path().ends_with('/') {
+            let path = format!("{}/", api_url.path());
+            api_url.set_path(&path);
+        }
+        self.api_url = api_url;
+        self
+    }
+
+    /// Timeout of each request, from connecting until the response body is read
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Limit of the token, shared with every client of it.
+    /// Only the first of the clients of a token alive at once sets it.
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    /// Don't share the limit and the merged GETs with other clients of the token.
+    /// For short-lived clients, like the ones of SmartApp lifecycle events.
+    pub fn unshared(mut self) -> Self {
+        self.shared = false;
+        self
+    }
+
+    pub fn build(self) -> Result<ApiClient, Error> {
+        let mut http_client = reqwest::Client::builder();
+        if let Some(timeout) = self.timeout {
+            http_client = http_client.timeout(timeout);
+        }
+        if let Some(user_agent) = self.user_agent {
+            http_client = http_client.user_agent(user_agent);
+        }
+
+        let scheduler = if self.shared {
+            Scheduler::shared(
+                &self.credentials.scheduler_key(&self.api_url),
+                self.rate_limit,
+            )
+        } else {
+            Scheduler::unshared(self.rate_limit)
+        };
+
+        Ok(ApiClient {
+            scheduler,
+            credentials: self.credentials,
+            http_client: http_client.build()?,
+            api_url: self.api_url,
+        })
+    }
+}
+
+#[test]
+fn test_api_client_builder() {
+    let client = ApiClientBuilder::with_token("token")
+        .api_url(reqwest::Url::parse("http://127.0.0.1:8080/v1").unwrap())
+        .timeout(std::time::Duration::from_secs(5))
+        .build()
+        .unwrap();
+    assert_eq!(
+        client.url("devices/cb2eddac").unwrap().as_str(),
+        "http://127.0.0.1:8080/v1/devices/cb2eddac"
+    );
+
+    let client = ApiClient::new("token");
+    assert_eq!(
+        client.url("locations").unwrap().as_str(),
+        "https://api.smartthings.com/v1/locations"
+    );
 }
 
 impl ApiClient {
     pub fn new(token: &str) -> Self {
-        Self {
-            token: token.to_string(),
-            http_client: reqwest::Client::new(),
+        ApiClientBuilder::with_token(token)
+            .build()
+            .expect("Failed to create HTTP client")
+    }
+
+    pub fn with_oauth(session: OAuthSession) -> Self {
+        ApiClientBuilder::with_oauth(session)
+            .build()
+            .expect("Failed to create HTTP client")
+    }
+
+    fn url(&self, path: &str) -> Result<reqwest::Url, Error> {
+        Ok(self.api_url.join(path)?)
+    }
+
+    async fn access_token(&self) -> Result<String, Error> {
+        match &self.credentials {
+            Credentials::Token(token) => Ok(token.clone()),
+            Credentials::OAuth(session) => session.access_token(&self.http_client).await,
         }
     }
 
-    pub async fn descriptor(&self, device_id: &str) -> Result<DeviceDescriptor, Error> {
-        Ok(self
-            .http_client
-            .get(BASE_URL.join(device_id)?)
-            .bearer_auth(&self.token)
+    /// Send a request built by `request`, once the rate limit allows.
+    /// When an OAuth access token is rejected, it is refreshed and the request is sent once more.
+    /// Responses other than 2xx are turned into `Error`.
+    async fn send<F>(&self, request: F) -> Result<reqwest::Response, Error>
+    where
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        let token = self.access_token().await?;
+        self.scheduler.acquire().await;
+        let mut response = request(&self.http_client)
+            .bearer_auth(&token)
             .send()
-            .await?
-            .json()
-            .await?)
+            .await?;
+
+        if let Credentials::OAuth(session) = &self.credentials {
+            if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+                let token = session.refresh(&self.http_client, &token).await?;
+                self.scheduler.acquire().await;
+                response = request(&self.http_client)
+                    .bearer_auth(&token)
+                    .send()
+                    .await?;
+            }
+        }
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(response)
+        } else {
+            let headers = response.headers().clone();
+            let body = response.text().await?;
+            let error = Error::from_response(status, &headers, body);
+            if let Error::TooManyRequests {
+                retry_after: Some(retry_after),
+                ..
+            } = &error
+            {
+                self.scheduler.pause(*retry_after);
+            }
+            Err(error)
+        }
+    }
+
+    /// GET `url`, sent again with backoff while it fails transiently.
+    /// Concurrent GETs of the same `url` are sent once.
+    async fn get<T: serde::de::DeserializeOwned>(&self, url: reqwest::Url) -> Result<T, Error> {
+        let client = self.clone();
+        let body = self
+            .scheduler
+            .coalesce(url.clone(), async move {
+                let mut attempt = 0;
+                loop {
+                    let error = match client.send(|http| http.get(url.clone())).await {
+                        Ok(response) => return Ok(response.bytes().await?.to_vec()),
+                        Err(e) => e,
+                    };
+                    match scheduler::retry_delay(&error, attempt) {
+                        Some(delay) => {
+                            log::warn!("retry GET {} in {:?} - {}", &url, delay, error);
+                            tokio::time::sleep(delay).await;
+                            attempt += 1;
+                        }
+                        None => return Err(error),
+                    }
+                }
+            })
+            .await?;
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    async fn get_all<T: serde::de::DeserializeOwned>(
+        &self,
+        url: reqwest::Url,
+    ) -> Result<Vec<T>, Error> {
+        let mut items = Vec::new();
+        let mut next = Some(url);
+        while let Some(url) = next.take() {
+            let page: Page<T> = self.get(url).await?;
+            items.extend(page.items);
+            next = page
+                .links
+                .and_then(|links| links.next)
+                .map(|link| reqwest::Url::parse(&link.href))
+                .transpose()?;
+        }
+
+        Ok(items)
+    }
+
+    pub async fn list_devices(
+        &self,
+        location_id: Option<&str>,
+    ) -> Result<Vec<DeviceDescriptor>, Error> {
+        let mut url = self.url("devices")?;
+        if let Some(location_id) = location_id {
+            url.query_pairs_mut().append_pair("locationId", location_id);
+        }
+        self.get_all(url).await
+    }
+
+    /// GET `path` relative to the API root, like `devices/{id}`, as it is returned
+    pub async fn get_json(&self, path: &str) -> Result<serde_json::Value, Error> {
+        self.get(self.url(path)?).await
+    }
+
+    pub async fn list_locations(&self) -> Result<Vec<Location>, Error> {
+        self.get_all(self.url("locations")?).await
+    }
+
+    pub async fn list_rooms(&self, location_id: &str) -> Result<Vec<Room>, Error> {
+        self.get_all(self.url(&format!("locations/{}/rooms", location_id))?)
+            .await
+    }
+
+    pub async fn descriptor(&self, device_id: &str) -> Result<DeviceDescriptor, Error> {
+        self.get(self.url(&format!("devices/{}", device_id))?).await
     }
 
     pub async fn component_status(
@@ -667,58 +1612,139 @@ impl ApiClient {
         device_id: &str,
         component_name: &str,
     ) -> Result<ComponentStatus, Error> {
-        Ok(self
-            .http_client
-            .get(BASE_URL.join(&format!(
-                "{}/components/{}/status",
-                device_id, component_name
-            ))?)
-            .bearer_auth(&self.token)
-            .send()
-            .await?
-            .json()
-            .await?)
+        self.get(self.url(&format!(
+            "devices/{}/components/{}/status",
+            device_id, component_name
+        ))?)
+        .await
+    }
+
+    /// Status of every component at once
+    pub async fn device_status(&self, device_id: &str) -> Result<DeviceStatus, Error> {
+        self.get(self.url(&format!("devices/{}/status", device_id))?)
+            .await
+    }
+
+    /// Same as `device_status`, but keep the values unparsed to apply events on them later
+    pub async fn raw_device_status(&self, device_id: &str) -> Result<RawDeviceStatus, Error> {
+        self.get(self.url(&format!("devices/{}/status", device_id))?)
+            .await
     }
 
-    pub async fn capability_status(
+    pub async fn device_health(&self, device_id: &str) -> Result<DeviceHealth, Error> {
+        self.get(self.url(&format!("devices/{}/health", device_id))?)
+            .await
+    }
+
+    /// Same as `component_status`, but keep the values unparsed to apply events on them later
+    pub async fn raw_component_status(
+        &self,
+        device_id: &str,
+        component_name: &str,
+    ) -> Result<RawComponentStatus, Error> {
+        self.get(self.url(&format!(
+            "devices/{}/components/{}/status",
+            device_id, component_name
+        ))?)
+        .await
+    }
+
+    /// Status of a single capability, like `capabilities::switch::Status`
+    pub async fn capability_status<T: serde::de::DeserializeOwned>(
         &self,
         device_id: &str,
         component_name: &str,
         capability: Capability,
-    ) -> Result<CapabilityStatus, Error> {
-        Ok(self
-            .http_client
-            .get(BASE_URL.join(&format!(
-                "{}/components/{}/capabilities/{}/status",
-                device_id,
-                component_name,
-                serde_qs::to_string(&capability).unwrap(),
-            ))?)
-            .bearer_auth(&self.token)
-            .send()
-            .await?
-            .json()
-            .await?)
+    ) -> Result<T, Error> {
+        self.get(self.url(&format!(
+            "devices/{}/components/{}/capabilities/{}/status",
+            device_id,
+            component_name,
+            serde_qs::to_string(&capability).unwrap(),
+        ))?)
+        .await
     }
 
-    pub async fn command<T: Into<CapabilityCommand>>(
+    /// Send every command in a single request, so they are applied together
+    pub async fn commands(
         &self,
         device_id: &str,
-        command: T,
-    ) -> Result<(), Error> {
-        let body = CapabilityCommandRequest {
-            commands: vec![command.into()],
-        };
+        commands: Vec<RawCommand>,
+    ) -> Result<Vec<CommandResult>, Error> {
+        let body = CapabilityCommandRequest { commands };
+        let url = self.url(&format!("devices/{}/commands", device_id))?;
         let ret: CommandResponse = self
-            .http_client
-            .post(BASE_URL.join(&format!("{}/commands", device_id))?)
-            .bearer_auth(&self.token)
-            .json(&body)
-            .send()
+            .send(|client| client.post(url.clone()).json(&body))
             .await?
             .json()
             .await?;
-        eprintln!("{:?}", ret);
+        log::debug!("command results of {} - {:?}", device_id, &ret);
+        ret.check()
+    }
+
+    /// Same as `commands`, but return only after the device reports every new value,
+    /// or fail with `Error::CommandNotConfirmed` when it doesn't within `timeout`.
+    pub async fn commands_confirmed(
+        &self,
+        device_id: &str,
+        commands: Vec<RawCommand>,
+        timeout: std::time::Duration,
+    ) -> Result<(), Error> {
+        self.commands(device_id, commands.clone()).await?;
+        self.wait_for_reflection(device_id, &commands, timeout)
+            .await
+    }
+
+    pub async fn command(&self, device_id: &str, command: RawCommand) -> Result<(), Error> {
+        self.commands(device_id, vec![command]).await?;
         Ok(())
     }
+
+    /// Same as `command`, but return only after the device reports the new value,
+    /// or fail with `Error::CommandNotConfirmed` when it doesn't within `timeout`.
+    pub async fn command_confirmed(
+        &self,
+        device_id: &str,
+        command: RawCommand,
+        timeout: std::time::Duration,
+    ) -> Result<(), Error> {
+        self.commands_confirmed(device_id, vec![command], timeout)
+            .await
+    }
+
+    async fn wait_for_reflection(
+        &self,
+        device_id: &str,
+        commands: &[RawCommand],
+        timeout: std::time::Duration,
+    ) -> Result<(), Error> {
+        let mut component_names: Vec<&str> = commands
+            .iter()
+            .map(|command| command.component.as_str())
+            .collect();
+        component_names.sort_unstable();
+        component_names.dedup();
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let mut reflected = true;
+            for component_name in &component_names {
+                let status = self.component_status(device_id, component_name).await?;
+                reflected &= commands
+                    .iter()
+                    .filter(|command| command.component == *component_name)
+                    .all(|command| command.is_reflected_in(&status) == Some(true));
+                if !reflected {
+                    break;
+                }
+            }
+            if reflected {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() + CONFIRM_POLL_INTERVAL > deadline {
+                return Err(Error::CommandNotConfirmed);
+            }
+            tokio::time::sleep(CONFIRM_POLL_INTERVAL).await;
+        }
+    }
 }
@@ -0,0 +1,245 @@
+//! Mock SmartThings API for tests.
+//!
+//! GET requests are answered with recorded fixtures, and commands and scene executions are
+//! recorded instead of being sent to devices. Updated rules replace their fixtures.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::Path as UrlPath,
+    http::{StatusCode, Uri},
+    response::IntoResponse,
+    routing::{get, post, put},
+    Extension, Json, Router,
+};
+
+use crate::{ApiClient, ApiClientBuilder};
+
+/// Fixtures shipped with this crate, recorded from a Samsung air conditioner
+pub const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+/// Id of the air conditioner in `FIXTURES_DIR`
+pub const FIXTURE_DEVICE_ID: &str = "cb2eddac-bfd2-1057-7493-3a0a573e507a";
+
+/// Command received by the mock server
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct ReceivedCommand {
+    #[serde(skip)]
+    pub device_id: String,
+    pub component: String,
+    pub capability: String,
+    pub command: String,
+    #[serde(default)]
+    pub arguments: Vec<serde_json::Value>,
+}
+
+#[derive(serde::Deserialize)]
+struct CommandsBody {
+    commands: Vec<ReceivedCommand>,
+}
+
+#[derive(Default)]
+struct MockState {
+    /// response bodies by path relative to the API root
+    fixtures: Mutex<HashMap<String, serde_json::Value>>,
+    commands: Mutex<Vec<ReceivedCommand>>,
+    executed_scenes: Mutex<Vec<String>>,
+}
+
+pub struct MockServer {
+    api_url: reqwest::Url,
+    state: Arc<MockState>,
+    shutdown: Option<tokio::sync::oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    /// Serve on a random local port until dropped
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let state = Arc::new(MockState::default());
+
+        let app = Router::new()
+            .route("/v1/devices/:device_id/commands", post(handle_commands))
+            .route("/v1/scenes/:scene_id/execute", post(handle_execute_scene))
+            .route(
+                "/v1/rules/:rule_id",
+                put(handle_update_rule).get(handle_fixture),
+            )
+            .fallback(get(handle_fixture))
+            .layer(Extension(state.clone()));
+
+        let (shutdown, shutdown_signal) = tokio::sync::oneshot::channel::<()>();
+        let server = axum::Server::from_tcp(listener)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
+            .serve(app.into_make_service())
+            .with_graceful_shutdown(async move {
+                let _ = shutdown_signal.await;
+            });
+        tokio::spawn(async move {
+            if let Err(e) = server.await {
+                log::error!("mock SmartThings server failed - {}", e);
+            }
+        });
+
+        Ok(Self {
+            api_url: reqwest::Url::parse(&format!("http://{}/v1/", address)).unwrap(),
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// Same as `start`, serving every fixture in `FIXTURES_DIR`
+    pub async fn with_fixtures() -> std::io::Result<Self> {
+        let server = Self::start().await?;
+        server.load_fixtures(FIXTURES_DIR)?;
+        Ok(server)
+    }
+
+    pub fn api_url(&self) -> &reqwest::Url {
+        &self.api_url
+    }
+
+    /// Client of this server, authorized with a dummy token
+    pub fn client(&self) -> ApiClient {
+        ApiClientBuilder::with_token("mock-token")
+            .api_url(self.api_url.clone())
+            .build()
+            .unwrap()
+    }
+
+    /// Answer GET `path`, relative to the API root like `devices/{id}`, with `body`.
+    /// Query strings are ignored when matching.
+    pub fn add_fixture(&self, path: &str, body: serde_json::Value) {
+        self.state
+            .fixtures
+            .lock()
+            .unwrap()
+            .insert(path.trim_matches('/').to_string(), body);
+    }
+
+    /// Add every `.json` file under `dir`, served at its path without the extension.
+    /// `dir/devices/{id}/components/main/status.json` answers `devices/{id}/components/main/status`.
+    pub fn load_fixtures<P: AsRef<Path>>(&self, dir: P) -> std::io::Result<()> {
+        fn visit(server: &MockServer, root: &Path, dir: &Path) -> std::io::Result<()> {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    visit(server, root, &path)?;
+                } else if path.extension().map_or(false, |o| o == "json") {
+                    let body = serde_json::from_slice(&std::fs::read(&path)?)?;
+                    let relative = path.strip_prefix(root).unwrap().with_extension("");
+                    let relative: Vec<_> = relative
+                        .components()
+                        .map(|o| o.as_os_str().to_string_lossy())
+                        .collect();
+                    server.add_fixture(&relative.join("/"), body);
+                }
+            }
+            Ok(())
+        }
+
+        visit(self, dir.as_ref(), dir.as_ref())
+    }
+
+    /// Commands received so far, in order
+    pub fn commands(&self) -> Vec<ReceivedCommand> {
+        self.state.commands.lock().unwrap().clone()
+    }
+
+    /// Ids of scenes executed so far, in order
+    pub fn executed_scenes(&self) -> Vec<String> {
+        self.state.executed_scenes.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+async fn handle_fixture(
+    Extension(state): Extension<Arc<MockState>>,
+    uri: Uri,
+) -> impl IntoResponse {
+    let path = uri.path().trim_start_matches("/v1/").trim_matches('/');
+    match state.fixtures.lock().unwrap().get(path) {
+        Some(body) => (StatusCode::OK, Json(body.clone())),
+        None => {
+            log::warn!("no fixture for {}", path);
+            (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({
+                    "requestId": "mock",
+                    "error": {
+                        "code": "NotFoundError",
+                        "message": format!("No fixture for {}", path),
+                        "details": []
+                    }
+                })),
+            )
+        }
+    }
+}
+
+async fn handle_commands(
+    Extension(state): Extension<Arc<MockState>>,
+    UrlPath(device_id): UrlPath<String>,
+    Json(body): Json<CommandsBody>,
+) -> Json<serde_json::Value> {
+    let mut commands = state.commands.lock().unwrap();
+    let results: Vec<_> = body
+        .commands
+        .into_iter()
+        .map(|mut command| {
+            command.device_id = device_id.clone();
+            commands.push(command);
+            serde_json::json!({
+                "id": format!("mock-command-{}", commands.len()),
+                "status": "ACCEPTED"
+            })
+        })
+        .collect();
+
+    Json(serde_json::json!({ "results": results }))
+}
+
+async fn handle_execute_scene(
+    Extension(state): Extension<Arc<MockState>>,
+    UrlPath(scene_id): UrlPath<String>,
+) -> Json<serde_json::Value> {
+    state.executed_scenes.lock().unwrap().push(scene_id);
+    Json(serde_json::json!({ "status": "success" }))
+}
+
+/// Store the rule as the fixture of `rules/{id}` and in the `rules` list
+async fn handle_update_rule(
+    Extension(state): Extension<Arc<MockState>>,
+    UrlPath(rule_id): UrlPath<String>,
+    Json(mut rule): Json<serde_json::Value>,
+) -> Json<serde_json::Value> {
+    rule["id"] = serde_json::Value::String(rule_id.clone());
+
+    let mut fixtures = state.fixtures.lock().unwrap();
+    if let Some(items) = fixtures
+        .get_mut("rules")
+        .and_then(|rules| rules["items"].as_array_mut())
+    {
+        for item in items
+            .iter_mut()
+            .filter(|item| item["id"] == rule_id.as_str())
+        {
+            *item = rule.clone();
+        }
+    }
+    fixtures.insert(format!("rules/{}", rule_id), rule.clone());
+
+    Json(rule)
+}
@@ -0,0 +1,271 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::Error;
+
+pub const AUTHORIZE_URL: once_cell::sync::Lazy<reqwest::Url> = once_cell::sync::Lazy::new(|| {
+    reqwest::Url::parse("https://api.smartthings.com/oauth/authorize").unwrap()
+});
+
+pub const TOKEN_URL: once_cell::sync::Lazy<reqwest::Url> = once_cell::sync::Lazy::new(|| {
+    reqwest::Url::parse("https://auth-global.api.smartthings.com/oauth/token").unwrap()
+});
+
+/// Tokens are refreshed this long before they actually expire
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Credentials of a SmartThings API Access app, created with `smartthings apps:create`
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct OAuthApp {
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OAuthTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// unix timestamp in seconds
+    pub expires_at: u64,
+}
+
+impl OAuthTokens {
+    fn is_expired(&self) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        now + EXPIRY_MARGIN >= Duration::from_secs(self.expires_at)
+    }
+
+    pub async fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(serde_json::from_slice(&tokio::fs::read(path).await?)?)
+    }
+
+    /// Write tokens to a temporary file first, so an interrupted write never loses the refresh token.
+    /// The file is readable only by its owner on unix.
+    pub async fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let temp_path = path.with_extension("tmp");
+        // the mode applies only to a new file, so one left by an interrupted save is replaced
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        drop(options.open(&temp_path).await?);
+        tokio::fs::write(&temp_path, serde_json::to_vec_pretty(self)?).await?;
+        tokio::fs::rename(&temp_path, path).await?;
+        Ok(())
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: String,
+    expires_in: u64,
+}
+
+impl From<TokenResponse> for OAuthTokens {
+    fn from(response: TokenResponse) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Self {
+            access_token: response.access_token,
+            refresh_token: response.refresh_token,
+            expires_at: now.as_secs() + response.expires_in,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+#[test]
+fn test_token_response_deserialization() {
+    let response: TokenResponse = serde_json::from_value(serde_json::json!({
+        "access_token": "6a2c4a0f-4b0e-4f6a-9d7c-1d2c3b4a5f6e",
+        "token_type": "bearer",
+        "refresh_token": "0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0",
+        "expires_in": 86399,
+        "scope": "r:devices:* x:devices:*",
+        "installed_app_id": "5d1a0a4e-7b6f-4c3d-9e2f-1a0b9c8d7e6f"
+    }))
+    .unwrap();
+    let tokens = OAuthTokens::from(response);
+    assert_eq!(tokens.refresh_token, "0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0");
+    assert!(!tokens.is_expired());
+
+    let expired = OAuthTokens {
+        expires_at: tokens.expires_at - 86399,
+        ..tokens
+    };
+    assert!(expired.is_expired());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_save_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = std::env::temp_dir().join(format!("oauth_test_{}.json", std::process::id()));
+    let tokens = OAuthTokens {
+        access_token: "6a2c4a0f-4b0e-4f6a-9d7c-1d2c3b4a5f6e".to_string(),
+        refresh_token: "0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0".to_string(),
+        expires_at: 0,
+    };
+    tokens.save(&path).await.unwrap();
+    let metadata = tokio::fs::metadata(&path).await.unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    assert_eq!(
+        OAuthTokens::load(&path).await.unwrap().refresh_token,
+        tokens.refresh_token
+    );
+    tokio::fs::remove_file(&path).await.unwrap();
+}
+
+impl OAuthApp {
+    pub fn authorize_url(&self, scopes: &[&str]) -> reqwest::Url {
+        let mut url = AUTHORIZE_URL.clone();
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri)
+            .append_pair("scope", &scopes.join(" "));
+        url
+    }
+
+    async fn request_token(
+        &self,
+        http_client: &reqwest::Client,
+        form: &[(&str, &str)],
+    ) -> Result<OAuthTokens, Error> {
+        let response = http_client
+            .post(TOKEN_URL.clone())
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(form)
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            Ok(response.json::<TokenResponse>().await?.into())
+        } else {
+            let status = response.status();
+            let body = response.text().await?;
+            Err(Error::OAuthError(
+                match serde_json::from_str::<TokenErrorResponse>(&body) {
+                    Ok(TokenErrorResponse {
+                        error,
+                        error_description: Some(description),
+                    }) => format!("{} - {}", error, description),
+                    Ok(TokenErrorResponse { error, .. }) => error,
+                    Err(_) => format!("{} - {}", status, body),
+                },
+            ))
+        }
+    }
+
+    pub async fn exchange_code(
+        &self,
+        http_client: &reqwest::Client,
+        code: &str,
+    ) -> Result<OAuthTokens, Error> {
+        self.request_token(
+            http_client,
+            &[
+                ("grant_type", "authorization_code"),
+                ("client_id", &self.client_id),
+                ("redirect_uri", &self.redirect_uri),
+                ("code", code),
+            ],
+        )
+        .await
+    }
+
+    pub async fn refresh(
+        &self,
+        http_client: &reqwest::Client,
+        refresh_token: &str,
+    ) -> Result<OAuthTokens, Error> {
+        self.request_token(
+            http_client,
+            &[
+                ("grant_type", "refresh_token"),
+                ("client_id", &self.client_id),
+                ("refresh_token", refresh_token),
+            ],
+        )
+        .await
+    }
+}
+
+/// OAuth tokens shared by every clone of an `ApiClient`.
+///
+/// SmartThings rotates the refresh token on each refresh, so all clients using the same
+/// state file must share one session.
+#[derive(Clone)]
+pub struct OAuthSession {
+    app: Arc<OAuthApp>,
+    state_path: Arc<PathBuf>,
+    tokens: Arc<tokio::sync::Mutex<OAuthTokens>>,
+}
+
+impl OAuthSession {
+    pub fn state_path(&self) -> &Path {
+        &self.state_path
+    }
+
+    pub async fn load<P: Into<PathBuf>>(app: OAuthApp, state_path: P) -> Result<Self, Error> {
+        let state_path = state_path.into();
+        let tokens = OAuthTokens::load(&state_path).await?;
+        Ok(Self {
+            app: Arc::new(app),
+            state_path: Arc::new(state_path),
+            tokens: Arc::new(tokio::sync::Mutex::new(tokens)),
+        })
+    }
+
+    /// Return a valid access token, refreshing it first when it is about to expire
+    pub(crate) async fn access_token(
+        &self,
+        http_client: &reqwest::Client,
+    ) -> Result<String, Error> {
+        let mut tokens = self.tokens.lock().await;
+        if tokens.is_expired() {
+            *tokens = self.refresh_locked(http_client, &tokens).await?;
+        }
+        Ok(tokens.access_token.clone())
+    }
+
+    /// Refresh tokens after `rejected_token` was refused by the API.
+    /// Nothing happens when another request has already replaced that token.
+    pub(crate) async fn refresh(
+        &self,
+        http_client: &reqwest::Client,
+        rejected_token: &str,
+    ) -> Result<String, Error> {
+        let mut tokens = self.tokens.lock().await;
+        if tokens.access_token == rejected_token {
+            *tokens = self.refresh_locked(http_client, &tokens).await?;
+        }
+        Ok(tokens.access_token.clone())
+    }
+
+    async fn refresh_locked(
+        &self,
+        http_client: &reqwest::Client,
+        tokens: &OAuthTokens,
+    ) -> Result<OAuthTokens, Error> {
+        log::info!("refresh SmartThings OAuth token");
+        let refreshed = self.app.refresh(http_client, &tokens.refresh_token).await?;
+        refreshed.save(self.state_path.as_ref()).await?;
+        Ok(refreshed)
+    }
+}
@@ -0,0 +1,281 @@
+//! Requests of every `ApiClient` sharing a token go through one `Scheduler`, which keeps
+//! them under the rate limit of the token and merges concurrent identical GETs.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use futures::{
+    future::{BoxFuture, Shared},
+    FutureExt,
+};
+use rand::Rng;
+use tokio::time::Instant;
+
+use crate::Error;
+
+/// GETs failing transiently are sent this many more times
+pub(crate) const MAX_RETRIES: u32 = 3;
+
+/// Delay before the first retry, doubled on each one
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+
+/// SHA-256 of a scheduler key, so tokens aren't kept around in `SCHEDULERS`
+type KeyDigest = [u8; 32];
+
+/// Schedulers by API root and token, so clients created separately for the same token
+/// share the limit. A scheduler is dropped with the last client using it.
+static SCHEDULERS: once_cell::sync::Lazy<Mutex<HashMap<KeyDigest, Weak<Scheduler>>>> =
+    once_cell::sync::Lazy::new(Default::default);
+
+/// Token bucket refilled at `per_minute`, holding at most `burst` requests
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+impl Default for RateLimit {
+    /// SmartThings allows 250 requests per minute for a token
+    fn default() -> Self {
+        Self {
+            burst: 50,
+            per_minute: 250,
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// set by 429 responses, no request is sent until then
+    paused_until: Option<Instant>,
+}
+
+type SharedResponse = Shared<BoxFuture<'static, Result<Arc<Vec<u8>>, Arc<Error>>>>;
+
+pub(crate) struct Scheduler {
+    limit: RateLimit,
+    bucket: Mutex<Bucket>,
+    in_flight: Mutex<HashMap<reqwest::Url, SharedResponse>>,
+}
+
+impl Scheduler {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            bucket: Mutex::new(Bucket {
+                tokens: limit.burst as f64,
+                updated: Instant::now(),
+                paused_until: None,
+            }),
+            in_flight: Default::default(),
+        }
+    }
+
+    /// Scheduler of its own, for a client not sharing the limit
+    pub(crate) fn unshared(limit: RateLimit) -> Arc<Self> {
+        Arc::new(Self::new(limit))
+    }
+
+    /// Scheduler of `key`, created with `limit` if no client uses one yet
+    pub(crate) fn shared(key: &str, limit: RateLimit) -> Arc<Self> {
+        let digest: KeyDigest = ring::digest::digest(&ring::digest::SHA256, key.as_bytes())
+            .as_ref()
+            .try_into()
+            .unwrap();
+
+        let mut schedulers = SCHEDULERS.lock().unwrap();
+        if let Some(scheduler) = schedulers.get(&digest).and_then(Weak::upgrade) {
+            return scheduler;
+        }
+        schedulers.retain(|_, scheduler| scheduler.strong_count() > 0);
+        let scheduler = Arc::new(Self::new(limit));
+        schedulers.insert(digest, Arc::downgrade(&scheduler));
+        scheduler
+    }
+
+    /// Wait until a request may be sent
+    pub(crate) async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                match bucket.paused_until {
+                    Some(until) if until > now => until - now,
+                    _ => {
+                        bucket.paused_until = None;
+                        let per_second = self.limit.per_minute as f64 / 60.0;
+                        let refilled = (now - bucket.updated).as_secs_f64() * per_second;
+                        bucket.tokens = (bucket.tokens + refilled).min(self.limit.burst as f64);
+                        bucket.updated = now;
+                        if bucket.tokens >= 1.0 {
+                            bucket.tokens -= 1.0;
+                            return;
+                        }
+                        Duration::from_secs_f64((1.0 - bucket.tokens) / per_second)
+                    }
+                }
+            };
+            log::debug!("rate limited, wait {:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Hold every request until `retry_after` has passed
+    pub(crate) fn pause(&self, retry_after: Duration) {
+        let mut bucket = self.bucket.lock().unwrap();
+        let until = Instant::now() + retry_after;
+        bucket.paused_until = Some(bucket.paused_until.map_or(until, |o| o.max(until)));
+        bucket.tokens = 0.0;
+    }
+
+    /// Run `request` for `url`, or wait for the same request already running
+    pub(crate) async fn coalesce<F>(
+        self: &Arc<Self>,
+        url: reqwest::Url,
+        request: F,
+    ) -> Result<Arc<Vec<u8>>, Error>
+    where
+        F: Future<Output = Result<Vec<u8>, Error>> + Send + 'static,
+    {
+        let shared = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(&url) {
+                Some(shared) => {
+                    log::trace!("join request in flight {}", &url);
+                    shared.clone()
+                }
+                None => {
+                    let scheduler = self.clone();
+                    let key = url.clone();
+                    let shared = async move {
+                        let ret = request.await.map(Arc::new).map_err(Arc::new);
+                        scheduler.in_flight.lock().unwrap().remove(&key);
+                        ret
+                    }
+                    .boxed()
+                    .shared();
+                    in_flight.insert(url, shared.clone());
+                    shared
+                }
+            }
+        };
+
+        // the error is shared only when another caller still holds it
+        shared
+            .await
+            .map_err(|e| Arc::try_unwrap(e).unwrap_or_else(Error::Shared))
+    }
+}
+
+/// Delay before sending a failed GET again, `None` when it shouldn't be
+pub(crate) fn retry_delay(error: &Error, attempt: u32) -> Option<Duration> {
+    if attempt >= MAX_RETRIES || !error.is_transient() {
+        return None;
+    }
+
+    let jitter = |max: Duration| max.mul_f64(rand::thread_rng().gen_range(0.0..1.0));
+    Some(match error.inner() {
+        Error::TooManyRequests {
+            retry_after: Some(retry_after),
+            ..
+        } => *retry_after + jitter(BACKOFF_BASE),
+        _ => {
+            // half of the backoff is fixed, so retries never come right away
+            let backoff = BACKOFF_BASE * 2u32.pow(attempt);
+            backoff / 2 + jitter(backoff / 2)
+        }
+    })
+}
+
+#[test]
+fn test_shared() {
+    let first = Scheduler::shared("test_shared token", RateLimit::default());
+    let second = Scheduler::shared("test_shared token", RateLimit::default());
+    assert!(Arc::ptr_eq(&first, &second));
+    assert!(!Arc::ptr_eq(
+        &first,
+        &Scheduler::unshared(RateLimit::default())
+    ));
+
+    // dropped with its last client
+    let weak = Arc::downgrade(&first);
+    drop((first, second));
+    assert!(weak.upgrade().is_none());
+    let third = Scheduler::shared("test_shared token", RateLimit::default());
+    assert_eq!(Arc::strong_count(&third), 1);
+}
+
+#[tokio::test]
+async fn test_token_bucket() {
+    let scheduler = Scheduler::new(RateLimit {
+        burst: 2,
+        per_minute: 600,
+    });
+
+    let started = Instant::now();
+    scheduler.acquire().await;
+    scheduler.acquire().await;
+    assert!(started.elapsed() < Duration::from_millis(50));
+    // refilled at 10 per second
+    scheduler.acquire().await;
+    assert!(started.elapsed() >= Duration::from_millis(90));
+
+    scheduler.pause(Duration::from_millis(200));
+    let paused = Instant::now();
+    scheduler.acquire().await;
+    assert!(paused.elapsed() >= Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn test_coalesce() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let scheduler = Arc::new(Scheduler::new(RateLimit::default()));
+    let url =
+        reqwest::Url::parse("https://api.smartthings.com/v1/devices/cb2eddac/status").unwrap();
+    let sent = Arc::new(AtomicUsize::new(0));
+    let request = || {
+        let sent = sent.clone();
+        async move {
+            sent.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(b"{}".to_vec())
+        }
+    };
+
+    let (first, second) = tokio::join!(
+        scheduler.coalesce(url.clone(), request()),
+        scheduler.coalesce(url.clone(), request())
+    );
+    assert_eq!(sent.load(Ordering::SeqCst), 1);
+    assert_eq!(first.unwrap(), second.unwrap());
+
+    // finished requests are not reused
+    scheduler.coalesce(url, request()).await.unwrap();
+    assert_eq!(sent.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_retry_delay() {
+    let too_many_requests = |retry_after| Error::TooManyRequests {
+        retry_after,
+        error: crate::ApiError {
+            status: reqwest::StatusCode::TOO_MANY_REQUESTS,
+            request_id: None,
+            error: None,
+            body: String::new(),
+        },
+    };
+
+    let delay = retry_delay(&too_many_requests(Some(Duration::from_secs(3))), 0).unwrap();
+    assert!(delay >= Duration::from_secs(3) && delay < Duration::from_secs(3) + BACKOFF_BASE);
+    let delay = retry_delay(&too_many_requests(None), 2).unwrap();
+    assert!(delay >= BACKOFF_BASE * 2 && delay < BACKOFF_BASE * 4);
+    assert!(retry_delay(&too_many_requests(None), MAX_RETRIES).is_none());
+    assert!(retry_delay(&Error::CommandNotConfirmed, 0).is_none());
+}
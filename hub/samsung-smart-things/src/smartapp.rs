@@ -0,0 +1,842 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use crate::{ApiClient, Error, HealthState};
+
+/// Certificates of the keys SmartThings signs webhook requests with are served from here
+pub const KEY_URL: once_cell::sync::Lazy<reqwest::Url> =
+    once_cell::sync::Lazy::new(|| reqwest::Url::parse("https://key.smartthings.com").unwrap());
+
+/// Headers a request signature must cover, so neither the target, the body nor the time can be
+/// swapped under a captured signature
+const SIGNED_HEADERS: &[&str] = &["(request-target)", "digest", "date"];
+
+/// Allowed difference between the `Date` of a signed request and the local clock
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(5 * 60);
+
+/// Domain SmartThings sends the confirmation URL of a newly registered SmartApp on
+const CONFIRMATION_DOMAIN: &str = "smartthings.com";
+
+/// Certificates kept by a `SignatureVerifier`. SmartThings signs with few keys at a time.
+const MAX_CERTIFICATES: usize = 8;
+
+/// Request sent to a webhook SmartApp
+#[derive(Debug, serde::Deserialize)]
+#[serde(tag = "lifecycle", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LifecycleRequest {
+    #[serde(rename_all = "camelCase")]
+    Ping { ping_data: PingData },
+    /// Sent once the app is registered, which has to be confirmed by fetching `confirmation_url`
+    #[serde(rename_all = "camelCase")]
+    Confirmation { confirmation_data: ConfirmationData },
+    #[serde(rename_all = "camelCase")]
+    Configuration {
+        configuration_data: ConfigurationData,
+    },
+    #[serde(rename_all = "camelCase")]
+    Install { install_data: InstallData },
+    #[serde(rename_all = "camelCase")]
+    Update { update_data: InstallData },
+    #[serde(rename_all = "camelCase")]
+    Event { event_data: EventData },
+    #[serde(rename_all = "camelCase")]
+    Uninstall { uninstall_data: UninstallData },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct PingData {
+    pub challenge: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmationData {
+    pub app_id: String,
+    pub confirmation_url: String,
+}
+
+impl ConfirmationData {
+    /// `confirmation_url`, which must be an https URL on a smartthings.com host, as it is
+    /// fetched on behalf of whoever sent the request
+    pub fn confirmation_url(&self) -> Result<reqwest::Url, Error> {
+        let url = reqwest::Url::parse(&self.confirmation_url)?;
+        let on_smart_things = matches!(
+            url.host_str().and_then(|host| host.strip_suffix(CONFIRMATION_DOMAIN)),
+            Some(subdomain) if subdomain.is_empty() || subdomain.ends_with('.')
+        );
+        if url.scheme() != "https" || !on_smart_things {
+            return Err(Error::InvalidConfirmationUrl(self.confirmation_url.clone()));
+        }
+        Ok(url)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ConfigurationPhase {
+    Initialize,
+    Page,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigurationData {
+    pub installed_app_id: String,
+    pub phase: ConfigurationPhase,
+    #[serde(default)]
+    pub page_id: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceConfig {
+    pub device_id: String,
+    pub component_id: String,
+}
+
+/// Value of a setting the user chose while installing the app
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "valueType", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ConfigEntry {
+    #[serde(rename_all = "camelCase")]
+    Device { device_config: DeviceConfig },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledApp {
+    pub installed_app_id: String,
+    pub location_id: String,
+    /// entries by setting id
+    #[serde(default)]
+    pub config: HashMap<String, Vec<ConfigEntry>>,
+}
+
+impl InstalledApp {
+    /// Devices chosen for the setting `setting_id`
+    pub fn devices(&self, setting_id: &str) -> impl Iterator<Item = &DeviceConfig> {
+        self.config
+            .get(setting_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| match entry {
+                ConfigEntry::Device { device_config } => Some(device_config),
+                ConfigEntry::Other => None,
+            })
+    }
+}
+
+/// Payload of both INSTALL and UPDATE
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallData {
+    /// short-lived token scoped to the installed app
+    pub auth_token: String,
+    pub installed_app: InstalledApp,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventData {
+    pub auth_token: String,
+    pub installed_app: InstalledApp,
+    pub events: Vec<Event>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UninstallData {
+    pub installed_app: InstalledApp,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(tag = "eventType", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Event {
+    #[serde(rename_all = "camelCase")]
+    DeviceEvent { device_event: DeviceEvent },
+    #[serde(rename_all = "camelCase")]
+    DeviceHealthEvent {
+        device_health_event: DeviceHealthEvent,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Device went online or offline
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceHealthEvent {
+    pub device_id: String,
+    pub status: HealthState,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// New value of a single attribute
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceEvent {
+    #[serde(default)]
+    pub subscription_name: Option<String>,
+    pub device_id: String,
+    pub component_id: String,
+    pub capability: String,
+    pub attribute: String,
+    pub value: serde_json::Value,
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub state_change: bool,
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LifecycleResponse {
+    PingData {
+        challenge: String,
+    },
+    /// URL of the app, answered to CONFIRMATION
+    TargetUrl(String),
+    ConfigurationData(ConfigurationResponse),
+    InstallData {},
+    UpdateData {},
+    EventData {},
+    UninstallData {},
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConfigurationResponse {
+    Initialize(InitializeResponse),
+    Page(ConfigurationPage),
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeResponse {
+    pub name: String,
+    pub description: String,
+    pub id: String,
+    pub permissions: Vec<String>,
+    pub first_page_id: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigurationPage {
+    pub page_id: String,
+    pub name: String,
+    pub next_page_id: Option<String>,
+    pub previous_page_id: Option<String>,
+    pub complete: bool,
+    pub sections: Vec<Section>,
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Section {
+    pub name: String,
+    pub settings: Vec<Setting>,
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Setting {
+    #[serde(rename_all = "camelCase")]
+    Device {
+        id: String,
+        name: String,
+        description: String,
+        required: bool,
+        multiple: bool,
+        capabilities: Vec<String>,
+        permissions: Vec<String>,
+    },
+}
+
+#[test]
+fn test_lifecycle_deserialization() {
+    let request: LifecycleRequest = serde_json::from_value(serde_json::json!({
+        "lifecycle": "EVENT",
+        "executionId": "b328f242-c602-4204-8d73-33c48ae180af",
+        "locale": "en",
+        "version": "1.0.0",
+        "eventData": {
+            "authToken": "580aff1d-0a2b-4b4f-9a3e-5c0c6c1b3f0e",
+            "installedApp": {
+                "installedAppId": "d692699d-e7a6-400d-a0b7-d5be96e7a564",
+                "locationId": "e675a3d9-2499-406c-86dc-8a492a886494",
+                "config": {
+                    "devices": [{
+                        "valueType": "DEVICE",
+                        "deviceConfig": {
+                            "deviceId": "cb2eddac-bfd2-1057-7493-3a0a573e507a",
+                            "componentId": "main"
+                        }
+                    }],
+                    "title": [{
+                        "valueType": "STRING",
+                        "stringConfig": { "value": "Air conditioners" }
+                    }]
+                }
+            },
+            "events": [{
+                "eventTime": "2026-10-17T09:30:00Z",
+                "eventType": "DEVICE_EVENT",
+                "deviceEvent": {
+                    "subscriptionName": "cb2eddac-bfd2-1057-7493-3a0a573e507a",
+                    "eventId": "736e3903-001c-4d40-b408-ff40d162a06b",
+                    "locationId": "e675a3d9-2499-406c-86dc-8a492a886494",
+                    "deviceId": "cb2eddac-bfd2-1057-7493-3a0a573e507a",
+                    "componentId": "main",
+                    "capability": "thermostatCoolingSetpoint",
+                    "attribute": "coolingSetpoint",
+                    "value": 24,
+                    "unit": "C",
+                    "valueType": "number",
+                    "stateChange": true
+                }
+            }, {
+                "eventTime": "2026-10-17T09:31:00Z",
+                "eventType": "DEVICE_HEALTH_EVENT",
+                "deviceHealthEvent": {
+                    "eventId": "2a1f3b4c-5d6e-4f70-8192-a3b4c5d6e7f8",
+                    "locationId": "e675a3d9-2499-406c-86dc-8a492a886494",
+                    "deviceId": "cb2eddac-bfd2-1057-7493-3a0a573e507a",
+                    "hubId": null,
+                    "status": "OFFLINE",
+                    "reason": "NONE"
+                }
+            }, {
+                "eventTime": "2026-10-17T09:30:00Z",
+                "eventType": "TIMER_EVENT",
+                "timerEvent": {}
+            }]
+        }
+    }))
+    .unwrap();
+
+    match request {
+        LifecycleRequest::Event { event_data } => {
+            let devices: Vec<_> = event_data.installed_app.devices("devices").collect();
+            assert_eq!(devices.len(), 1);
+            assert_eq!(devices[0].device_id, "cb2eddac-bfd2-1057-7493-3a0a573e507a");
+
+            match &event_data.events[..] {
+                [Event::DeviceEvent { device_event }, Event::DeviceHealthEvent {
+                    device_health_event,
+                }, Event::Other] => {
+                    assert_eq!(device_health_event.status, HealthState::Offline);
+                    assert_eq!(device_event.attribute, "coolingSetpoint");
+                    assert_eq!(device_event.value, serde_json::json!(24));
+                    assert_eq!(device_event.unit.as_deref(), Some("C"));
+                }
+                events => panic!("unexpected events {:?}", events),
+            }
+        }
+        request => panic!("unexpected request {:?}", request),
+    }
+
+    assert_eq!(
+        serde_json::to_value(&LifecycleResponse::PingData {
+            challenge: "1a904d57-4fab-4b15-a11e-1c4bfe7cb502".to_string()
+        })
+        .unwrap(),
+        serde_json::json!({ "pingData": { "challenge": "1a904d57-4fab-4b15-a11e-1c4bfe7cb502" } })
+    );
+    assert_eq!(
+        serde_json::to_value(&LifecycleResponse::InstallData {}).unwrap(),
+        serde_json::json!({ "installData": {} })
+    );
+}
+
+#[test]
+fn test_confirmation() {
+    let request: LifecycleRequest = serde_json::from_value(serde_json::json!({
+        "lifecycle": "CONFIRMATION",
+        "executionId": "8a4c5b3e-2f1d-4e6a-9b7c-0d1e2f3a4b5c",
+        "appId": "6f1e7d2a-3b4c-4d5e-8f9a-0b1c2d3e4f5a",
+        "locale": "en",
+        "version": "0.1.0",
+        "confirmationData": {
+            "appId": "6f1e7d2a-3b4c-4d5e-8f9a-0b1c2d3e4f5a",
+            "confirmationUrl": "https://api.smartthings.com/apps/6f1e7d2a-3b4c-4d5e-8f9a-0b1c2d3e4f5a/confirm-registration?token=9a8b7c6d"
+        },
+        "settings": {}
+    }))
+    .unwrap();
+    let mut confirmation_data = match request {
+        LifecycleRequest::Confirmation { confirmation_data } => confirmation_data,
+        request => panic!("unexpected request {:?}", request),
+    };
+    assert_eq!(
+        confirmation_data.confirmation_url().unwrap().host_str(),
+        Some("api.smartthings.com")
+    );
+
+    for confirmation_url in [
+        "http://api.smartthings.com/apps/confirm-registration",
+        "https://smartthings.com.example.com/confirm-registration",
+        "https://evilsmartthings.com/confirm-registration",
+        "https://example.com/?host=api.smartthings.com",
+        "https://169.254.169.254/latest/meta-data",
+    ] {
+        confirmation_data.confirmation_url = confirmation_url.to_string();
+        assert!(
+            confirmation_data.confirmation_url().is_err(),
+            "{}",
+            confirmation_url
+        );
+    }
+
+    assert_eq!(
+        serde_json::to_value(&LifecycleResponse::TargetUrl(
+            "https://hub.example.com/smartthings".to_string()
+        ))
+        .unwrap(),
+        serde_json::json!({ "targetUrl": "https://hub.example.com/smartthings" })
+    );
+}
+
+/// Parsed `Authorization: Signature keyId="...",signature="...",headers="..."` header
+struct SignatureHeader {
+    key_id: String,
+    signature: Vec<u8>,
+    headers: Vec<String>,
+}
+
+impl SignatureHeader {
+    fn parse(value: &str) -> Result<Self, Error> {
+        let params = value
+            .strip_prefix("Signature ")
+            .ok_or_else(|| Error::InvalidSignature("not a signature authorization".to_string()))?;
+
+        let mut key_id = None;
+        let mut signature = None;
+        let mut headers = None;
+        for param in params.split(',') {
+            let (name, value) = param
+                .trim()
+                .split_once('=')
+                .ok_or_else(|| Error::InvalidSignature(format!("malformed parameter {}", param)))?;
+            let value = value.trim_matches('"');
+            match name {
+                "keyId" => key_id = Some(value.to_string()),
+                "signature" => {
+                    signature = Some(base64::decode(value).map_err(|e| {
+                        Error::InvalidSignature(format!("malformed signature - {}", e))
+                    })?)
+                }
+                "headers" => headers = Some(value.split(' ').map(str::to_lowercase).collect()),
+                "algorithm" if value != "rsa-sha256" => {
+                    return Err(Error::InvalidSignature(format!(
+                        "unsupported algorithm {}",
+                        value
+                    )))
+                }
+                _ => {}
+            }
+        }
+
+        // the draft defaults to `date` alone, which isn't enough
+        let headers: Vec<String> =
+            headers.ok_or_else(|| Error::InvalidSignature("no headers".to_string()))?;
+        if let Some(missing) = SIGNED_HEADERS
+            .iter()
+            .find(|name| !headers.iter().any(|header| header == *name))
+        {
+            return Err(Error::InvalidSignature(format!("{} isn't signed", missing)));
+        }
+
+        Ok(Self {
+            key_id: key_id.ok_or_else(|| Error::InvalidSignature("no keyId".to_string()))?,
+            signature: signature
+                .ok_or_else(|| Error::InvalidSignature("no signature".to_string()))?,
+            headers,
+        })
+    }
+
+    /// URL of the certificate of `key_id`, which must be a path on `KEY_URL`
+    fn key_url(&self) -> Result<reqwest::Url, Error> {
+        if !self.key_id.starts_with('/') || self.key_id.starts_with("//") {
+            return Err(Error::InvalidSignature(format!(
+                "keyId {} isn't a path",
+                &self.key_id
+            )));
+        }
+        let url = KEY_URL.join(&self.key_id)?;
+        if url.origin() != KEY_URL.origin() {
+            return Err(Error::InvalidSignature(format!(
+                "keyId {} isn't on {}",
+                &self.key_id,
+                KEY_URL.as_str()
+            )));
+        }
+        Ok(url)
+    }
+
+    /// Lines covered by the signature, in the order listed in `headers`
+    fn signing_string(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &reqwest::header::HeaderMap,
+    ) -> Result<String, Error> {
+        let lines: Result<Vec<_>, Error> = self
+            .headers
+            .iter()
+            .map(|name| {
+                if name == "(request-target)" {
+                    return Ok(format!(
+                        "(request-target): {} {}",
+                        method.to_lowercase(),
+                        path
+                    ));
+                }
+                let value = headers
+                    .get(name.as_str())
+                    .and_then(|value| value.to_str().ok())
+                    .ok_or_else(|| Error::InvalidSignature(format!("no {} header", name)))?;
+                Ok(format!("{}: {}", name, value))
+            })
+            .collect();
+
+        Ok(lines?.join("\n"))
+    }
+}
+
+/// Check the `Digest` header against the body, so the signature covers the body as well
+fn verify_digest(headers: &reqwest::header::HeaderMap, body: &[u8]) -> Result<(), Error> {
+    let digest = headers
+        .get("digest")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("SHA-256="))
+        .ok_or_else(|| Error::InvalidSignature("no SHA-256 digest".to_string()))?;
+
+    let expected = base64::encode(ring::digest::digest(&ring::digest::SHA256, body));
+    if digest == expected {
+        Ok(())
+    } else {
+        Err(Error::InvalidSignature("digest mismatch".to_string()))
+    }
+}
+
+/// Check the `Date` header is within `MAX_CLOCK_SKEW` of `now`, so captured requests can't be
+/// replayed later
+fn verify_date(headers: &reqwest::header::HeaderMap, now: SystemTime) -> Result<(), Error> {
+    let date = headers
+        .get("date")
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| Error::InvalidSignature("no date header".to_string()))?;
+    let date = httpdate::parse_http_date(date)
+        .map_err(|e| Error::InvalidSignature(format!("malformed date - {}", e)))?;
+
+    let skew = now
+        .duration_since(date)
+        .or_else(|_| date.duration_since(now))
+        .unwrap_or_default();
+    if skew > MAX_CLOCK_SKEW {
+        Err(Error::InvalidSignature(format!(
+            "date is {} seconds off",
+            skew.as_secs()
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+fn verify_with_certificate(
+    certificate_der: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), Error> {
+    let certificate = webpki::EndEntityCert::try_from(certificate_der)
+        .map_err(|e| Error::InvalidSignature(format!("malformed certificate - {:?}", e)))?;
+    certificate
+        .verify_signature(&webpki::RSA_PKCS1_2048_8192_SHA256, message, signature)
+        .map_err(|e| Error::InvalidSignature(format!("{:?}", e)))
+}
+
+/// Decode the first certificate of a PEM file
+fn certificate_from_pem(pem: &str) -> Result<Vec<u8>, Error> {
+    let encoded: String = pem
+        .lines()
+        .skip_while(|line| !line.starts_with("-----BEGIN CERTIFICATE-----"))
+        .skip(1)
+        .take_while(|line| !line.starts_with("-----END CERTIFICATE-----"))
+        .collect();
+    base64::decode(encoded)
+        .map_err(|e| Error::InvalidSignature(format!("malformed certificate - {}", e)))
+}
+
+/// Verifies HTTP signatures of requests SmartThings sends to a webhook SmartApp.
+/// Certificates are downloaded from `KEY_URL` on first use of each key, and up to
+/// `MAX_CERTIFICATES` of them are kept afterwards.
+#[derive(Default)]
+pub struct SignatureVerifier {
+    http_client: reqwest::Client,
+    certificates: std::sync::Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl SignatureVerifier {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    async fn certificate(&self, signature: &SignatureHeader) -> Result<Vec<u8>, Error> {
+        let key_id = &signature.key_id;
+        if let Some(certificate) = self.certificates.lock().unwrap().get(key_id) {
+            return Ok(certificate.clone());
+        }
+
+        let url = signature.key_url()?;
+        log::info!("download SmartThings signing certificate {}", key_id);
+        let response = self.http_client.get(url).send().await?.error_for_status()?;
+        let certificate = certificate_from_pem(&response.text().await?)?;
+
+        let mut certificates = self.certificates.lock().unwrap();
+        if certificates.len() >= MAX_CERTIFICATES && !certificates.contains_key(key_id) {
+            // keys are rotated rarely, so any of them will do
+            let evicted = certificates.keys().next().cloned().unwrap();
+            certificates.remove(&evicted);
+        }
+        certificates.insert(key_id.clone(), certificate.clone());
+        Ok(certificate)
+    }
+
+    /// `path` is the request target SmartThings called, including the query string
+    pub async fn verify(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &reqwest::header::HeaderMap,
+        body: &[u8],
+    ) -> Result<(), Error> {
+        let authorization = headers
+            .get(reqwest::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| Error::InvalidSignature("no authorization header".to_string()))?;
+        let signature = SignatureHeader::parse(authorization)?;
+        verify_digest(headers, body)?;
+        verify_date(headers, SystemTime::now())?;
+
+        let signing_string = signature.signing_string(method, path, headers)?;
+        let certificate = self.certificate(&signature).await?;
+        verify_with_certificate(
+            &certificate,
+            signing_string.as_bytes(),
+            &signature.signature,
+        )
+    }
+}
+
+#[test]
+fn test_signature_verification() {
+    let certificate = certificate_from_pem(include_str!("./smartapp_test_cert.pem")).unwrap();
+    let body =
+        br#"{"lifecycle":"PING","pingData":{"challenge":"1a904d57-4fab-4b15-a11e-1c4bfe7cb502"}}"#;
+
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        "digest",
+        "SHA-256=4yUTRZ12UV0NgHWjidSjofTlhtCkorWiKltmjsPS5Z8="
+            .parse()
+            .unwrap(),
+    );
+    headers.insert("date", "Sat, 17 Oct 2026 09:30:00 GMT".parse().unwrap());
+    let signature = SignatureHeader::parse(concat!(
+        r#"Signature keyId="/pl/useast2/1a-2b-3c-4d",signature=""#,
+        "xy4fkOmsGHttpfiEbXuLvikfMSUWiM5RK7JXPu3fXHVPbjpxhjzTJyKSpxBiFifo5ZNXvW6v9EKftJgsxABzO8b+",
+        "5a6jgUmk4GgMD2cCg8uLueQC4OQIQCHxOiThvmHz8IcE1JB4jl1jLR9Xtj0kVPXhA4nj2pun+Rip7ZE9HpsfPG34",
+        "ybYXN2lwNR9gl4v99l3FXEY+yGEQHcBlQOrGqh3VnO/rAub/NWOfqR8nreVYNOSwQxY88niATM2bnYPRQ7+Mxgw5",
+        "wT6ZzVoziL87BueNpKh+QbivAbLBhuHyLaed/JnOItCAEo8IH869MDJQTV7+66xsZrGfXrDPa9p9AQ==",
+        r#"",headers="(request-target) digest date",algorithm="rsa-sha256""#,
+    ))
+    .unwrap();
+    assert_eq!(signature.key_id, "/pl/useast2/1a-2b-3c-4d");
+
+    verify_digest(&headers, body).unwrap();
+    let signing_string = signature
+        .signing_string("POST", "/smartthings", &headers)
+        .unwrap();
+    verify_with_certificate(
+        &certificate,
+        signing_string.as_bytes(),
+        &signature.signature,
+    )
+    .unwrap();
+
+    let signed_at = httpdate::parse_http_date("Sat, 17 Oct 2026 09:30:00 GMT").unwrap();
+    verify_date(&headers, signed_at + Duration::from_secs(60)).unwrap();
+    assert!(verify_date(&headers, signed_at + Duration::from_secs(3600)).is_err());
+    assert!(verify_date(&headers, signed_at - Duration::from_secs(3600)).is_err());
+
+    // tampered body or request target
+    assert!(verify_digest(&headers, br#"{"lifecycle":"PING"}"#).is_err());
+    let signing_string = signature
+        .signing_string("POST", "/other", &headers)
+        .unwrap();
+    assert!(verify_with_certificate(
+        &certificate,
+        signing_string.as_bytes(),
+        &signature.signature
+    )
+    .is_err());
+
+    // keys are only fetched from the SmartThings key server
+    assert_eq!(
+        signature.key_url().unwrap().as_str(),
+        "https://key.smartthings.com/pl/useast2/1a-2b-3c-4d"
+    );
+    for key_id in [
+        "//evil.example/key",
+        "/\\evil.example/key",
+        "https://evil.example/key",
+        "key",
+    ] {
+        let signature = SignatureHeader {
+            key_id: key_id.to_string(),
+            signature: vec![],
+            headers: vec![],
+        };
+        assert!(signature.key_url().is_err(), "{}", key_id);
+    }
+
+    // a signature of the date alone could be replayed against any target and body
+    assert!(
+        SignatureHeader::parse(r#"Signature keyId="/k",signature="AA==",headers="date""#).is_err()
+    );
+    assert!(SignatureHeader::parse(r#"Signature keyId="/k",signature="AA==""#).is_err());
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceSubscription {
+    pub device_id: String,
+    /// `*` for every component
+    pub component_id: String,
+    /// `*` for every capability
+    pub capability: String,
+    /// `*` for every attribute
+    pub attribute: String,
+    pub state_change_only: bool,
+    #[serde(default)]
+    pub subscription_name: Option<String>,
+}
+
+impl DeviceSubscription {
+    /// Every change of every attribute of the device
+    pub fn all(device_id: &str) -> Self {
+        Self {
+            device_id: device_id.to_string(),
+            component_id: "*".to_string(),
+            capability: "*".to_string(),
+            attribute: "*".to_string(),
+            state_change_only: true,
+            // names are unique per installed app
+            subscription_name: Some(device_id.to_string()),
+        }
+    }
+}
+
+/// Online and offline changes of the devices
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceHealthSubscription {
+    pub device_ids: Vec<String>,
+    #[serde(default)]
+    pub subscription_name: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "sourceType", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SubscriptionSource {
+    Device {
+        device: DeviceSubscription,
+    },
+    #[serde(rename_all = "camelCase")]
+    DeviceHealth {
+        device_health: DeviceHealthSubscription,
+    },
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subscription {
+    pub id: String,
+    pub installed_app_id: String,
+    #[serde(flatten)]
+    pub source: SubscriptionSource,
+}
+
+#[test]
+fn test_subscription_serialization() {
+    assert_eq!(
+        serde_json::to_value(&SubscriptionSource::Device {
+            device: DeviceSubscription::all("cb2eddac-bfd2-1057-7493-3a0a573e507a"),
+        })
+        .unwrap(),
+        serde_json::json!({
+            "sourceType": "DEVICE",
+            "device": {
+                "deviceId": "cb2eddac-bfd2-1057-7493-3a0a573e507a",
+                "componentId": "*",
+                "capability": "*",
+                "attribute": "*",
+                "stateChangeOnly": true,
+                "subscriptionName": "cb2eddac-bfd2-1057-7493-3a0a573e507a"
+            }
+        })
+    );
+    assert_eq!(
+        serde_json::to_value(&SubscriptionSource::DeviceHealth {
+            device_health: DeviceHealthSubscription {
+                device_ids: vec!["cb2eddac-bfd2-1057-7493-3a0a573e507a".to_string()],
+                subscription_name: Some("health".to_string()),
+            },
+        })
+        .unwrap(),
+        serde_json::json!({
+            "sourceType": "DEVICE_HEALTH",
+            "deviceHealth": {
+                "deviceIds": ["cb2eddac-bfd2-1057-7493-3a0a573e507a"],
+                "subscriptionName": "health"
+            }
+        })
+    );
+}
+
+/// Subscriptions belong to an installed app, so these need the token SmartThings sends
+/// with INSTALL, UPDATE and EVENT.
+impl ApiClient {
+    pub async fn list_subscriptions(
+        &self,
+        installed_app_id: &str,
+    ) -> Result<Vec<Subscription>, Error> {
+        self.get_all(self.url(&format!("installedapps/{}/subscriptions", installed_app_id))?)
+            .await
+    }
+
+    pub async fn create_subscription(
+        &self,
+        installed_app_id: &str,
+        source: &SubscriptionSource,
+    ) -> Result<Subscription, Error> {
+        let url = self.url(&format!("installedapps/{}/subscriptions", installed_app_id))?;
+        Ok(self
+            .send(|client| client.post(url.clone()).json(source))
+            .await?
+            .json()
+            .await?)
+    }
+
+    pub async fn delete_subscriptions(&self, installed_app_id: &str) -> Result<(), Error> {
+        let url = self.url(&format!("installedapps/{}/subscriptions", installed_app_id))?;
+        self.send(|client| client.delete(url.clone())).await?;
+        Ok(())
+    }
+}
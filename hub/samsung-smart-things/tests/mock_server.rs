@@ -0,0 +1,96 @@
+use samsung_smart_things::{self as samsung, mock::MockServer, mock::FIXTURE_DEVICE_ID};
+
+#[tokio::test]
+async fn test_fixtures() {
+    let server = MockServer::with_fixtures().await.unwrap();
+    let client = server.client();
+
+    let devices = client.list_devices(None).await.unwrap();
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].device_id, FIXTURE_DEVICE_ID);
+
+    let descriptor = client.descriptor(FIXTURE_DEVICE_ID).await.unwrap();
+    assert!(descriptor
+        .component("main")
+        .unwrap()
+        .has_capability(samsung::Capability::AirConditionerMode));
+
+    let status = client
+        .component_status(FIXTURE_DEVICE_ID, "main")
+        .await
+        .unwrap();
+    let cooling_setpoint = status
+        .thermostat_cooling_setpoint
+        .as_ref()
+        .and_then(|o| o.cooling_setpoint.as_ref())
+        .unwrap();
+    assert_eq!(cooling_setpoint.value, Some(24.0));
+
+    let device_status = client.device_status(FIXTURE_DEVICE_ID).await.unwrap();
+    assert_eq!(device_status.components["main"], status);
+
+    let power_consumption = status
+        .power_consumption_report
+        .and_then(|o| o.power_consumption)
+        .and_then(|o| o.value)
+        .unwrap();
+    assert_eq!(power_consumption.energy, Some(1234567.0));
+
+    match client.descriptor("unknown").await {
+        Err(samsung::Error::NotFound(_)) => {}
+        ret => panic!("unexpected result {:?}", ret.map(|o| o.device_id)),
+    }
+}
+
+#[tokio::test]
+async fn test_commands_are_recorded() {
+    let server = MockServer::with_fixtures().await.unwrap();
+    let client = server.client();
+
+    client
+        .commands(
+            FIXTURE_DEVICE_ID,
+            vec![
+                samsung::capabilities::switch::Command::on().to_component("main"),
+                samsung::capabilities::thermostat_cooling_setpoint::Command::set_cooling_setpoint(
+                    22.0,
+                )
+                .to_component("main"),
+            ],
+        )
+        .await
+        .unwrap();
+
+    let commands = server.commands();
+    assert_eq!(commands.len(), 2);
+    assert_eq!(commands[0].device_id, FIXTURE_DEVICE_ID);
+    assert_eq!(commands[0].capability, "switch");
+    assert_eq!(commands[0].command, "on");
+    assert_eq!(commands[1].command, "setCoolingSetpoint");
+    assert_eq!(commands[1].arguments, vec![serde_json::json!(22.0)]);
+}
+
+#[tokio::test]
+async fn test_scenes_and_rules() {
+    let server = MockServer::with_fixtures().await.unwrap();
+    let client = server.client();
+
+    let scenes = client.list_scenes(None).await.unwrap();
+    assert_eq!(scenes.len(), 1);
+    assert_eq!(scenes[0].scene_name, "Good night");
+    client.execute_scene(&scenes[0].scene_id).await.unwrap();
+    assert_eq!(server.executed_scenes(), vec![scenes[0].scene_id.clone()]);
+
+    let location_id = &scenes[0].location_id;
+    let rules = client.list_rules(location_id).await.unwrap();
+    assert_eq!(rules.len(), 1);
+    assert!(rules[0].is_enabled());
+
+    let rule = client
+        .set_rule_enabled(location_id, &rules[0].id, false)
+        .await
+        .unwrap();
+    assert_eq!(rule.status, Some(samsung::RuleStatus::Disabled));
+    assert!(rule.definition.contains_key("actions"));
+    assert!(!client.list_rules(location_id).await.unwrap()[0].is_enabled());
+}
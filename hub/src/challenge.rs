@@ -0,0 +1,272 @@
+//! Two-factor challenges asked before executing commands, configured per device and per
+//! command under `[challenges.<device id>]`
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use argon2::{password_hash::PasswordHash, Argon2, PasswordVerifier};
+use google_smart_home::{server::FulfillmentError, Challenge, ChallengeType, Command};
+
+/// Wrong PINs in a row after which the device refuses PINs for `LOCKOUT`
+const MAX_FAILED_ATTEMPTS: u32 = 3;
+const LOCKOUT: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChallengeConfig {
+    /// The user has to confirm the command
+    Ack,
+    /// The user has to answer the PIN. `pin_hash` is an argon2 PHC string, like
+    /// `echo -n 1234 | argon2 <salt> -id -e`
+    Pin { pin_hash: String },
+}
+
+struct FailedAttempts {
+    count: u32,
+    last: Instant,
+}
+
+/// Challenge configs keyed by device id, then by command name like
+/// `action.devices.commands.OnOff`
+pub type ChallengeConfigs = HashMap<String, HashMap<String, ChallengeConfig>>;
+
+pub struct Challenges {
+    configs: ChallengeConfigs,
+    failed_attempts: Mutex<HashMap<String, FailedAttempts>>,
+}
+
+fn command_name(command: &Command) -> Option<String> {
+    serde_json::to_value(command)
+        .ok()?
+        .get("command")?
+        .as_str()
+        .map(str::to_string)
+}
+
+impl Challenges {
+    pub fn new(configs: ChallengeConfigs) -> anyhow::Result<Self> {
+        for (device_id, commands) in configs.iter() {
+            for (command, config) in commands.iter() {
+                if let ChallengeConfig::Pin { pin_hash } = config {
+                    PasswordHash::new(pin_hash)
+                        .map_err(|e| anyhow::anyhow!("{}", e))
+                        .with_context(|| {
+                            format!("invalid pin_hash of {} of {}", command, device_id)
+                        })?;
+                }
+            }
+        }
+
+        Ok(Self {
+            configs,
+            failed_attempts: Default::default(),
+        })
+    }
+
+    /// Check the answer of the user against the challenges `commands` need. A PIN also
+    /// counts as the confirmation.
+    pub async fn check(
+        &self,
+        device_id: &str,
+        commands: &[Command],
+        challenge: Option<&Challenge>,
+    ) -> Result<(), FulfillmentError> {
+        let configs = match self.configs.get(device_id) {
+            Some(configs) => configs,
+            None => return Ok(()),
+        };
+        let required: Vec<_> = commands
+            .iter()
+            .filter_map(|command| configs.get(&command_name(command)?))
+            .collect();
+
+        let pin_hashes: Vec<_> = required
+            .iter()
+            .filter_map(|config| match config {
+                ChallengeConfig::Pin { pin_hash } => Some(pin_hash.as_str()),
+                ChallengeConfig::Ack => None,
+            })
+            .collect();
+        if !pin_hashes.is_empty() {
+            return self
+                .check_pin(
+                    device_id,
+                    &pin_hashes,
+                    challenge.and_then(|c| c.pin.as_deref()),
+                )
+                .await;
+        }
+
+        if !required.is_empty() && !matches!(challenge, Some(Challenge { ack: true, .. })) {
+            return Err(FulfillmentError::Challenge(ChallengeType::AckNeeded));
+        }
+        Ok(())
+    }
+
+    /// The lock isn't held while argon2 verifies the PIN, which runs on the blocking pool
+    async fn check_pin(
+        &self,
+        device_id: &str,
+        pin_hashes: &[&str],
+        pin: Option<&str>,
+    ) -> Result<(), FulfillmentError> {
+        if let Some(failed) = self.failed_attempts.lock().unwrap().get(device_id) {
+            if failed.count >= MAX_FAILED_ATTEMPTS && failed.last.elapsed() < LOCKOUT {
+                return Err(google_smart_home::Error::TooManyFailedAttempts.into());
+            }
+        }
+
+        let pin = match pin {
+            Some(pin) => pin.to_string(),
+            None => return Err(FulfillmentError::Challenge(ChallengeType::PinNeeded)),
+        };
+        let pin_hashes: Vec<_> = pin_hashes
+            .iter()
+            .map(|pin_hash| pin_hash.to_string())
+            .collect();
+        let verified = tokio::task::spawn_blocking(move || {
+            pin_hashes.iter().all(|pin_hash| {
+                PasswordHash::new(pin_hash)
+                    .and_then(|hash| Argon2::default().verify_password(pin.as_bytes(), &hash))
+                    .is_ok()
+            })
+        })
+        .await
+        .map_err(|e| FulfillmentError::Internal(Box::new(e)))?;
+
+        let mut failed_attempts = self.failed_attempts.lock().unwrap();
+        if verified {
+            failed_attempts.remove(device_id);
+            return Ok(());
+        }
+
+        let failed = failed_attempts
+            .entry(device_id.to_string())
+            .or_insert(FailedAttempts {
+                count: 0,
+                last: Instant::now(),
+            });
+        if failed.last.elapsed() >= LOCKOUT {
+            failed.count = 0;
+        }
+        failed.count += 1;
+        failed.last = Instant::now();
+        log::warn!(
+            "wrong pin for {} - {} attempts in a row",
+            device_id,
+            failed.count
+        );
+
+        if failed.count >= MAX_FAILED_ATTEMPTS {
+            Err(google_smart_home::Error::TooManyFailedAttempts.into())
+        } else {
+            Err(FulfillmentError::Challenge(
+                ChallengeType::ChallengeFailedPinNeeded,
+            ))
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_challenges() {
+    use argon2::{password_hash::SaltString, PasswordHasher};
+
+    let pin_hash = Argon2::default()
+        .hash_password(b"1234", &SaltString::new("c29tZXNhbHQ").unwrap())
+        .unwrap()
+        .to_string();
+    let configs: ChallengeConfigs = toml::from_str(&format!(
+        r#"
+        [air_conditioner]
+        "action.devices.commands.ThermostatTemperatureSetpoint" = {{ type = "ack" }}
+        [lock]
+        "action.devices.commands.OnOff" = {{ type = "pin", pin_hash = "{}" }}
+        "#,
+        pin_hash
+    ))
+    .unwrap();
+    let challenges = Challenges::new(configs).unwrap();
+
+    let answer = |ack, pin: Option<&str>| Challenge {
+        ack,
+        pin: pin.map(str::to_string),
+    };
+    let challenge_type = |result| match result {
+        Err(FulfillmentError::Challenge(challenge_type)) => Some(challenge_type),
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(()) => None,
+    };
+
+    let setpoint = [Command::ThermostatTemperatureSetpoint {
+        thermostat_temperature_setpoint: 16.0,
+    }];
+    assert_eq!(
+        challenge_type(challenges.check("air_conditioner", &setpoint, None).await),
+        Some(ChallengeType::AckNeeded)
+    );
+    assert_eq!(
+        challenge_type(
+            challenges
+                .check("air_conditioner", &setpoint, Some(&answer(true, None)))
+                .await
+        ),
+        None
+    );
+    let on = [Command::OnOff { on: true }];
+    assert_eq!(
+        challenge_type(challenges.check("air_conditioner", &on, None).await),
+        None
+    );
+
+    assert_eq!(
+        challenge_type(
+            challenges
+                .check("lock", &on, Some(&answer(true, None)))
+                .await
+        ),
+        Some(ChallengeType::PinNeeded)
+    );
+    assert_eq!(
+        challenge_type(
+            challenges
+                .check("lock", &on, Some(&answer(false, Some("0000"))))
+                .await
+        ),
+        Some(ChallengeType::ChallengeFailedPinNeeded)
+    );
+    assert_eq!(
+        challenge_type(
+            challenges
+                .check("lock", &on, Some(&answer(false, Some("1234"))))
+                .await
+        ),
+        None
+    );
+
+    for _ in 1..MAX_FAILED_ATTEMPTS {
+        assert!(challenges
+            .check("lock", &on, Some(&answer(false, Some("0000"))))
+            .await
+            .is_err());
+    }
+    assert!(matches!(
+        challenges
+            .check("lock", &on, Some(&answer(false, Some("0000"))))
+            .await,
+        Err(FulfillmentError::Device(
+            google_smart_home::Error::TooManyFailedAttempts
+        ))
+    ));
+    assert!(matches!(
+        challenges
+            .check("lock", &on, Some(&answer(false, Some("1234"))))
+            .await,
+        Err(FulfillmentError::Device(
+            google_smart_home::Error::TooManyFailedAttempts
+        ))
+    ));
+}
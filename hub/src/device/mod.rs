@@ -2,6 +2,12 @@ mod plant_led;
 pub use plant_led::*;
 mod samsung_air_conditioner;
 pub use samsung_air_conditioner::*;
+mod samsung_smart_things;
+pub use samsung_smart_things::*;
+mod samsung_smart_things_automations;
+pub use samsung_smart_things_automations::*;
+mod samsung_smart_things_location;
+pub use samsung_smart_things_location::*;
 
 use crate::Error;
 use google_smart_home::{Command, DeviceWithDetail, States};
@@ -11,6 +17,31 @@ pub trait HomeDevice {
     fn sync(&self, global_id: &str) -> DeviceWithDetail;
     async fn query(&self) -> Result<States, Error>;
     async fn execute(&self, executions: &Vec<Command>) -> Result<States, Error>;
+
+    /// Id of the device on the LAN, reported in `otherDeviceIds` for Local Home
+    fn local_id(&self) -> Option<&str> {
+        None
+    }
+
+    /// SmartThings device mirrored by this device, to route SmartApp events to it
+    fn smart_things_device_id(&self) -> Option<&str> {
+        None
+    }
+
+    async fn handle_smart_things_event(&self, _event: &::samsung_smart_things::DeviceEvent) {}
+
+    async fn handle_smart_things_health(&self, _event: &::samsung_smart_things::DeviceHealthEvent) {
+    }
+
+    /// Whether the device is reachable, for devices which can tell
+    async fn online(&self) -> Result<Option<bool>, Error> {
+        Ok(None)
+    }
+
+    /// Power and energy meter readings, for devices which report them
+    async fn energy(&self) -> Result<Option<crate::energy::EnergyReading>, Error> {
+        Ok(None)
+    }
 }
 
 #[derive(serde::Deserialize)]
@@ -19,18 +50,42 @@ pub trait HomeDevice {
 pub enum DeviceConfigs {
     PlantLed(PlantLedConfig),
     SamsungAirConditioner(SamsungAirConditionerConfig),
+    SamsungSmartThingsLocation(SamsungSmartThingsLocationConfig),
+    SamsungSmartThingsAutomations(SamsungSmartThingsAutomationsConfig),
 }
 
 impl DeviceConfigs {
-    pub async fn create_device(self) -> anyhow::Result<Box<dyn HomeDevice + Send + Sync>> {
+    pub async fn create_devices(
+        self,
+        key: String,
+    ) -> anyhow::Result<Vec<(String, Box<dyn HomeDevice + Send + Sync>)>> {
         Ok(match self {
             DeviceConfigs::PlantLed(config) => {
                 log::trace!("create PlantLed");
-                Box::new(PlantLed::new(config).await?)
-            },
+                vec![(key, Box::new(PlantLed::new(config).await?))]
+            }
             DeviceConfigs::SamsungAirConditioner(config) => {
                 log::trace!("create samsung air conditioner");
-                Box::new(SamsungAirConditioner::new(config).await?)
+                let ids: Vec<_> = std::iter::once(key.clone())
+                    .chain(
+                        config
+                            .components
+                            .iter()
+                            .map(|component| format!("{}_{}", &key, component)),
+                    )
+                    .collect();
+                ids.into_iter()
+                    .zip(SamsungAirConditioner::new(config).await?)
+                    .map(|(id, device)| (id, Box::new(device) as Box<dyn HomeDevice + Send + Sync>))
+                    .collect()
+            }
+            DeviceConfigs::SamsungSmartThingsLocation(config) => {
+                log::trace!("import samsung smart things location");
+                import_smart_things_location(&key, config).await?
+            }
+            DeviceConfigs::SamsungSmartThingsAutomations(config) => {
+                log::trace!("import samsung smart things scenes and rules");
+                import_smart_things_automations(&key, config).await?
             }
         })
     }
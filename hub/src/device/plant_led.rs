@@ -1,12 +1,21 @@
+use std::time::Duration;
+
 use reqwest::Url;
 
 use anyhow::Context;
 
 use crate::{Error, ErrorWrap};
 use google_smart_home::{
-    Attributes, Command, Device, DeviceName, DeviceWithDetail, State, States, Trait, Type,
+    Attributes, BrightnessCommand, Command, Device, DeviceInfo, DeviceName, DeviceWithDetail,
+    OnOffCommand, OtherDeviceId, ScanData, State, States, Trait, TraitCommand, Type,
 };
 
+/// The firmware answers on the LAN right away, so a board which doesn't is off
+const VERSION_TIMEOUT: Duration = Duration::from_secs(3);
+/// The DNS of the LAN may not answer yet when the hub boots together with the router
+const RESOLVE_ATTEMPTS: u32 = 5;
+const RESOLVE_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
 #[derive(serde::Deserialize)]
 pub struct PlantLedConfig {
     pub host: String,
@@ -15,19 +24,118 @@ pub struct PlantLedConfig {
 
 pub struct PlantLed {
     api_endpoint: Url,
+    host: String,
+    /// IPv4 address `host` resolves to, which the Local Home app matches with the scanned board
+    address: String,
+    internal_id: u8,
+    /// `{address}/{internal_id}`, the id of the light on the LAN. The address is the IPv4
+    /// address `host` resolves to, as the board reports it when Local Home scans.
+    local_id: String,
+    device_info: DeviceInfo,
 }
 
 impl PlantLed {
     pub async fn new(config: PlantLedConfig) -> anyhow::Result<Self> {
+        let version_endpoint = Url::parse(&format!("http://{}/version", config.host))?;
+        let address = Self::resolve_address(&version_endpoint)
+            .await
+            .with_context(|| format!("Failed to resolve plant LED {}", &config.host))?;
+        let device_info = match Self::fetch_device_info(version_endpoint).await {
+            Ok(device_info) => device_info,
+            Err(e) => {
+                log::warn!(
+                    "failed to read firmware version of {} - {:?}",
+                    &config.host,
+                    e
+                );
+                Self::parse_version_response("")
+            }
+        };
+
         Ok(Self {
             api_endpoint: Url::parse(&format!(
                 "http://{}/lights/{}/power",
                 config.host, config.internal_id
             ))
             .unwrap(),
+            local_id: format!("{}/{}", address, config.internal_id),
+            host: config.host,
+            address,
+            internal_id: config.internal_id,
+            device_info,
         })
     }
 
+    /// Board found by Local Home. The firmware answers the UDP discovery with its address,
+    /// which is the proxy of the lights `{address}/{internal_id}`.
+    pub fn board_from_scan_data(scan: &ScanData) -> Option<String> {
+        let data = &scan.udp_scan_data.as_ref()?.data;
+        if data.len() % 2 != 0 {
+            return None;
+        }
+        let bytes = (0..data.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(data.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<_>>>()?;
+        String::from_utf8(bytes)
+            .ok()
+            .filter(|address| !address.is_empty())
+    }
+
+    async fn resolve_address(endpoint: &Url) -> anyhow::Result<String> {
+        let host = endpoint.host_str().context("No host")?;
+        let port = endpoint.port_or_known_default().unwrap_or(80);
+        let mut attempt = 1;
+        loop {
+            let result = match tokio::net::lookup_host((host, port)).await {
+                Ok(mut addresses) => addresses
+                    .find(|address| address.is_ipv4())
+                    .map(|address| address.ip().to_string())
+                    .with_context(|| format!("No IPv4 address of {}", host)),
+                Err(e) => Err(e.into()),
+            };
+            match result {
+                Err(e) if attempt < RESOLVE_ATTEMPTS => {
+                    log::warn!("failed to resolve {}, retry - {:?}", host, e);
+                    tokio::time::sleep(RESOLVE_RETRY_INTERVAL).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn fetch_device_info(version_endpoint: Url) -> anyhow::Result<DeviceInfo> {
+        let body = reqwest::Client::builder()
+            .timeout(VERSION_TIMEOUT)
+            .build()?
+            .get(version_endpoint)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(Self::parse_version_response(&body))
+    }
+
+    /// `/version` of the firmware answers its version, and the board on the next line
+    fn parse_version_response(body: &str) -> DeviceInfo {
+        let mut lines = body
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string);
+        let sw_version = lines.next();
+        let hw_version = lines.next();
+
+        DeviceInfo {
+            manufacturer: Some("perlmint".to_string()),
+            model: Some("plant_care".to_string()),
+            hw_version,
+            sw_version,
+        }
+    }
+
     fn parse_plant_led_response(body: String) -> Result<States, Error> {
         let raw_brightness: u16 = body
             .trim()
@@ -56,15 +164,24 @@ impl super::HomeDevice for PlantLed {
         DeviceWithDetail {
             basic: Device {
                 id: global_id.to_string(),
-                custom_data: Default::default(),
+                custom_data: [
+                    ("host".to_string(), self.host.clone().into()),
+                    ("address".to_string(), self.address.clone().into()),
+                    ("internalId".to_string(), self.internal_id.into()),
+                ]
+                .into_iter()
+                .collect(),
             },
             name: DeviceName {
                 name: "식물등".to_string(),
                 default_names: Default::default(),
                 nicknames: Default::default(),
             },
-            device_info: None,
-            other_device_ids: Default::default(),
+            device_info: Some(self.device_info.clone()),
+            other_device_ids: vec![OtherDeviceId {
+                agent_id: None,
+                device_id: self.local_id.clone(),
+            }],
             room_hint: None,
             traits: vec![Trait::OnOff, Trait::Brightness],
             attributes: Attributes(vec![]),
@@ -73,6 +190,10 @@ impl super::HomeDevice for PlantLed {
         }
     }
 
+    fn local_id(&self) -> Option<&str> {
+        Some(&self.local_id)
+    }
+
     async fn query(&self) -> Result<States, Error> {
         let body = reqwest::get(self.api_endpoint.clone())
             .await
@@ -85,13 +206,16 @@ impl super::HomeDevice for PlantLed {
     }
 
     async fn execute(&self, executions: &Vec<Command>) -> Result<States, Error> {
-        let query = match executions.get(0).unwrap() {
-            Command::OnOff { on } => if *on { 128 } else { 0 }.to_string(),
-            Command::BrightnessAbsolute { brightness } => {
-                ((*brightness as f32 * 255f32 / 100f32) as u8).to_string()
+        let query = match TraitCommand::from(executions.get(0).unwrap().clone()) {
+            TraitCommand::OnOff(OnOffCommand::OnOff { on }) => if on { 128 } else { 0 }.to_string(),
+            TraitCommand::Brightness(BrightnessCommand::BrightnessAbsolute { brightness }) => {
+                ((brightness as f32 * 255f32 / 100f32) as u8).to_string()
             }
-            _ => {
-                unreachable!();
+            command => {
+                log::warn!("plant led doesn't support {:?}", command);
+                return Err(Error::DeviceError(
+                    google_smart_home::Error::FunctionNotSupported,
+                ));
             }
         };
         log::info!("set plant led power {}", &query);
@@ -115,3 +239,30 @@ impl super::HomeDevice for PlantLed {
         Self::parse_plant_led_response(body)
     }
 }
+
+#[test]
+fn test_parse_version_response() {
+    let device_info = PlantLed::parse_version_response("1.1.0\r\nESP8266\r\n");
+    assert_eq!(device_info.sw_version.as_deref(), Some("1.1.0"));
+    assert_eq!(device_info.hw_version.as_deref(), Some("ESP8266"));
+
+    let device_info = PlantLed::parse_version_response("");
+    assert_eq!(device_info.model.as_deref(), Some("plant_care"));
+    assert!(device_info.sw_version.is_none());
+}
+
+#[test]
+fn test_board_from_scan_data() {
+    let scan = |data: &str| ScanData {
+        udp_scan_data: Some(google_smart_home::UdpScanData {
+            data: data.to_string(),
+        }),
+        ..Default::default()
+    };
+    assert_eq!(
+        PlantLed::board_from_scan_data(&scan("3139322e3136382e302e37")).as_deref(),
+        Some("192.168.0.7")
+    );
+    assert!(PlantLed::board_from_scan_data(&scan("31393")).is_none());
+    assert!(PlantLed::board_from_scan_data(&ScanData::default()).is_none());
+}
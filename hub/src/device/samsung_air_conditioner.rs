@@ -1,217 +1,455 @@
-use crate::{Error, ErrorWrap};
-use google_smart_home as google;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{energy::EnergyReading, metrics::SAMPLE_INTERVAL, Error};
+use google_smart_home::{self as google, TraitBuilder};
+use samsung::capabilities::{
+    air_conditioner_mode, custom_thermostat_setpoint_control, switch, thermostat_cooling_setpoint,
+};
 use samsung_smart_things as samsung;
 
-use super::HomeDevice;
+use super::{HomeDevice, SmartThingsCredentials};
 
 #[derive(Debug, Clone, serde::Deserialize)]
 pub struct SamsungAirConditionerConfig {
-    pub token: String, // https://account.smartthings.com/tokens
+    #[serde(flatten)]
+    pub credentials: SmartThingsCredentials,
     pub device_id: String,
+    #[serde(default)]
+    pub room_hint: Option<String>,
+    /// wait until the device reports new values before answering EXECUTE
+    #[serde(default)]
+    pub confirm_timeout_secs: Option<u64>,
+    /// other components exposed as their own devices, named `{key}_{component}`
+    #[serde(default)]
+    pub components: Vec<String>,
 }
 
 pub struct SamsungAirConditioner {
     client: samsung::ApiClient,
     device_id: String,
+    component: String,
     name: String,
+    room_hint: Option<String>,
+    device_info: google::DeviceInfo,
+    confirm_timeout: Option<Duration>,
     traits: Vec<google::Trait>,
     attributes: google::Attributes,
+    status: tokio::sync::Mutex<samsung::RawComponentStatus>,
+    /// set once SmartApp events arrive, after which `status` is kept current by them
+    receives_events: AtomicBool,
+    /// last known health and when it was learned, also kept current by SmartApp events
+    health: std::sync::Mutex<Option<(samsung::HealthState, Instant)>>,
 }
 
 impl SamsungAirConditioner {
-    pub async fn new(config: SamsungAirConditionerConfig) -> anyhow::Result<Self> {
-        let client = samsung::ApiClient::new(&config.token);
-        let device_id = config.device_id;
+    /// Devices for the main component and every one in `config.components`, in that order
+    pub async fn new(config: SamsungAirConditionerConfig) -> anyhow::Result<Vec<Self>> {
+        let client = config.credentials.create_client().await?;
+        let mut components = vec!["main".to_string()];
+        components.extend(config.components);
+        Self::with_components(
+            client,
+            config.device_id,
+            &components,
+            config.room_hint,
+            config.confirm_timeout_secs.map(Duration::from_secs),
+        )
+        .await
+    }
+
+    pub async fn with_client(
+        client: samsung::ApiClient,
+        device_id: String,
+        room_hint: Option<String>,
+        confirm_timeout: Option<Duration>,
+    ) -> anyhow::Result<Self> {
+        let mut devices = Self::with_components(
+            client,
+            device_id,
+            &["main".to_string()],
+            room_hint,
+            confirm_timeout,
+        )
+        .await?;
+        Ok(devices.remove(0))
+    }
+
+    /// One device for each of `components`, created from a single status request
+    pub async fn with_components(
+        client: samsung::ApiClient,
+        device_id: String,
+        components: &[String],
+        room_hint: Option<String>,
+        confirm_timeout: Option<Duration>,
+    ) -> anyhow::Result<Vec<Self>> {
         let descriptor = client.descriptor(&device_id).await?;
+        let mut device_status = client.raw_device_status(&device_id).await?;
+        let ocf = descriptor.ocf.as_ref();
+        let device_info = google::DeviceInfo {
+            manufacturer: descriptor.manufacturer().map(str::to_string),
+            model: descriptor.model().map(str::to_string),
+            hw_version: ocf.and_then(|ocf| ocf.hw_version.clone()),
+            sw_version: ocf.and_then(|ocf| ocf.firmware_version.clone()),
+        };
 
-        let name = descriptor.label;
+        components
+            .iter()
+            .map(|component| {
+                let component_descriptor = descriptor.component(component).ok_or_else(|| {
+                    anyhow::anyhow!("{} has no component {}", &descriptor.label, component)
+                })?;
+                let name = if component == "main" {
+                    descriptor.label.clone()
+                } else {
+                    format!("{} {}", &descriptor.label, &component_descriptor.label)
+                };
+                let status = device_status
+                    .components
+                    .remove(component)
+                    .unwrap_or_default();
 
-        let main_status = client.component_status(&device_id, "main").await?;
+                let mut device = Self::from_status(
+                    client.clone(),
+                    device_id.clone(),
+                    component.clone(),
+                    name,
+                    room_hint.clone(),
+                    confirm_timeout,
+                    status,
+                )?;
+                device.device_info = device_info.clone();
+                Ok(device)
+            })
+            .collect()
+    }
+
+    fn from_status(
+        client: samsung::ApiClient,
+        device_id: String,
+        component: String,
+        name: String,
+        room_hint: Option<String>,
+        confirm_timeout: Option<Duration>,
+        raw_status: samsung::RawComponentStatus,
+    ) -> anyhow::Result<Self> {
+        let status = raw_status.parse()?;
 
         let mut traits = Vec::new();
         let mut attributes = google::Attributes(Vec::new());
 
         let mut available_thermostat_modes = Vec::new();
         let mut thermostat_temperature_range = None;
+        let mut sensor_states_supported = Vec::new();
         let mut thermostat_temperature_unit =
             google::TemperatureSetting_thermostatTemperatureUnit::C;
 
-        for capability in main_status.0 {
-            match capability {
-                samsung::CapabilityStatus::Switch { .. } => traits.push(google::Trait::OnOff),
-                samsung::CapabilityStatus::AirConditionerMode {
-                    supported_ac_modes: supperted_ac_modes, ..
-                } => {
-                    traits.push(google::Trait::TemperatureSetting);
-                    for mode in supperted_ac_modes.value {
-                        let mode = match mode {
-                            samsung::enums::AirConditionerMode::Cool => {
-                                Some(google::ThermostatMode::Cool)
-                            }
-                            samsung::enums::AirConditionerMode::Wind => {
-                                Some(google::ThermostatMode::FanOnly)
-                            }
-                            samsung::enums::AirConditionerMode::Dry => {
-                                Some(google::ThermostatMode::Dry)
-                            }
-                            _ => None,
-                        };
-                        if let Some(mode) = mode {
-                            available_thermostat_modes.push(mode);
-                        }
+        if status.switch.is_some() {
+            google::OnOffTrait::new().add_to(&mut traits, &mut attributes)
+        }
+
+        let supported_ac_modes = status
+            .air_conditioner_mode
+            .and_then(|status| status.supported_ac_modes)
+            .and_then(|supported_ac_modes| supported_ac_modes.value)
+            .unwrap_or_default();
+        for mode in supported_ac_modes {
+            if let Some(mode) = thermostat_mode(&mode) {
+                available_thermostat_modes.push(mode);
+            }
+        }
+
+        let setpoint_control = status
+            .custom_thermostat_setpoint_control
+            .unwrap_or_default();
+        if let (Some(minimum_setpoint), Some(maximum_setpoint)) = (
+            setpoint_control.minimum_setpoint,
+            setpoint_control.maximum_setpoint,
+        ) {
+            use custom_thermostat_setpoint_control::Unit;
+
+            let fahrenheit = match (&minimum_setpoint.unit, &maximum_setpoint.unit) {
+                (Some(Unit::Fahrenheit), Some(Unit::Fahrenheit)) => Some(true),
+                (Some(Unit::Celsius) | None, Some(Unit::Celsius) | None) => Some(false),
+                (min_unit, max_unit) => {
+                    log::warn!(
+                        "skip setpoint range of {} in {:?} to {:?}",
+                        &name,
+                        min_unit,
+                        max_unit
+                    );
+                    None
+                }
+            };
+            if let Some(fahrenheit) = fahrenheit {
+                let celsius = |value: f64| {
+                    if fahrenheit {
+                        (value - 32.0) * 5.0 / 9.0
+                    } else {
+                        value
                     }
+                };
+                if fahrenheit {
+                    thermostat_temperature_unit =
+                        google::TemperatureSetting_thermostatTemperatureUnit::F;
                 }
-                samsung::CapabilityStatus::ThermostatSetpointControl {
-                    minimum_setpoint,
-                    maximum_setpoint,
-                } => {
-                    assert_eq!(minimum_setpoint.unit, maximum_setpoint.unit);
-                    thermostat_temperature_unit = match minimum_setpoint.unit {
-                        samsung::enums::TemperatureUnit::Celsius => {
-                            google::TemperatureSetting_thermostatTemperatureUnit::C
-                        }
-                        samsung::enums::TemperatureUnit::Farenheit => {
-                            google::TemperatureSetting_thermostatTemperatureUnit::F
-                        }
-                    };
+                if let (Some(min), Some(max)) = (minimum_setpoint.value, maximum_setpoint.value) {
                     thermostat_temperature_range = Some(google::TemperatureRange {
-                        max_threshold_celsius: maximum_setpoint.value as _,
-                        min_threshold_celsius: minimum_setpoint.value as _,
-                    })
-                }
-                samsung::CapabilityStatus::DustSensor { .. } => {
-                    traits.push(google::Trait::SensorState);
-                    attributes.0.push(
-                        google::Attribute::SensorState {
-                            sensor_states_supported: vec![
-                                google::SensorStateSupported::Pm25 {
-                                    numeric_capabilities: google::PmSensorStateSupported {
-                                        raw_value_unit: Some(google::PmSensorStateSupported_rawValueUnit::MicrogramsPerCubicMeter),
-                                    }
-                                },
-                                google::SensorStateSupported::Pm10 {
-                                    numeric_capabilities: google::PmSensorStateSupported {
-                                        raw_value_unit: Some(google::PmSensorStateSupported_rawValueUnit::MicrogramsPerCubicMeter),
-                                    }
-                                }
-                            ]
-                        }
-                    );
-                }
-                samsung::CapabilityStatus::RelativeHumidityMeasurement { .. } => {
-                    traits.push(google::Trait::HumiditySetting);
-                    attributes.0.push(google::Attribute::HumiditySetting {
-                        command_only_humidity_setting: Some(false),
-                        humidity_setpoint_range: None,
-                        query_only_humidity_setting: Some(true),
+                        max_threshold_celsius: celsius(max),
+                        min_threshold_celsius: celsius(min),
                     })
                 }
-                _ => {}
             }
         }
 
+        if status.dust_sensor.is_some() {
+            let numeric_capabilities = google::PmSensorStateSupported::new().raw_value_unit(
+                google::PmSensorStateSupported_rawValueUnit::MicrogramsPerCubicMeter,
+            );
+            sensor_states_supported.push(google::SensorStateSupported::Pm25 {
+                numeric_capabilities: numeric_capabilities.clone(),
+            });
+            sensor_states_supported.push(google::SensorStateSupported::Pm10 {
+                numeric_capabilities,
+            });
+        }
+        if status.air_quality_sensor.is_some() {
+            sensor_states_supported.push(google::SensorStateSupported::AirQuality {
+                descriptive_capabilities: google::AirQualitySensorStateSupported::new(vec![
+                    google::AirQualitySensorStateSupported_availableStates::Good,
+                    google::AirQualitySensorStateSupported_availableStates::Fair,
+                    google::AirQualitySensorStateSupported_availableStates::Poor,
+                    google::AirQualitySensorStateSupported_availableStates::VeryPoor,
+                    google::AirQualitySensorStateSupported_availableStates::Severe,
+                ]),
+            });
+        }
+        // Google has no sensor state for PM1.0 or odor, so veryFineDustSensor and
+        // odorSensor are not reported. Power and energy have no Google trait either
+        // (EnergyStorage is for batteries), they are served by `energy` instead.
+        if status.relative_humidity_measurement.is_some() {
+            google::HumiditySettingTrait::new()
+                .command_only_humidity_setting(false)
+                .query_only_humidity_setting(true)
+                .add_to(&mut traits, &mut attributes)
+        }
+
+        if !sensor_states_supported.is_empty() {
+            google::SensorStateTrait::new(sensor_states_supported)
+                .add_to(&mut traits, &mut attributes);
+        }
+
+        // TemperatureSetting requires attributes, so declare it only with a mode to report
         if !available_thermostat_modes.is_empty() {
-            attributes.0.push(google::Attribute::TemperatureSetting {
+            google::TemperatureSettingTrait::new(
                 available_thermostat_modes,
-                buffer_range_celsius: None,
-                command_only_temperature_setting: None,
-                query_only_temperature_setting: None,
-                thermostat_temperature_range,
                 thermostat_temperature_unit,
-            })
+            )
+            .set_thermostat_temperature_range(thermostat_temperature_range)
+            .add_to(&mut traits, &mut attributes);
         }
 
         Ok(Self {
             client,
             device_id,
+            component,
             name,
+            room_hint,
+            device_info: Default::default(),
+            confirm_timeout,
             traits,
             attributes,
+            status: tokio::sync::Mutex::new(raw_status),
+            receives_events: AtomicBool::new(false),
+            health: std::sync::Mutex::new(None),
         })
     }
 
-    async fn query_status(&self) -> anyhow::Result<google::States> {
-        let main_status = self
+    async fn send_commands(
+        &self,
+        commands: Vec<samsung::RawCommand>,
+    ) -> Result<(), samsung::Error> {
+        match self.confirm_timeout {
+            Some(timeout) => {
+                self.client
+                    .commands_confirmed(&self.device_id, commands, timeout)
+                    .await
+            }
+            None => self
+                .client
+                .commands(&self.device_id, commands)
+                .await
+                .map(|_| ()),
+        }
+    }
+
+    async fn fetch_status(&self) -> Result<samsung::ComponentStatus, samsung::Error> {
+        let raw = self
             .client
-            .component_status(&self.device_id, "main")
+            .raw_component_status(&self.device_id, &self.component)
             .await?;
+        let status = raw.parse()?;
+        *self.status.lock().await = raw;
+        Ok(status)
+    }
 
-        let mut ret = google::States(Vec::new());
-        let mut active_thermostat_mode = None;
-        let mut thermostat_humidity_ambient = None;
-        let mut thermostat_temperature_ambient = 0.0;
-        let mut thermostat_temperature_setpoint = 0.0;
-
-        for capability in main_status.0 {
-            match capability {
-                samsung::CapabilityStatus::Switch { switch } => {
-                    ret.0.push(google::State::OnOff {
-                        on: Some(switch.value.into()),
-                    });
-                }
-                samsung::CapabilityStatus::AirConditionerMode {
-                    air_conditioner_mode,
-                    ..
-                } => {
-                    active_thermostat_mode = match air_conditioner_mode.value {
-                        samsung::enums::AirConditionerMode::Cool => {
-                            Some(google::ThermostatMode::Cool)
-                        }
-                        samsung::enums::AirConditionerMode::Wind => {
-                            Some(google::ThermostatMode::FanOnly)
-                        }
-                        samsung::enums::AirConditionerMode::Dry => {
-                            Some(google::ThermostatMode::Dry)
-                        }
-                        _ => None,
-                    };
-                }
-                samsung::CapabilityStatus::ThermostatSetpointControl { .. } => {
-                    // ignore
-                }
-                samsung::CapabilityStatus::TemperatureMeasurement { temperature } => {
-                    thermostat_temperature_ambient = temperature.value as _;
-                }
-                samsung::CapabilityStatus::ThermostatCoolingSetpoint { cooling_setpoint: cooling_set_point } => {
-                    thermostat_temperature_setpoint = cooling_set_point.value as _;
-                }
-                samsung::CapabilityStatus::DustSensor {
-                    dust_level,
-                    fine_dust_level,
-                } => {
-                    ret.0.push(google::State::SensorState {
-                        current_sensor_state_data: vec![
-                            google::SensorState::Pm25 {
-                                raw_value: fine_dust_level.value as _,
-                            },
-                            google::SensorState::Pm10 {
-                                raw_value: dust_level.value as _,
-                            },
-                        ],
-                    });
-                }
-                samsung::CapabilityStatus::RelativeHumidityMeasurement { humidity } => {
-                    thermostat_humidity_ambient = Some(humidity.value as _);
+    /// The event-fed status when available, unless `fresh` is requested
+    async fn current_status(&self, fresh: bool) -> Result<samsung::ComponentStatus, Error> {
+        Ok(if !fresh && self.receives_events.load(Ordering::Acquire) {
+            self.status
+                .lock()
+                .await
+                .parse()
+                .map_err(Error::server_error)?
+        } else {
+            self.fetch_status().await?
+        })
+    }
+
+    /// Same as `current_status`, for health. Without events, a health fetched within
+    /// `SAMPLE_INTERVAL` is reused, so queries and metrics samples don't each fetch it.
+    async fn current_health(&self, fresh: bool) -> Result<samsung::HealthState, Error> {
+        if !fresh {
+            if let Some((health, learned_at)) = *self.health.lock().unwrap() {
+                if self.receives_events.load(Ordering::Acquire)
+                    || learned_at.elapsed() < SAMPLE_INTERVAL
+                {
+                    return Ok(health);
                 }
-                _ => {}
             }
         }
 
-        ret.0.push(google::State::TemperatureSetting {
-            active_thermostat_mode: active_thermostat_mode.clone(),
-            target_temp_reached_estimate_unix_timestamp_sec: None,
-            thermostat_humidity_ambient,
-            _details: google::TemperatureSettingDetail::SingleTemperaturSetting {
-                thermostat_mode: active_thermostat_mode.unwrap(),
-                thermostat_temperature_ambient,
-                thermostat_temperature_setpoint,
-            },
-        });
+        let health = self.client.device_health(&self.device_id).await?.state;
+        *self.health.lock().unwrap() = Some((health, Instant::now()));
+        Ok(health)
+    }
+
+    async fn query_status(&self, fresh: bool) -> Result<google::States, Error> {
+        let (health, status) = tokio::join!(self.current_health(fresh), self.current_status(fresh));
+        match health {
+            // SmartThings keeps answering the last status of an unplugged device
+            Ok(samsung::HealthState::Offline) => {
+                return Err(Error::DeviceError(google::Error::DeviceOffline))
+            }
+            Ok(_) => {}
+            // the health is unknown then, which doesn't keep the status from being reported
+            Err(e) => log::warn!("failed to read health of {} - {:?}", &self.name, e),
+        }
+        let status = status?;
+
+        let mut ret = google::States(Vec::new());
+        let mut current_sensor_state_data = Vec::new();
+
+        if let Some(switch) = status
+            .switch
+            .and_then(|status| status.switch)
+            .and_then(|switch| switch.value)
+        {
+            ret.0.push(google::State::OnOff {
+                on: Some(switch == switch::SwitchState::On),
+            });
+        }
+        let active_thermostat_mode = status
+            .air_conditioner_mode
+            .and_then(|status| status.air_conditioner_mode)
+            .and_then(|mode| mode.value)
+            .and_then(|mode| thermostat_mode(&mode));
+        let thermostat_temperature_ambient = status
+            .temperature_measurement
+            .and_then(|status| status.temperature)
+            .and_then(|temperature| temperature.value)
+            .unwrap_or_default();
+        let thermostat_temperature_setpoint = status
+            .thermostat_cooling_setpoint
+            .and_then(|status| status.cooling_setpoint)
+            .and_then(|cooling_setpoint| cooling_setpoint.value)
+            .unwrap_or_default();
+        let dust_sensor = status.dust_sensor.unwrap_or_default();
+        if let Some(fine_dust_level) = dust_sensor.fine_dust_level.and_then(|level| level.value) {
+            current_sensor_state_data.push(google::SensorState::Pm25 {
+                raw_value: fine_dust_level as _,
+            });
+        }
+        if let Some(dust_level) = dust_sensor.dust_level.and_then(|level| level.value) {
+            current_sensor_state_data.push(google::SensorState::Pm10 {
+                raw_value: dust_level as _,
+            });
+        }
+        if let Some(air_quality) = status
+            .air_quality_sensor
+            .and_then(|status| status.air_quality)
+            .and_then(|air_quality| air_quality.value)
+        {
+            current_sensor_state_data.push(google::SensorState::AirQuality {
+                current_sensor_state: air_quality_state(air_quality),
+            });
+        }
+        let thermostat_humidity_ambient = status
+            .relative_humidity_measurement
+            .and_then(|status| status.humidity)
+            .and_then(|humidity| humidity.value)
+            .map(|humidity| humidity as _);
+
+        if !current_sensor_state_data.is_empty() {
+            ret.0
+                .push(google::SensorStateState::new(current_sensor_state_data).into());
+        }
+
+        // the trait is only declared with modes, and a mode Google doesn't know can't be reported
+        let temperature_setting_declared = self.traits.contains(&google::Trait::TemperatureSetting);
+        if let Some(thermostat_mode) =
+            active_thermostat_mode.filter(|_| temperature_setting_declared)
+        {
+            let temperature_setting = google::TemperatureSettingState::new(
+                google::TemperatureSettingDetail::SingleTemperaturSetting {
+                    thermostat_mode: thermostat_mode.clone(),
+                    thermostat_temperature_ambient: thermostat_temperature_ambient as _,
+                    thermostat_temperature_setpoint: thermostat_temperature_setpoint as _,
+                },
+            )
+            .active_thermostat_mode(thermostat_mode)
+            .set_thermostat_humidity_ambient(thermostat_humidity_ambient);
+            ret.0.push(temperature_setting.into());
+        }
 
         Ok(ret)
     }
 }
 
+/// Google mode of a SmartThings `airConditionerMode`, `None` for modes Google doesn't have
+fn thermostat_mode(mode: &str) -> Option<google::ThermostatMode> {
+    match mode {
+        "cool" => Some(google::ThermostatMode::Cool),
+        "wind" => Some(google::ThermostatMode::FanOnly),
+        "dry" => Some(google::ThermostatMode::Dry),
+        _ => None,
+    }
+}
+
+/// SmartThings `airConditionerMode` of a Google mode, `None` for modes the AC doesn't have
+fn air_conditioner_mode(mode: &google::ThermostatMode) -> Option<&'static str> {
+    match mode {
+        google::ThermostatMode::Cool => Some("cool"),
+        google::ThermostatMode::FanOnly => Some("wind"),
+        google::ThermostatMode::Dry => Some("dry"),
+        google::ThermostatMode::Auto => Some("auto"),
+        _ => None,
+    }
+}
+
+/// Band of a CAQI (Common Air Quality Index) value
+fn air_quality_state(caqi: i64) -> google::AirQualitySensorStateSupported_availableStates {
+    match caqi {
+        0..=24 => google::AirQualitySensorStateSupported_availableStates::Good,
+        25..=49 => google::AirQualitySensorStateSupported_availableStates::Fair,
+        50..=74 => google::AirQualitySensorStateSupported_availableStates::Poor,
+        75..=100 => google::AirQualitySensorStateSupported_availableStates::VeryPoor,
+        _ => google::AirQualitySensorStateSupported_availableStates::Severe,
+    }
+}
+
 #[async_trait::async_trait]
 impl HomeDevice for SamsungAirConditioner {
     fn sync(&self, global_id: &str) -> google::DeviceWithDetail {
@@ -229,53 +467,365 @@ impl HomeDevice for SamsungAirConditioner {
                 nicknames: vec![],
             },
             will_report_state: false,
-            room_hint: None,
-            device_info: None,
+            room_hint: self.room_hint.clone(),
+            device_info: Some(self.device_info.clone()),
+            // the AC is only reached through the cloud, so it has no local id
             other_device_ids: vec![],
         }
     }
 
+    fn smart_things_device_id(&self) -> Option<&str> {
+        Some(&self.device_id)
+    }
+
+    async fn energy(&self) -> Result<Option<EnergyReading>, Error> {
+        let status = self.current_status(false).await?;
+        let mut reading = EnergyReading {
+            power_watts: status
+                .power_meter
+                .and_then(|status| status.power)
+                .and_then(|power| power.value),
+            energy_kwh: status
+                .energy_meter
+                .and_then(|status| status.energy)
+                .and_then(|energy| energy.kilowatt_hours()),
+        };
+        if let Some(report) = status
+            .power_consumption_report
+            .and_then(|status| status.power_consumption)
+            .and_then(|power_consumption| power_consumption.value)
+        {
+            // powerMeter and energyMeter are more recent when both are present
+            reading.power_watts = reading.power_watts.or(report.power);
+            reading.energy_kwh = reading
+                .energy_kwh
+                .or(report.energy.map(|energy| energy / 1000.0));
+        }
+
+        Ok(
+            if reading.power_watts.is_none() && reading.energy_kwh.is_none() {
+                None
+            } else {
+                Some(reading)
+            },
+        )
+    }
+
+    async fn online(&self) -> Result<Option<bool>, Error> {
+        Ok(match self.current_health(false).await? {
+            samsung::HealthState::Online => Some(true),
+            samsung::HealthState::Offline => Some(false),
+            samsung::HealthState::Unknown => None,
+        })
+    }
+
+    async fn handle_smart_things_health(&self, event: &samsung::DeviceHealthEvent) {
+        log::info!("{} is {:?}", &self.name, event.status);
+        *self.health.lock().unwrap() = Some((event.status, Instant::now()));
+    }
+
+    async fn handle_smart_things_event(&self, event: &samsung::DeviceEvent) {
+        if event.component_id != self.component {
+            return;
+        }
+
+        if !self.receives_events.load(Ordering::Acquire) {
+            // values may have changed since the last poll, so start from a fresh status
+            if let Err(e) = self.fetch_status().await {
+                log::warn!("failed to refresh status of {} - {}", &self.name, e);
+                return;
+            }
+            self.receives_events.store(true, Ordering::Release);
+        }
+
+        if let Some(status) = self.status.lock().await.apply(event) {
+            log::debug!("{} is updated - {:?}", &self.name, status);
+        }
+    }
+
     async fn query(&self) -> Result<google::States, Error> {
-        self.query_status().await.server_error()
+        self.query_status(false).await
     }
 
     async fn execute(&self, executions: &Vec<google::Command>) -> Result<google::States, Error> {
+        let mut commands = Vec::new();
         let mut states = Vec::new();
 
         for command in executions {
-            match command {
-                google::Command::OnOff { on } => {
-                    if self.client.command(
-                        &self.device_id,
-                        samsung::command::Switch::new(on),
-                    ).await.is_ok() {
-                        states.push(google::State::OnOff {
-                            on: Some(*on),
-                        })
-                    }
-                },
-                google::Command::ThermostatSetMode { thermostat_mode } => self.client.command(
-                    &self.device_id,
-                    samsung::command::AirConditionerMode::SetAirConditionerMode(match thermostat_mode {
-                        google::ThermostatMode::Auto => samsung::enums::AirConditionerMode::Auto,
-                        google::ThermostatMode::Dry => samsung::enums::AirConditionerMode::Dry,
-                        google::ThermostatMode::FanOnly => samsung::enums::AirConditionerMode::Wind,
-                        google::ThermostatMode::Cool => samsung::enums::AirConditionerMode::Cool,
-                        _ => unreachable!("Unknown mode"),
-                    }),
-                ).await.unwrap(),
-                google::Command::ThermostatTemperatureSetpoint {
-                    thermostat_temperature_setpoint,
-                } => self.client.command(
-                    &self.device_id,
-                    samsung::command::ThermostatCoolingSetpoint::SetCoolingSetpoint(
-                        *thermostat_temperature_setpoint as _,
-                    ),
-                ).await.unwrap(),
-                _ => unreachable!(),
-            }
+            let command = match google::TraitCommand::from(command.clone()) {
+                google::TraitCommand::OnOff(google::OnOffCommand::OnOff { on }) => {
+                    states.push(google::State::OnOff { on: Some(on) });
+                    let command = if on {
+                        switch::Command::on()
+                    } else {
+                        switch::Command::off()
+                    };
+                    command.to_component(&self.component)
+                }
+                google::TraitCommand::TemperatureSetting(
+                    google::TemperatureSettingCommand::ThermostatSetMode { thermostat_mode },
+                ) => {
+                    let mode = air_conditioner_mode(&thermostat_mode).ok_or_else(|| {
+                        log::warn!("{} has no mode {:?}", &self.name, thermostat_mode);
+                        Error::DeviceError(google::Error::NotSupported)
+                    })?;
+                    air_conditioner_mode::Command::set_air_conditioner_mode(mode.to_string())
+                        .to_component(&self.component)
+                }
+                google::TraitCommand::TemperatureSetting(
+                    google::TemperatureSettingCommand::ThermostatTemperatureSetpoint {
+                        thermostat_temperature_setpoint,
+                    },
+                ) => thermostat_cooling_setpoint::Command::set_cooling_setpoint(
+                    thermostat_temperature_setpoint as _,
+                )
+                .to_component(&self.component),
+                command => {
+                    log::warn!("{} doesn't support {:?}", &self.name, command);
+                    return Err(Error::DeviceError(google::Error::FunctionNotSupported));
+                }
+            };
+            commands.push(command);
+        }
+
+        // one request for the whole execution, so the unit is never left half-configured
+        self.send_commands(commands).await?;
+
+        if self.confirm_timeout.is_some() {
+            // every command is reflected, so report what the device actually shows
+            self.query_status(true).await
+        } else {
+            Ok(google::States(states))
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_with_mock_server() {
+    use samsung::mock::{MockServer, FIXTURE_DEVICE_ID};
+
+    let server = MockServer::with_fixtures().await.unwrap();
+    let device = SamsungAirConditioner::with_client(
+        server.client(),
+        FIXTURE_DEVICE_ID.to_string(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(device.name, "Air conditioner");
+    assert!(device.traits.contains(&google::Trait::OnOff));
+    let sync = device.sync("room_air_conditioner");
+    let device_info = sync.device_info.clone().unwrap();
+    assert_eq!(
+        device_info.manufacturer.as_deref(),
+        Some("Samsung Electronics")
+    );
+    assert_eq!(device_info.sw_version.as_deref(), Some("0.1.0"));
+    assert!(sync.other_device_ids.is_empty());
+    sync.validate().unwrap();
+
+    let states = match device.query().await {
+        Ok(states) => states,
+        Err(_) => panic!("query failed"),
+    };
+    sync.validate_states(&states).unwrap();
+    assert!(states
+        .0
+        .iter()
+        .any(|state| matches!(state, google::State::OnOff { on: Some(false) })));
+    assert!(states.0.iter().any(|state| match state {
+        google::State::SensorState {
+            current_sensor_state_data,
+        } => current_sensor_state_data.contains(&google::SensorState::AirQuality {
+            current_sensor_state: google::AirQualitySensorStateSupported_availableStates::Fair,
+        }),
+        _ => false,
+    }));
+
+    match device.energy().await {
+        Ok(Some(reading)) => {
+            assert_eq!(reading.power_watts, Some(0.0));
+            assert_eq!(reading.energy_kwh, Some(1234.567));
         }
+        _ => panic!("no energy reading"),
+    }
+
+    let executions = vec![
+        google::Command::OnOff { on: true },
+        google::Command::ThermostatTemperatureSetpoint {
+            thermostat_temperature_setpoint: 22.0,
+        },
+    ];
+    sync.validate_commands(&executions).unwrap();
+    match device.execute(&executions).await {
+        Ok(states) => sync.validate_states(&states).unwrap(),
+        Err(_) => panic!("execute failed"),
+    }
+    let commands: Vec<_> = server
+        .commands()
+        .into_iter()
+        .map(|command| (command.capability, command.command, command.arguments))
+        .collect();
+    assert_eq!(
+        commands,
+        vec![
+            ("switch".to_string(), "on".to_string(), vec![]),
+            (
+                "thermostatCoolingSetpoint".to_string(),
+                "setCoolingSetpoint".to_string(),
+                vec![serde_json::json!(22.0)]
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_component_with_mock_server() {
+    use samsung::mock::{MockServer, FIXTURES_DIR, FIXTURE_DEVICE_ID};
 
-        Ok(google::States(states))
+    // two-zone unit made of the recorded single-zone one
+    let server = MockServer::start().await.unwrap();
+    let read = |path: &str| -> serde_json::Value {
+        serde_json::from_slice(&std::fs::read(format!("{}/{}", FIXTURES_DIR, path)).unwrap())
+            .unwrap()
+    };
+    let mut descriptor = read(&format!("devices/{}.json", FIXTURE_DEVICE_ID));
+    let mut zone = descriptor["components"][0].clone();
+    zone["id"] = "zone2".into();
+    zone["label"] = "Zone 2".into();
+    descriptor["components"].as_array_mut().unwrap().push(zone);
+    let mut status = read(&format!("devices/{}/status.json", FIXTURE_DEVICE_ID));
+    status["components"]["zone2"] = status["components"]["main"].clone();
+    server.add_fixture(&format!("devices/{}", FIXTURE_DEVICE_ID), descriptor);
+    server.add_fixture(&format!("devices/{}/status", FIXTURE_DEVICE_ID), status);
+
+    let devices = SamsungAirConditioner::with_components(
+        server.client(),
+        FIXTURE_DEVICE_ID.to_string(),
+        &["main".to_string(), "zone2".to_string()],
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(devices[1].name, "Air conditioner Zone 2");
+
+    if devices[1]
+        .execute(&vec![google::Command::OnOff { on: false }])
+        .await
+        .is_err()
+    {
+        panic!("execute failed");
     }
+    let commands = server.commands();
+    assert_eq!(commands.len(), 1);
+    assert_eq!(commands[0].component, "zone2");
+}
+
+#[tokio::test]
+async fn test_setpoint_range() {
+    use samsung::mock::MockServer;
+
+    let server = MockServer::start().await.unwrap();
+    let range = |min_unit: &str, max_unit: &str| {
+        let raw_status = serde_json::from_value(serde_json::json!({
+            "airConditionerMode": {
+                "supportedAcModes": { "value": ["cool"] },
+                "airConditionerMode": { "value": "cool" }
+            },
+            "custom.thermostatSetpointControl": {
+                "minimumSetpoint": { "value": 59, "unit": min_unit },
+                "maximumSetpoint": { "value": 86, "unit": max_unit }
+            }
+        }))
+        .unwrap();
+        let device = SamsungAirConditioner::from_status(
+            server.client(),
+            "device".to_string(),
+            "main".to_string(),
+            "Air conditioner".to_string(),
+            None,
+            None,
+            raw_status,
+        )
+        .unwrap();
+        let sync = serde_json::to_value(device.sync("air_conditioner")).unwrap();
+        (
+            sync["attributes"]["thermostatTemperatureUnit"].clone(),
+            sync["attributes"]["thermostatTemperatureRange"].clone(),
+        )
+    };
+
+    assert_eq!(
+        range("F", "F"),
+        (
+            serde_json::json!("F"),
+            serde_json::json!({ "minThresholdCelsius": 15.0, "maxThresholdCelsius": 30.0 })
+        )
+    );
+    assert_eq!(
+        range("C", "C").1,
+        serde_json::json!({ "minThresholdCelsius": 59.0, "maxThresholdCelsius": 86.0 })
+    );
+    assert_eq!(range("C", "F").1, serde_json::Value::Null);
+}
+
+#[tokio::test]
+async fn test_offline_with_mock_server() {
+    use samsung::mock::{MockServer, FIXTURE_DEVICE_ID};
+
+    let server = MockServer::with_fixtures().await.unwrap();
+    let device = SamsungAirConditioner::with_client(
+        server.client(),
+        FIXTURE_DEVICE_ID.to_string(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    server.add_fixture(
+        &format!("devices/{}/health", FIXTURE_DEVICE_ID),
+        serde_json::json!({ "deviceId": FIXTURE_DEVICE_ID, "state": "OFFLINE" }),
+    );
+    assert!(matches!(
+        device.query().await,
+        Err(Error::DeviceError(google::Error::DeviceOffline))
+    ));
+    assert!(matches!(device.online().await, Ok(Some(false))));
+}
+
+#[tokio::test]
+async fn test_health_with_mock_server() {
+    use samsung::mock::{MockServer, FIXTURE_DEVICE_ID};
+
+    let server = MockServer::with_fixtures().await.unwrap();
+    let device = SamsungAirConditioner::with_client(
+        server.client(),
+        FIXTURE_DEVICE_ID.to_string(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    assert!(matches!(device.online().await, Ok(Some(true))));
+
+    // the health read by the sample is reused until the next one
+    server.add_fixture(
+        &format!("devices/{}/health", FIXTURE_DEVICE_ID),
+        serde_json::json!({ "deviceId": FIXTURE_DEVICE_ID, "state": "OFFLINE" }),
+    );
+    assert!(device.query().await.is_ok());
+
+    // a health which can't be read doesn't fail the query
+    *device.health.lock().unwrap() = None;
+    server.add_fixture(
+        &format!("devices/{}/health", FIXTURE_DEVICE_ID),
+        serde_json::json!({ "deviceId": FIXTURE_DEVICE_ID }),
+    );
+    let states = device.query().await.unwrap();
+    assert!(states
+        .0
+        .iter()
+        .any(|state| matches!(state, google::State::OnOff { .. })));
 }
@@ -0,0 +1,70 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use google_smart_home as google;
+use samsung_smart_things as samsung;
+
+use crate::Error;
+
+/// OAuth sessions by state file. Refresh tokens are rotated on use, so devices sharing
+/// a state file have to share the session as well.
+static OAUTH_CLIENTS: once_cell::sync::Lazy<
+    tokio::sync::Mutex<HashMap<PathBuf, samsung::ApiClient>>,
+> = once_cell::sync::Lazy::new(Default::default);
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(untagged)]
+pub enum SmartThingsCredentials {
+    Token {
+        token: String, // https://account.smartthings.com/tokens
+    },
+    OAuth {
+        oauth: SmartThingsOAuthConfig,
+    },
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct SmartThingsOAuthConfig {
+    #[serde(flatten)]
+    pub app: samsung::OAuthApp,
+    /// written by `smart-things-authorize` and updated on every token refresh
+    pub state_file: PathBuf,
+}
+
+impl SmartThingsCredentials {
+    pub async fn create_client(&self) -> anyhow::Result<samsung::ApiClient> {
+        Ok(match self {
+            SmartThingsCredentials::Token { token } => samsung::ApiClient::new(token),
+            SmartThingsCredentials::OAuth { oauth } => {
+                let mut clients = OAUTH_CLIENTS.lock().await;
+                if let Some(client) = clients.get(&oauth.state_file) {
+                    client.clone()
+                } else {
+                    let session =
+                        samsung::OAuthSession::load(oauth.app.clone(), oauth.state_file.clone())
+                            .await?;
+                    let client = samsung::ApiClient::with_oauth(session);
+                    clients.insert(oauth.state_file.clone(), client.clone());
+                    client
+                }
+            }
+        })
+    }
+}
+
+impl From<samsung::Error> for Error {
+    fn from(e: samsung::Error) -> Self {
+        let google_error = match e.inner() {
+            samsung::Error::Unauthorized(_)
+            | samsung::Error::Forbidden(_)
+            | samsung::Error::OAuthError(_) => google::Error::AuthFailure,
+            samsung::Error::NotFound(_) => google::Error::DeviceNotFound,
+            samsung::Error::CommandFailed(_) => google::Error::HardError,
+            samsung::Error::CommandNotConfirmed => google::Error::TransientError,
+            e if e.is_transient() => google::Error::TransientError,
+            _ => return Error::ServerError(anyhow::Error::new(e)),
+        };
+        log::error!("SmartThings request failed - {}", &e);
+
+        Error::DeviceError(google_error)
+    }
+}
@@ -0,0 +1,250 @@
+use google_smart_home as google;
+use samsung_smart_things as samsung;
+
+use super::{HomeDevice, SmartThingsCredentials};
+use crate::Error;
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct SamsungSmartThingsAutomationsConfig {
+    #[serde(flatten)]
+    pub credentials: SmartThingsCredentials,
+    pub location_id: String,
+    /// scene or rule ids or names to import. every one is imported when empty
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// scene or rule ids or names to skip
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl SamsungSmartThingsAutomationsConfig {
+    fn accepts(&self, id: &str, name: &str) -> bool {
+        let matches = |filter: &String| filter == id || filter == name;
+
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+/// Scene made in the SmartThings app, activated as a Google scene
+pub struct SamsungSmartThingsScene {
+    client: samsung::ApiClient,
+    scene_id: String,
+    name: String,
+}
+
+/// Rule made in the SmartThings app, enabled and disabled as a switch
+pub struct SamsungSmartThingsRule {
+    client: samsung::ApiClient,
+    location_id: String,
+    rule_id: String,
+    name: String,
+}
+
+/// Import scenes and rules of the location, named `{key}_scene_{scene_id}` and
+/// `{key}_rule_{rule_id}`.
+pub async fn import_smart_things_automations(
+    key: &str,
+    config: SamsungSmartThingsAutomationsConfig,
+) -> anyhow::Result<Vec<(String, Box<dyn HomeDevice + Send + Sync>)>> {
+    let client = config.credentials.create_client().await?;
+
+    let scenes = client
+        .list_scenes(Some(&config.location_id))
+        .await?
+        .into_iter()
+        .filter(|scene| config.accepts(&scene.scene_id, &scene.scene_name))
+        .map(|scene| {
+            log::trace!("import SmartThings scene {}", &scene.scene_name);
+            (
+                format!("{}_scene_{}", key, &scene.scene_id),
+                Box::new(SamsungSmartThingsScene {
+                    client: client.clone(),
+                    scene_id: scene.scene_id,
+                    name: scene.scene_name,
+                }) as Box<dyn HomeDevice + Send + Sync>,
+            )
+        });
+    let rules = client
+        .list_rules(&config.location_id)
+        .await?
+        .into_iter()
+        .filter(|rule| config.accepts(&rule.id, &rule.name))
+        .map(|rule| {
+            log::trace!("import SmartThings rule {}", &rule.name);
+            (
+                format!("{}_rule_{}", key, &rule.id),
+                Box::new(SamsungSmartThingsRule {
+                    client: client.clone(),
+                    location_id: config.location_id.clone(),
+                    rule_id: rule.id,
+                    name: rule.name,
+                }) as Box<dyn HomeDevice + Send + Sync>,
+            )
+        });
+
+    Ok(scenes.chain(rules).collect())
+}
+
+fn device_name(name: &str) -> google::DeviceName {
+    google::DeviceName {
+        default_names: vec![],
+        name: name.to_string(),
+        nicknames: vec![],
+    }
+}
+
+#[async_trait::async_trait]
+impl HomeDevice for SamsungSmartThingsScene {
+    fn sync(&self, global_id: &str) -> google::DeviceWithDetail {
+        google::DeviceWithDetail {
+            basic: google::Device {
+                id: global_id.to_string(),
+                custom_data: Default::default(),
+            },
+            r#type: google::Type::Scene,
+            attributes: google::Attributes(vec![google::Attribute::Scene {
+                scene_reversible: Some(false),
+            }]),
+            traits: vec![google::Trait::Scene],
+            name: device_name(&self.name),
+            will_report_state: false,
+            room_hint: None,
+            device_info: None,
+            other_device_ids: vec![],
+        }
+    }
+
+    async fn query(&self) -> Result<google::States, Error> {
+        Ok(google::States(vec![]))
+    }
+
+    async fn execute(&self, executions: &Vec<google::Command>) -> Result<google::States, Error> {
+        for execution in executions {
+            match google::TraitCommand::from(execution.clone()) {
+                google::TraitCommand::Scene(google::SceneCommand::ActivateScene {
+                    deactivate: Some(true),
+                }) => return Err(Error::DeviceError(google::Error::FunctionNotSupported)),
+                google::TraitCommand::Scene(google::SceneCommand::ActivateScene { .. }) => {
+                    log::info!("execute SmartThings scene {}", &self.name);
+                    self.client.execute_scene(&self.scene_id).await?;
+                }
+                command => {
+                    log::warn!("scene {} doesn't support {:?}", &self.name, command);
+                    return Err(Error::DeviceError(google::Error::FunctionNotSupported));
+                }
+            }
+        }
+
+        Ok(google::States(vec![]))
+    }
+}
+
+#[async_trait::async_trait]
+impl HomeDevice for SamsungSmartThingsRule {
+    fn sync(&self, global_id: &str) -> google::DeviceWithDetail {
+        google::DeviceWithDetail {
+            basic: google::Device {
+                id: global_id.to_string(),
+                custom_data: Default::default(),
+            },
+            r#type: google::Type::Switch,
+            attributes: google::Attributes(vec![]),
+            traits: vec![google::Trait::OnOff],
+            name: device_name(&self.name),
+            will_report_state: false,
+            room_hint: None,
+            device_info: None,
+            other_device_ids: vec![],
+        }
+    }
+
+    async fn query(&self) -> Result<google::States, Error> {
+        let rule = self.client.rule(&self.location_id, &self.rule_id).await?;
+        Ok(google::States(vec![google::State::OnOff {
+            on: Some(rule.is_enabled()),
+        }]))
+    }
+
+    async fn execute(&self, executions: &Vec<google::Command>) -> Result<google::States, Error> {
+        let mut states = Vec::new();
+        for execution in executions {
+            match google::TraitCommand::from(execution.clone()) {
+                google::TraitCommand::OnOff(google::OnOffCommand::OnOff { on }) => {
+                    log::info!("set SmartThings rule {} enabled {}", &self.name, on);
+                    let rule = self
+                        .client
+                        .set_rule_enabled(&self.location_id, &self.rule_id, on)
+                        .await?;
+                    states.push(google::State::OnOff {
+                        on: Some(rule.is_enabled()),
+                    });
+                }
+                command => {
+                    log::warn!("rule {} doesn't support {:?}", &self.name, command);
+                    return Err(Error::DeviceError(google::Error::FunctionNotSupported));
+                }
+            }
+        }
+
+        Ok(google::States(states))
+    }
+}
+
+#[tokio::test]
+async fn test_with_mock_server() {
+    use samsung::mock::MockServer;
+
+    let server = MockServer::with_fixtures().await.unwrap();
+    let client = server.client();
+    let location_id = "6b3d1909-1e1c-43ec-adc2-5f941de4fbf9";
+
+    let scene = client
+        .list_scenes(Some(location_id))
+        .await
+        .unwrap()
+        .remove(0);
+    let scene = SamsungSmartThingsScene {
+        client: client.clone(),
+        scene_id: scene.scene_id,
+        name: scene.scene_name,
+    };
+    assert!(scene
+        .execute(&vec![google::Command::ActivateScene { deactivate: None }])
+        .await
+        .is_ok());
+    assert_eq!(server.executed_scenes(), vec![scene.scene_id.clone()]);
+    assert!(matches!(
+        scene
+            .execute(&vec![google::Command::ActivateScene {
+                deactivate: Some(true)
+            }])
+            .await,
+        Err(Error::DeviceError(google::Error::FunctionNotSupported))
+    ));
+
+    let rule = client.list_rules(location_id).await.unwrap().remove(0);
+    let rule = SamsungSmartThingsRule {
+        client,
+        location_id: location_id.to_string(),
+        rule_id: rule.id,
+        name: rule.name,
+    };
+    match rule
+        .execute(&vec![google::Command::OnOff { on: false }])
+        .await
+    {
+        Ok(states) => assert!(matches!(
+            &states.0[..],
+            [google::State::OnOff { on: Some(false) }]
+        )),
+        Err(_) => panic!("execute failed"),
+    }
+    match rule.query().await {
+        Ok(states) => assert!(matches!(
+            &states.0[..],
+            [google::State::OnOff { on: Some(false) }]
+        )),
+        Err(_) => panic!("query failed"),
+    }
+}
@@ -0,0 +1,125 @@
+use std::{collections::HashMap, time::Duration};
+
+use samsung_smart_things as samsung;
+
+use super::{HomeDevice, SamsungAirConditioner, SmartThingsCredentials};
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct SamsungSmartThingsLocationConfig {
+    #[serde(flatten)]
+    pub credentials: SmartThingsCredentials,
+    pub location_id: String,
+    /// device ids or labels to import. every device is imported when empty
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// device ids or labels to skip
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub confirm_timeout_secs: Option<u64>,
+    /// other components exposed as their own devices when a device has them,
+    /// named `{key}_{device_id}_{component}`
+    #[serde(default)]
+    pub components: Vec<String>,
+}
+
+impl SamsungSmartThingsLocationConfig {
+    fn accepts(&self, descriptor: &samsung::DeviceDescriptor) -> bool {
+        let matches =
+            |filter: &String| filter == &descriptor.device_id || filter == &descriptor.label;
+
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+/// Import every supported device in the location, named `{key}_{device_id}`.
+pub async fn import_smart_things_location(
+    key: &str,
+    config: SamsungSmartThingsLocationConfig,
+) -> anyhow::Result<Vec<(String, Box<dyn HomeDevice + Send + Sync>)>> {
+    let client = config.credentials.create_client().await?;
+
+    let rooms: HashMap<_, _> = client
+        .list_rooms(&config.location_id)
+        .await?
+        .into_iter()
+        .map(|room| (room.room_id, room.name))
+        .collect();
+    let descriptors = client.list_devices(Some(&config.location_id)).await?;
+
+    let devices = descriptors.into_iter().filter_map(|descriptor| {
+        if !config.accepts(&descriptor) {
+            log::debug!("skip filtered SmartThings device {}", &descriptor.label);
+            return None;
+        }
+
+        let is_air_conditioner = descriptor
+            .component("main")
+            .map(|main| main.has_capability(samsung::Capability::AirConditionerMode))
+            .unwrap_or(false);
+        if !is_air_conditioner {
+            log::info!("skip unsupported SmartThings device {}", &descriptor.label);
+            return None;
+        }
+
+        let client = client.clone();
+        let confirm_timeout = config.confirm_timeout_secs.map(Duration::from_secs);
+        let id = format!("{}_{}", key, &descriptor.device_id);
+        let components: Vec<_> = std::iter::once("main".to_string())
+            .chain(
+                config
+                    .components
+                    .iter()
+                    .filter(|component| descriptor.component(component).is_some())
+                    .cloned(),
+            )
+            .collect();
+        let room_hint = descriptor
+            .room_id
+            .as_ref()
+            .and_then(|room_id| rooms.get(room_id))
+            .cloned();
+        Some(async move {
+            log::trace!("create samsung air conditioner {}", &id);
+            let devices = match SamsungAirConditioner::with_components(
+                client,
+                descriptor.device_id,
+                &components,
+                room_hint,
+                confirm_timeout,
+            )
+            .await
+            {
+                Ok(devices) => devices,
+                Err(e) => {
+                    // the other devices of the location still work
+                    log::error!(
+                        "skip SmartThings device {} which failed to load - {:?}",
+                        &descriptor.label,
+                        e
+                    );
+                    return Vec::new();
+                }
+            };
+            components
+                .iter()
+                .zip(devices)
+                .map(|(component, device)| {
+                    let id = if component == "main" {
+                        id.clone()
+                    } else {
+                        format!("{}_{}", &id, component)
+                    };
+                    (id, Box::new(device) as Box<dyn HomeDevice + Send + Sync>)
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+
+    Ok(futures::future::join_all(devices)
+        .await
+        .into_iter()
+        .flatten()
+        .collect())
+}
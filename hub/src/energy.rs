@@ -0,0 +1,328 @@
+//! Power and energy consumption of devices, served in `/metrics` and as daily and
+//! monthly rollups under `/energy`
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+
+use axum::{extract::Path, http::StatusCode, response::IntoResponse, Extension, Json};
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+
+use crate::metrics::escape_label;
+
+/// Daily totals older than this are dropped, which keeps a year of monthly rollups
+const RETENTION_DAYS: usize = 400;
+/// Format of days in rollups and in the state file
+const DAY_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(serde::Deserialize)]
+pub struct EnergyConfig {
+    /// file daily totals are kept in across restarts
+    pub state_file: PathBuf,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EnergyReading {
+    /// current draw
+    pub power_watts: Option<f64>,
+    /// meter reading, accumulated since the device was installed
+    pub energy_kwh: Option<f64>,
+}
+
+#[derive(Default)]
+struct DeviceHistory {
+    latest: EnergyReading,
+    /// meter reading the next consumption is counted from
+    last_energy_kwh: Option<f64>,
+    daily_kwh: BTreeMap<NaiveDate, f64>,
+}
+
+/// What the state file keeps of a `DeviceHistory`
+#[derive(serde::Serialize, serde::Deserialize)]
+struct DeviceState {
+    last_energy_kwh: Option<f64>,
+    /// by `DAY_FORMAT`
+    daily_kwh: BTreeMap<String, f64>,
+}
+
+impl From<&DeviceHistory> for DeviceState {
+    fn from(history: &DeviceHistory) -> Self {
+        Self {
+            last_energy_kwh: history.last_energy_kwh,
+            daily_kwh: history
+                .daily_kwh
+                .iter()
+                .map(|(day, energy_kwh)| (day.format(DAY_FORMAT).to_string(), *energy_kwh))
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<DeviceState> for DeviceHistory {
+    type Error = chrono::ParseError;
+
+    fn try_from(state: DeviceState) -> Result<Self, Self::Error> {
+        Ok(Self {
+            latest: Default::default(),
+            last_energy_kwh: state.last_energy_kwh,
+            daily_kwh: state
+                .daily_kwh
+                .into_iter()
+                .map(|(day, energy_kwh)| {
+                    Ok((NaiveDate::parse_from_str(&day, DAY_FORMAT)?, energy_kwh))
+                })
+                .collect::<Result<_, Self::Error>>()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Daily,
+    Monthly,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Rollup {
+    /// `2022-07-01` for a day, `2022-07` for a month
+    pub period: String,
+    pub energy_kwh: f64,
+}
+
+/// History of every device reporting energy, kept in `state_file` across restarts when
+/// there is one
+#[derive(Default)]
+pub struct EnergyHistory {
+    devices: Mutex<HashMap<String, DeviceHistory>>,
+    state_file: Option<PathBuf>,
+}
+
+impl EnergyHistory {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// History saved in `state_file`, or an empty one before the first save
+    pub fn load(state_file: PathBuf) -> anyhow::Result<Self> {
+        let devices = match std::fs::read(&state_file) {
+            Ok(raw) => serde_json::from_slice::<HashMap<String, DeviceState>>(&raw)
+                .context("Failed to parse energy state file")?
+                .into_iter()
+                .map(|(device_id, state)| Ok((device_id, state.try_into()?)))
+                .collect::<Result<_, chrono::ParseError>>()
+                .context("Failed to parse energy state file")?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e).context("Failed to read energy state file"),
+        };
+        Ok(Self {
+            devices: Mutex::new(devices),
+            state_file: Some(state_file),
+        })
+    }
+
+    /// Write the history to a temporary file first, so an interrupted write never loses it.
+    /// Blocks on the file system.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let state_file = match &self.state_file {
+            Some(state_file) => state_file,
+            None => return Ok(()),
+        };
+        let state: HashMap<_, _> = self
+            .devices
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(device_id, history)| (device_id.clone(), DeviceState::from(history)))
+            .collect();
+        let temp_path = state_file.with_extension("tmp");
+        std::fs::write(&temp_path, serde_json::to_vec(&state)?)?;
+        std::fs::rename(&temp_path, state_file)?;
+        Ok(())
+    }
+
+    /// Count consumption since the previous reading of `device_id` into the day of `at`
+    pub fn record(&self, device_id: &str, reading: EnergyReading, at: DateTime<Local>) {
+        let mut devices = self.devices.lock().unwrap();
+        let history = devices.entry(device_id.to_string()).or_default();
+
+        if let Some(energy_kwh) = reading.energy_kwh {
+            if let Some(last_energy_kwh) = history.last_energy_kwh {
+                // the meter restarts from zero when the device is reset
+                let consumed = if energy_kwh >= last_energy_kwh {
+                    energy_kwh - last_energy_kwh
+                } else {
+                    energy_kwh
+                };
+                *history
+                    .daily_kwh
+                    .entry(at.date().naive_local())
+                    .or_default() += consumed;
+                while history.daily_kwh.len() > RETENTION_DAYS {
+                    let oldest = *history.daily_kwh.keys().next().unwrap();
+                    history.daily_kwh.remove(&oldest);
+                }
+            }
+            history.last_energy_kwh = Some(energy_kwh);
+        }
+        history.latest = reading;
+    }
+
+    /// Consumption of `device_id` per period, oldest first
+    pub fn rollups(&self, device_id: &str, period: Period) -> Option<Vec<Rollup>> {
+        let devices = self.devices.lock().unwrap();
+        let history = devices.get(device_id)?;
+
+        Some(match period {
+            Period::Daily => history
+                .daily_kwh
+                .iter()
+                .map(|(day, energy_kwh)| Rollup {
+                    period: day.format(DAY_FORMAT).to_string(),
+                    energy_kwh: *energy_kwh,
+                })
+                .collect(),
+            Period::Monthly => {
+                let mut monthly = BTreeMap::<(i32, u32), f64>::new();
+                for (day, energy_kwh) in &history.daily_kwh {
+                    *monthly.entry((day.year(), day.month())).or_default() += energy_kwh;
+                }
+                monthly
+                    .into_iter()
+                    .map(|((year, month), energy_kwh)| Rollup {
+                        period: format!("{:04}-{:02}", year, month),
+                        energy_kwh,
+                    })
+                    .collect()
+            }
+        })
+    }
+
+    /// Latest readings in Prometheus text exposition format
+    pub fn metrics(&self) -> String {
+        let devices = self.devices.lock().unwrap();
+        let mut device_ids: Vec<_> = devices.keys().collect();
+        device_ids.sort_unstable();
+
+        let mut ret = String::new();
+        ret.push_str("# HELP hub_power_watts Current power draw of the device\n");
+        ret.push_str("# TYPE hub_power_watts gauge\n");
+        for device_id in &device_ids {
+            if let Some(power_watts) = devices[*device_id].latest.power_watts {
+                let _ = writeln!(
+                    ret,
+                    "hub_power_watts{{device=\"{}\"}} {}",
+                    escape_label(device_id),
+                    power_watts
+                );
+            }
+        }
+        ret.push_str("# HELP hub_energy_kwh_total Energy meter reading of the device\n");
+        ret.push_str("# TYPE hub_energy_kwh_total counter\n");
+        for device_id in &device_ids {
+            if let Some(energy_kwh) = devices[*device_id].latest.energy_kwh {
+                let _ = writeln!(
+                    ret,
+                    "hub_energy_kwh_total{{device=\"{}\"}} {}",
+                    escape_label(device_id),
+                    energy_kwh
+                );
+            }
+        }
+
+        ret
+    }
+}
+
+pub async fn handle_rollups(
+    Extension(history): Extension<Arc<EnergyHistory>>,
+    Path((device_id, period)): Path<(String, Period)>,
+) -> Result<Json<Vec<Rollup>>, impl IntoResponse> {
+    history
+        .rollups(&device_id, period)
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "No energy history of the device"))
+}
+
+#[test]
+fn test_rollups() {
+    use chrono::TimeZone;
+
+    let history = EnergyHistory::new();
+    let reading = |energy_kwh| EnergyReading {
+        power_watts: Some(800.0),
+        energy_kwh: Some(energy_kwh),
+    };
+    let at = |month, day, hour| Local.ymd(2022, month, day).and_hms(hour, 0, 0);
+
+    history.record("ac", reading(100.0), at(6, 30, 23));
+    history.record("ac", reading(101.5), at(7, 1, 1));
+    history.record("ac", reading(103.0), at(7, 1, 12));
+    history.record("ac", reading(104.0), at(7, 2, 12));
+    // reset meter
+    history.record("ac", reading(0.5), at(7, 2, 13));
+
+    assert_eq!(
+        history.rollups("ac", Period::Daily),
+        Some(vec![
+            Rollup {
+                period: "2022-07-01".to_string(),
+                energy_kwh: 3.0,
+            },
+            Rollup {
+                period: "2022-07-02".to_string(),
+                energy_kwh: 1.5,
+            },
+        ])
+    );
+    assert_eq!(
+        history.rollups("ac", Period::Monthly),
+        Some(vec![Rollup {
+            period: "2022-07".to_string(),
+            energy_kwh: 4.5,
+        }])
+    );
+    assert_eq!(history.rollups("heater", Period::Daily), None);
+
+    let metrics = history.metrics();
+    assert!(metrics.contains("hub_power_watts{device=\"ac\"} 800\n"));
+    assert!(metrics.contains("hub_energy_kwh_total{device=\"ac\"} 0.5\n"));
+}
+
+#[test]
+fn test_state_file() {
+    use chrono::TimeZone;
+
+    let state_file =
+        std::env::temp_dir().join(format!("hub_energy_test_{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&state_file);
+    let reading = |energy_kwh| EnergyReading {
+        power_watts: None,
+        energy_kwh: Some(energy_kwh),
+    };
+    let at = |hour| Local.ymd(2022, 7, 1).and_hms(hour, 0, 0);
+
+    let history = EnergyHistory::load(state_file.clone()).unwrap();
+    assert_eq!(history.rollups("ac", Period::Daily), None);
+    history.record("ac", reading(100.0), at(1));
+    history.record("ac", reading(101.5), at(2));
+    history.save().unwrap();
+
+    // consumption is counted from the meter reading saved before the restart
+    let history = EnergyHistory::load(state_file.clone()).unwrap();
+    history.record("ac", reading(102.0), at(3));
+    assert_eq!(
+        history.rollups("ac", Period::Daily),
+        Some(vec![Rollup {
+            period: "2022-07-01".to_string(),
+            energy_kwh: 2.0,
+        }])
+    );
+
+    std::fs::remove_file(&state_file).unwrap();
+}
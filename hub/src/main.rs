@@ -1,20 +1,32 @@
-use std::{collections::HashMap, ops::Deref, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use anyhow::Context;
-use axum::{http::StatusCode, routing::post, Extension, Json, Router};
+use axum::{
+    http::StatusCode,
+    routing::{get, post},
+    Extension, Router,
+};
 
 use device::HomeDevice;
 use fallible_iterator::FallibleIterator;
 use google_smart_home::{
-    ExecuteRequest, ExecuteResponse, Intent, QueryRequest, QueryResponse, Response,
-    ResponsePayload, ResponseWithPayload, StateOrError, StatusReport, SyncResponse,
+    server::{Fulfillment, FulfillmentError},
+    Challenge, Command, Device, DeviceStatus, IdentifiedDevice, IdentifyRequest, IdentifyResponse,
+    ReachableDevice, ReachableDevicesRequest, ReachableDevicesResponse, SyncResponse,
 };
 
+mod challenge;
 mod device;
+mod energy;
+mod metrics;
+mod smart_app;
 
+#[derive(Debug)]
 pub enum Error {
     ClientError(anyhow::Error),
     ServerError(anyhow::Error),
+    /// Failure of a single device, reported to Google as its `errorCode`
+    DeviceError(google_smart_home::Error),
 }
 
 impl Error {
@@ -71,6 +83,10 @@ impl axum::response::IntoResponse for Error {
         let (code, e) = match self {
             Error::ClientError(e) => (StatusCode::BAD_REQUEST, e),
             Error::ServerError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e),
+            Error::DeviceError(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                anyhow::anyhow!("Unhandled device error - {:?}", e),
+            ),
         };
         log::error!("Error thrown - {:?}", e);
 
@@ -78,169 +94,223 @@ impl axum::response::IntoResponse for Error {
     }
 }
 
-async fn handle_sync(
-    devices: Arc<HashMap<String, Arc<Box<dyn HomeDevice + Send + Sync>>>>,
-) -> Result<SyncResponse, Error> {
-    log::trace!("handle sync begin");
-    let devices = devices.iter().map(|(id, device)| device.sync(id)).collect();
-    log::trace!(
-        "handle sync end - {}",
-        &serde_json::to_string(&devices).unwrap()
-    );
-    Ok(SyncResponse {
-        agent_user_id: "perlmint_home".to_string(),
-        devices,
-    })
+impl From<Error> for FulfillmentError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::ClientError(e) => FulfillmentError::BadRequest(e.into()),
+            Error::ServerError(e) => FulfillmentError::Internal(e.into()),
+            Error::DeviceError(e) => FulfillmentError::Device(e),
+        }
+    }
 }
 
-async fn handle_query(
+/// Executions taking longer are answered as PENDING, well before Google gives up
+const EXECUTE_PENDING_AFTER: Duration = Duration::from_secs(5);
+
+/// Devices of the hub, as Google sees them
+struct Home {
     devices: Arc<HashMap<String, Arc<Box<dyn HomeDevice + Send + Sync>>>>,
-    query: QueryRequest,
-) -> Result<QueryResponse, Error> {
-    log::trace!("handle query begin");
-    let devices = fallible_iterator::convert(
-        futures::future::join_all(query.devices.into_iter().map(|device| {
-            let home_device = devices.get(&device.id).cloned();
-            let device_id = device.id.clone();
-            tokio::spawn({
-                Box::pin(async move {
-                    let state = if let Some(home_device) = home_device {
-                        StateOrError::State(home_device.query().await?)
-                    } else {
-                        log::warn!("device {} is not found", &device_id);
-                        StateOrError::Error(google_smart_home::Error::DeviceNotFound)
-                    };
-
-                    Ok((device_id, state))
-                })
-            })
-        }))
-        .await
-        .into_iter()
-        .map(|o| match o {
-            Ok(Ok(v)) => Ok(v),
-            Ok(Err(e)) => Err(e),
-            Err(e) => Err(Error::server_error(e)),
-        }),
-    )
-    .collect()?;
-    log::trace!(
-        "handle query end - {}",
-        &serde_json::to_string(&devices).unwrap()
-    );
+    challenges: challenge::Challenges,
+}
 
-    Ok(QueryResponse { devices })
+impl Home {
+    fn device(
+        &self,
+        id: &str,
+    ) -> Result<&Arc<Box<dyn HomeDevice + Send + Sync>>, FulfillmentError> {
+        self.devices.get(id).ok_or_else(|| {
+            log::warn!("device {} is not found", id);
+            FulfillmentError::Device(google_smart_home::Error::DeviceNotFound)
+        })
+    }
 }
 
-async fn handle_execute(
-    devices: Arc<HashMap<String, Arc<Box<dyn HomeDevice + Send + Sync>>>>,
-    execute: ExecuteRequest,
-) -> Result<ExecuteResponse, Error> {
-    log::trace!("handle execute begin");
-    let commands = fallible_iterator::convert(
-        futures::future::join_all(execute.commands.into_iter().flat_map(|mut command| {
-            let execution = Arc::new(command.execution.drain(0..).collect::<Vec<_>>());
-            command.devices.into_iter().map({
-                let execution = execution.clone();
-                let devices = devices.clone();
-                move |device| {
-                    let home_device = devices.get(&device.id).cloned();
-                    let device_id = device.id.clone();
-                    tokio::spawn({
-                        let execution = execution.clone();
-                        Box::pin(async move {
-                            let state = if let Some(home_device) = home_device {
-                                StateOrError::State(home_device.execute(execution.deref()).await?)
-                            } else {
-                                log::warn!("device {} is not found", &device_id);
-                                StateOrError::Error(google_smart_home::Error::DeviceNotFound)
-                            };
-
-                            Ok(StatusReport {
-                                ids: vec![device_id],
-                                status: state,
-                            })
-                        })
-                    })
-                }
-            })
-        }))
-        .await
-        .into_iter()
-        .map(|o| match o {
-            Ok(Ok(v)) => Ok(v),
-            Ok(Err(e)) => Err(e),
-            Err(e) => Err(Error::server_error(e)),
-        }),
-    )
-    .collect()?;
-    log::trace!(
-        "handle execute end - {}",
-        &serde_json::to_string(&commands).unwrap()
-    );
+#[async_trait::async_trait]
+impl Fulfillment for Home {
+    async fn sync(&self) -> Result<SyncResponse, FulfillmentError> {
+        let devices = self
+            .devices
+            .iter()
+            .map(|(id, device)| device.sync(id))
+            .collect();
+        log::trace!("sync - {}", &serde_json::to_string(&devices).unwrap());
+        Ok(SyncResponse {
+            agent_user_id: "perlmint_home".to_string(),
+            devices,
+        })
+    }
 
-    Ok(ExecuteResponse { commands })
-}
+    async fn query(&self, device: &Device) -> Result<DeviceStatus, FulfillmentError> {
+        Ok(self.device(&device.id)?.query().await?.into())
+    }
 
-async fn handle_fulfillment(
-    Extension(devices): Extension<Arc<HashMap<String, Arc<Box<dyn HomeDevice + Send + Sync>>>>>,
-    Json(mut request): Json<google_smart_home::Request>,
-) -> Result<Json<google_smart_home::Response>, Error> {
-    log::trace!("{:?}", request);
-    let payload: ResponsePayload = {
-        let input = request.inputs.remove(0);
-
-        match input {
-            Intent::Sync => ResponsePayload::Sync(handle_sync(devices.clone()).await?),
-            Intent::Disconnect => {
-                return Ok(Json(Response::EmptyResponse));
-            }
-            Intent::Query(query) => {
-                ResponsePayload::Query(handle_query(devices.clone(), query).await?)
+    /// Slow executions, like SmartThings commands to a sleeping device, are reported as
+    /// PENDING and left running
+    async fn execute(
+        &self,
+        device: &Device,
+        executions: &[Command],
+        challenge: Option<&Challenge>,
+    ) -> Result<DeviceStatus, FulfillmentError> {
+        let home_device = self.device(&device.id)?.clone();
+        self.challenges
+            .check(&device.id, executions, challenge)
+            .await?;
+
+        let executions = executions.to_vec();
+        let mut task = tokio::spawn(async move { home_device.execute(&executions).await });
+        match tokio::time::timeout(EXECUTE_PENDING_AFTER, &mut task).await {
+            Ok(Ok(result)) => Ok(result?.into()),
+            // a device whose task panicked, reported as `fulfill` reports its own tasks
+            Ok(Err(e)) => {
+                log::error!("execution of {} failed - {}", &device.id, e);
+                Err(FulfillmentError::Device(
+                    google_smart_home::Error::UnknownError,
+                ))
             }
-            Intent::Execute(execute) => {
-                ResponsePayload::Execute(handle_execute(devices.clone(), execute).await?)
+            Err(_) => {
+                log::info!("execution of {} is pending", &device.id);
+                let device_id = device.id.clone();
+                tokio::spawn(async move {
+                    if let Ok(Err(e)) = task.await {
+                        log::error!(
+                            "pending execution of {} failed - {:?}",
+                            device_id,
+                            FulfillmentError::from(e)
+                        );
+                    }
+                });
+                Ok(DeviceStatus::pending())
             }
         }
-    };
+    }
+
+    /// Plant LED boards are proxies of their lights. `local-home` answers the scans on the LAN
+    /// itself, so this is only reached when a Local Home app forwards them to the hub.
+    async fn identify(
+        &self,
+        request: &IdentifyRequest,
+        _devices: &[Device],
+    ) -> Result<IdentifyResponse, FulfillmentError> {
+        let board = device::PlantLed::board_from_scan_data(&request.device).ok_or_else(|| {
+            log::warn!("unknown device is scanned - {:?}", &request.device);
+            FulfillmentError::Device(google_smart_home::Error::DeviceNotFound)
+        })?;
+        Ok(IdentifyResponse {
+            device: IdentifiedDevice {
+                id: board,
+                is_proxy: Some(true),
+                is_local_only: Some(true),
+                verification_id: None,
+                device_info: None,
+            },
+        })
+    }
 
-    Ok(Json(Response::ResponseWithPayload(ResponseWithPayload {
-        request_id: request.request_id,
-        payload,
-    })))
+    /// Lights of the board, by the address the board reported when scanned
+    async fn reachable_devices(
+        &self,
+        request: &ReachableDevicesRequest,
+    ) -> Result<ReachableDevicesResponse, FulfillmentError> {
+        let prefix = format!("{}/", &request.device.proxy_device.id);
+        let devices = self
+            .devices
+            .values()
+            .filter_map(|device| device.local_id())
+            .filter(|local_id| local_id.starts_with(&prefix))
+            .map(|local_id| ReachableDevice {
+                verification_id: local_id.to_string(),
+            })
+            .collect();
+        Ok(ReachableDevicesResponse { devices })
+    }
 }
 
 #[derive(serde::Deserialize)]
-#[serde(transparent)]
-pub struct HubConfig(pub HashMap<String, device::DeviceConfigs>);
+pub struct HubConfig {
+    /// webhook SmartApp served at `/smartthings`, to learn changes of SmartThings devices without polling
+    #[serde(default)]
+    pub smart_things_app: Option<smart_app::SmartAppConfig>,
+    /// challenges asked before executing commands, by device id and command name
+    #[serde(default)]
+    pub challenges: challenge::ChallengeConfigs,
+    /// energy history is lost on restart without it
+    #[serde(default)]
+    pub energy: Option<energy::EnergyConfig>,
+    #[serde(flatten)]
+    pub devices: HashMap<String, device::DeviceConfigs>,
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     env_logger::init();
 
-    let devices: HashMap<_, _> = {
+    let config: HubConfig = {
         let path = std::env::var("HUB_CONFIG").expect("HUB_CONFIG env is mandatory");
         let raw_config = std::fs::read(path).context("Failed to read specified config file")?;
-        let config: HubConfig =
-            toml::from_slice(&raw_config).context("Failed to parse config file")?;
+        toml::from_slice(&raw_config).context("Failed to parse config file")?
+    };
 
+    let devices: HashMap<_, _> = {
         fallible_iterator::convert(
-            futures::future::join_all(config.0.into_iter().map(|(key, config)| async {
+            futures::future::join_all(
                 config
-                    .create_device()
-                    .await
-                    .map(|device| (key, Arc::new(device)))
-            }))
+                    .devices
+                    .into_iter()
+                    .map(|(key, config)| config.create_devices(key)),
+            )
             .await
             .into_iter(),
         )
-        .collect()?
+        .collect::<Vec<_>>()?
+        .into_iter()
+        .flatten()
+        .map(|(key, device)| (key, Arc::new(device)))
+        .collect()
     };
 
-    let app = Router::new()
-        .route("/fulfillment", post(handle_fulfillment))
+    for (id, device) in devices.iter() {
+        device
+            .sync(id)
+            .validate()
+            .with_context(|| format!("Device {} doesn't match the Smart Home schema", id))?;
+    }
+
+    let devices = Arc::new(devices);
+    let challenges = challenge::Challenges::new(config.challenges)?;
+    let energy_history = Arc::new(match config.energy {
+        Some(energy_config) => energy::EnergyHistory::load(energy_config.state_file)?,
+        None => energy::EnergyHistory::new(),
+    });
+    let health = Arc::new(metrics::Health::new());
+    tokio::spawn(metrics::sample_periodically(
+        devices.clone(),
+        energy_history.clone(),
+        health.clone(),
+    ));
+
+    let mut app = Router::new()
+        .route(
+            "/fulfillment",
+            google_smart_home::server::service(Arc::new(Home {
+                devices: devices.clone(),
+                challenges,
+            })),
+        )
+        .route("/metrics", get(metrics::handle_metrics))
+        .route("/energy/:device_id/:period", get(energy::handle_rollups));
+    if let Some(smart_app_config) = config.smart_things_app {
+        app = app
+            .route("/smartthings", post(smart_app::handle_lifecycle))
+            .layer(Extension(Arc::new(smart_app::SmartApp::new(
+                smart_app_config,
+            ))));
+    }
+    let app = app
         .layer(tower_http::trace::TraceLayer::new_for_http())
-        .layer(Extension(Arc::new(devices)));
+        .layer(Extension(energy_history))
+        .layer(Extension(health))
+        .layer(Extension(devices));
 
     let signal = {
         #[cfg(target_os = "linux")]
@@ -267,3 +337,171 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+struct Light {
+    local_id: Option<&'static str>,
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl HomeDevice for Light {
+    fn sync(&self, global_id: &str) -> google_smart_home::DeviceWithDetail {
+        google_smart_home::DeviceWithDetail {
+            basic: Device {
+                id: global_id.to_string(),
+                custom_data: Default::default(),
+            },
+            r#type: google_smart_home::Type::Light,
+            attributes: google_smart_home::Attributes(vec![]),
+            traits: vec![google_smart_home::Trait::OnOff],
+            name: google_smart_home::DeviceName {
+                name: global_id.to_string(),
+                default_names: Default::default(),
+                nicknames: Default::default(),
+            },
+            will_report_state: false,
+            room_hint: None,
+            device_info: None,
+            other_device_ids: vec![],
+        }
+    }
+
+    fn local_id(&self) -> Option<&str> {
+        self.local_id
+    }
+
+    async fn query(&self) -> Result<google_smart_home::States, Error> {
+        Ok(google_smart_home::States(vec![]))
+    }
+
+    /// Turns on or off at once, takes a minute to dim, and panics on anything else
+    async fn execute(&self, executions: &Vec<Command>) -> Result<google_smart_home::States, Error> {
+        match executions.as_slice() {
+            [Command::OnOff { on }] => Ok(google_smart_home::States(vec![
+                google_smart_home::State::OnOff { on: Some(*on) },
+            ])),
+            [Command::BrightnessAbsolute { .. }] => {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(google_smart_home::States(vec![]))
+            }
+            _ => panic!("light doesn't know {:?}", executions),
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_fulfillment() {
+    use google_smart_home::{server::fulfill, server::testing, ScanData, UdpScanData};
+
+    let devices = [
+        ("light", Some("192.168.0.7/0")),
+        ("other_light", Some("192.168.0.8/0")),
+        ("fan", None),
+    ]
+    .into_iter()
+    .map(|(id, local_id)| {
+        let device: Box<dyn HomeDevice + Send + Sync> = Box::new(Light { local_id });
+        (id.to_string(), Arc::new(device))
+    })
+    .collect();
+    let challenges = challenge::Challenges::new(
+        toml::from_str(
+            r#"
+            [light]
+            "action.devices.commands.OnOff" = { type = "ack" }
+            "#,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let home = Arc::new(Home {
+        devices: Arc::new(devices),
+        challenges,
+    });
+    let commands = |request| async {
+        let response = fulfill(&home, request).await.unwrap();
+        serde_json::to_value(&response).unwrap()["payload"]["commands"].clone()
+    };
+
+    assert_eq!(
+        commands(testing::execute_request(
+            &["light"],
+            vec![Command::OnOff { on: true }]
+        ))
+        .await,
+        serde_json::json!([{
+            "ids": ["light"],
+            "status": "ERROR",
+            "errorCode": "challengeNeeded",
+            "challengeNeeded": { "type": "ackNeeded" }
+        }])
+    );
+    assert_eq!(
+        commands(testing::execute_request_with_challenge(
+            &["light"],
+            vec![Command::OnOff { on: true }],
+            Some(Challenge {
+                ack: true,
+                pin: None,
+            }),
+        ))
+        .await,
+        serde_json::json!([{ "ids": ["light"], "status": "SUCCESS", "states": { "on": true } }])
+    );
+
+    // time is paused, so the dimming light is answered PENDING without waiting
+    assert_eq!(
+        commands(testing::execute_request(
+            &["other_light"],
+            vec![Command::BrightnessAbsolute { brightness: 50 }]
+        ))
+        .await,
+        serde_json::json!([{ "ids": ["other_light"], "status": "PENDING" }])
+    );
+
+    assert_eq!(
+        commands(testing::execute_request(
+            &["fan", "other_light"],
+            vec![Command::ThermostatTemperatureSetpoint {
+                thermostat_temperature_setpoint: 22.0,
+            }]
+        ))
+        .await,
+        serde_json::json!([{
+            "ids": ["fan", "other_light"],
+            "status": "ERROR",
+            "errorCode": "unknownError"
+        }])
+    );
+
+    let scan = |data: &str| ScanData {
+        udp_scan_data: Some(UdpScanData {
+            data: data.to_string(),
+        }),
+        ..Default::default()
+    };
+    let response = fulfill(
+        &home,
+        testing::identify_request(scan("3139322e3136382e302e37"), &["light"]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        serde_json::to_value(&response).unwrap()["payload"]["device"],
+        serde_json::json!({ "id": "192.168.0.7", "isProxy": true, "isLocalOnly": true })
+    );
+    assert!(
+        fulfill(&home, testing::identify_request(scan("31393"), &[]))
+            .await
+            .is_err()
+    );
+
+    let response = fulfill(&home, testing::reachable_devices_request("192.168.0.7"))
+        .await
+        .unwrap();
+    assert_eq!(
+        serde_json::to_value(&response).unwrap()["payload"]["devices"],
+        serde_json::json!([{ "verificationId": "192.168.0.7/0" }])
+    );
+}
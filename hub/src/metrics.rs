@@ -0,0 +1,119 @@
+//! Devices sampled periodically, served at `/metrics` in Prometheus text exposition format
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{http::header, response::IntoResponse, Extension};
+use chrono::Local;
+
+use crate::{device::HomeDevice, energy::EnergyHistory};
+
+/// How often energy and health of every device are recorded
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Latest reachability of devices which can tell
+#[derive(Default)]
+pub struct Health {
+    online: Mutex<HashMap<String, bool>>,
+}
+
+impl Health {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn record(&self, device_id: &str, online: bool) {
+        self.online
+            .lock()
+            .unwrap()
+            .insert(device_id.to_string(), online);
+    }
+
+    pub fn metrics(&self) -> String {
+        let online = self.online.lock().unwrap();
+        let mut device_ids: Vec<_> = online.keys().collect();
+        device_ids.sort_unstable();
+
+        let mut ret = String::new();
+        ret.push_str("# HELP hub_device_online Whether the device is reachable\n");
+        ret.push_str("# TYPE hub_device_online gauge\n");
+        for device_id in device_ids {
+            let _ = writeln!(
+                ret,
+                "hub_device_online{{device=\"{}\"}} {}",
+                escape_label(device_id),
+                online[device_id] as u8
+            );
+        }
+
+        ret
+    }
+}
+
+pub fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Record energy and health of every device each `SAMPLE_INTERVAL`, forever. The energy
+/// history is saved after each sample.
+pub async fn sample_periodically(
+    devices: Arc<HashMap<String, Arc<Box<dyn HomeDevice + Send + Sync>>>>,
+    energy: Arc<EnergyHistory>,
+    health: Arc<Health>,
+) {
+    let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+    loop {
+        interval.tick().await;
+        for (id, device) in devices.iter() {
+            match device.online().await {
+                Ok(Some(online)) => health.record(id, online),
+                Ok(None) => {}
+                Err(e) => log::warn!("failed to read health of device {} - {:?}", id, e),
+            }
+            match device.energy().await {
+                Ok(Some(reading)) => energy.record(id, reading, Local::now()),
+                Ok(None) => {}
+                Err(e) => log::warn!("failed to read energy of device {} - {:?}", id, e),
+            }
+        }
+
+        let energy = energy.clone();
+        match tokio::task::spawn_blocking(move || energy.save()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log::warn!("failed to save energy history - {:?}", e),
+            Err(e) => log::warn!("failed to save energy history - {:?}", e),
+        }
+    }
+}
+
+pub async fn handle_metrics(
+    Extension(energy): Extension<Arc<EnergyHistory>>,
+    Extension(health): Extension<Arc<Health>>,
+) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        energy.metrics() + &health.metrics(),
+    )
+}
+
+#[test]
+fn test_health_metrics() {
+    let health = Health::new();
+    health.record("ac", true);
+    health.record("bedroom \"ac\"", false);
+
+    assert_eq!(
+        health.metrics(),
+        "# HELP hub_device_online Whether the device is reachable\n\
+         # TYPE hub_device_online gauge\n\
+         hub_device_online{device=\"ac\"} 1\n\
+         hub_device_online{device=\"bedroom \\\"ac\\\"\"} 0\n"
+    );
+}
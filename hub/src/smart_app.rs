@@ -0,0 +1,228 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap, Method, Uri},
+    Extension, Json,
+};
+use samsung_smart_things as samsung;
+
+use crate::{device::HomeDevice, Error, ErrorWrap};
+
+/// Id of the page setting where devices to receive events from are chosen
+const DEVICES_SETTING: &str = "devices";
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct SmartAppConfig {
+    /// shown while installing the app in the SmartThings app
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// only for local testing, every request from SmartThings is signed
+    #[serde(default)]
+    pub skip_signature_verification: bool,
+}
+
+/// Webhook SmartApp subscribing to events of the chosen devices
+pub struct SmartApp {
+    config: SmartAppConfig,
+    verifier: samsung::SignatureVerifier,
+    http_client: reqwest::Client,
+}
+
+impl SmartApp {
+    pub fn new(config: SmartAppConfig) -> Self {
+        Self {
+            config,
+            verifier: samsung::SignatureVerifier::new(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    fn configuration(&self, data: samsung::ConfigurationData) -> samsung::ConfigurationResponse {
+        match data.phase {
+            samsung::ConfigurationPhase::Initialize => {
+                samsung::ConfigurationResponse::Initialize(samsung::InitializeResponse {
+                    name: self.config.name.clone(),
+                    description: self.config.description.clone(),
+                    id: "hub".to_string(),
+                    permissions: vec![],
+                    first_page_id: "1".to_string(),
+                })
+            }
+            samsung::ConfigurationPhase::Page => {
+                samsung::ConfigurationResponse::Page(samsung::ConfigurationPage {
+                    page_id: "1".to_string(),
+                    name: self.config.name.clone(),
+                    next_page_id: None,
+                    previous_page_id: None,
+                    complete: true,
+                    sections: vec![samsung::Section {
+                        name: "Devices".to_string(),
+                        settings: vec![samsung::Setting::Device {
+                            id: DEVICES_SETTING.to_string(),
+                            name: "Devices to report changes of".to_string(),
+                            description: "Tap to set".to_string(),
+                            required: false,
+                            multiple: true,
+                            capabilities: vec!["switch".to_string()],
+                            permissions: vec!["r".to_string()],
+                        }],
+                    }],
+                })
+            }
+        }
+    }
+
+    /// Replace every subscription of the installed app with ones for the chosen devices
+    async fn subscribe(&self, data: samsung::InstallData) -> Result<(), samsung::Error> {
+        // a token per lifecycle event, so there is nothing to share the limit with
+        let client = samsung::ApiClientBuilder::with_token(&data.auth_token)
+            .unshared()
+            .build()?;
+        let installed_app_id = &data.installed_app.installed_app_id;
+
+        let mut device_ids: Vec<_> = data
+            .installed_app
+            .devices(DEVICES_SETTING)
+            .map(|device| device.device_id.as_str())
+            .collect();
+        device_ids.sort_unstable();
+        device_ids.dedup();
+
+        client.delete_subscriptions(installed_app_id).await?;
+        if !device_ids.is_empty() {
+            client
+                .create_subscription(
+                    installed_app_id,
+                    &samsung::SubscriptionSource::DeviceHealth {
+                        device_health: samsung::DeviceHealthSubscription {
+                            device_ids: device_ids.iter().map(|id| id.to_string()).collect(),
+                            subscription_name: Some("health".to_string()),
+                        },
+                    },
+                )
+                .await?;
+        }
+        for device_id in device_ids {
+            log::info!("subscribe events of SmartThings device {}", device_id);
+            client
+                .create_subscription(
+                    installed_app_id,
+                    &samsung::SubscriptionSource::Device {
+                        device: samsung::DeviceSubscription::all(device_id),
+                    },
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn handle_lifecycle(
+    Extension(app): Extension<Arc<SmartApp>>,
+    Extension(devices): Extension<Arc<HashMap<String, Arc<Box<dyn HomeDevice + Send + Sync>>>>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<samsung::LifecycleResponse>, Error> {
+    if !app.config.skip_signature_verification {
+        let path = uri
+            .path_and_query()
+            .map(|path| path.as_str())
+            .unwrap_or_else(|| uri.path());
+        app.verifier
+            .verify(method.as_str(), path, &headers, &body)
+            .await
+            .map_err(Error::client_error)?;
+    }
+
+    let request: samsung::LifecycleRequest =
+        serde_json::from_slice(&body).map_err(Error::client_error)?;
+    log::trace!("{:?}", request);
+
+    let response = match request {
+        samsung::LifecycleRequest::Ping { ping_data } => samsung::LifecycleResponse::PingData {
+            challenge: ping_data.challenge,
+        },
+        samsung::LifecycleRequest::Confirmation { confirmation_data } => {
+            let confirmation_url = confirmation_data
+                .confirmation_url()
+                .map_err(Error::client_error)?;
+            log::info!(
+                "confirm registration of SmartApp {}",
+                &confirmation_data.app_id
+            );
+            app.http_client
+                .get(confirmation_url)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .server_error()?;
+
+            // SmartThings called the app on its target URL
+            let host = headers
+                .get(header::HOST)
+                .and_then(|host| host.to_str().ok())
+                .ok_or_else(|| Error::ClientError(anyhow::anyhow!("No host header")))?;
+            samsung::LifecycleResponse::TargetUrl(format!("https://{}{}", host, uri.path()))
+        }
+        samsung::LifecycleRequest::Configuration { configuration_data } => {
+            samsung::LifecycleResponse::ConfigurationData(app.configuration(configuration_data))
+        }
+        samsung::LifecycleRequest::Install { install_data } => {
+            app.subscribe(install_data)
+                .await
+                .map_err(Error::server_error)?;
+            samsung::LifecycleResponse::InstallData {}
+        }
+        samsung::LifecycleRequest::Update { update_data } => {
+            app.subscribe(update_data)
+                .await
+                .map_err(Error::server_error)?;
+            samsung::LifecycleResponse::UpdateData {}
+        }
+        samsung::LifecycleRequest::Event { event_data } => {
+            let mirrors = |device_id: &str| -> Vec<_> {
+                devices
+                    .values()
+                    .filter(|device| device.smart_things_device_id() == Some(device_id))
+                    .cloned()
+                    .collect()
+            };
+            for event in event_data.events {
+                match event {
+                    samsung::Event::DeviceEvent { device_event } => {
+                        for device in mirrors(&device_event.device_id) {
+                            device.handle_smart_things_event(&device_event).await;
+                        }
+                    }
+                    samsung::Event::DeviceHealthEvent {
+                        device_health_event,
+                    } => {
+                        for device in mirrors(&device_health_event.device_id) {
+                            device
+                                .handle_smart_things_health(&device_health_event)
+                                .await;
+                        }
+                    }
+                    samsung::Event::Other => {}
+                }
+            }
+            samsung::LifecycleResponse::EventData {}
+        }
+        samsung::LifecycleRequest::Uninstall { .. } => {
+            // subscriptions are removed together with the installed app
+            samsung::LifecycleResponse::UninstallData {}
+        }
+        samsung::LifecycleRequest::Unknown => {
+            return Err(Error::ClientError(anyhow::anyhow!(
+                "Unsupported SmartApp lifecycle"
+            )));
+        }
+    };
+
+    Ok(Json(response))
+}
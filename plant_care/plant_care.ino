@@ -2,17 +2,23 @@
 #include <ESP8266WiFi.h>
 #include <ArduinoOTA.h>
 #define USE_OTA
+#define BOARD_NAME "ESP8266"
 #elif defined(ARDUINO_SAMD_NANO_33_IOT)
 #include <WiFiNINA.h>
 #define USE_WIFI_NINA
+#define BOARD_NAME "Arduino Nano 33 IoT"
 #endif
 #include <WiFiServer.h>
+#include <WiFiUdp.h>
 #include <string>
 
 const char WIFI_SSID[] = "";
 const char WIFI_PASSWORD[] = "";
 const int WIFI_KEY_INDEX = 0;
 
+// answered on GET /version with BOARD_NAME on the next line
+const char FIRMWARE_VERSION[] = "1.1.0";
+
 constexpr int MAX_OUT_POWER = 255;
 constexpr size_t OUT_COUNT = 2;
 constexpr int OUT_INDEXES[OUT_COUNT] = {
@@ -25,6 +31,29 @@ struct State
 
 WiFiServer server(80);
 
+// Local Home scans with a broadcast to DISCOVERY_PORT, answered with the address of the board
+constexpr uint16_t DISCOVERY_PORT = 3311;
+WiFiUDP discovery;
+
+void answerDiscovery()
+{
+    if (discovery.parsePacket() == 0)
+    {
+        return;
+    }
+    while (discovery.available())
+    {
+        discovery.read();
+    }
+
+    IPAddress ip = WiFi.localIP();
+    char address[16];
+    int length = snprintf(address, sizeof(address), "%u.%u.%u.%u", ip[0], ip[1], ip[2], ip[3]);
+    discovery.beginPacket(discovery.remoteIP(), discovery.remotePort());
+    discovery.write((const uint8_t *)address, length);
+    discovery.endPacket();
+}
+
 void printWifiStatus()
 {
     Serial.print("SSID: ");
@@ -59,6 +88,7 @@ void connectWifi()
     }
 
     server.begin();
+    discovery.begin(DISCOVERY_PORT);
 
     // you're connected now, so print out the status:
     printWifiStatus();
@@ -114,6 +144,8 @@ void loop()
         yield();
     }
 
+    answerDiscovery();
+
     WiFiClient client = server.available();
 
     if (client)
@@ -213,10 +245,11 @@ void loop()
             Serial.println(method.data());
             Serial.println(uri.data());
 
+            const bool is_version = method == "GET" && uri == "/version";
             size_t prev_pos = 1;
             size_t idx = 0;
             int out_idx = -1;
-            bool stop = false;
+            bool stop = is_version;
             while (!stop)
             {
                 size_t pos = uri.find('/', prev_pos);
@@ -251,7 +284,11 @@ void loop()
                 idx++;
                 prev_pos = pos + 1;
             }
-            if (out_idx != -1)
+            if (is_version)
+            {
+                response = std::string(FIRMWARE_VERSION) + "\n" + BOARD_NAME;
+            }
+            else if (out_idx != -1)
             {
                 if (out_idx >= OUT_COUNT)
                 {